@@ -0,0 +1,36 @@
+use lib_gb::serial::printer_serial_device::{PagePrinter, PrintedPage};
+use log::{info, error};
+use std::path::Path;
+use crate::ppm_file::write_ppm;
+
+// Saves every printed page as <prefix>_print_<n>.ppm without overwriting earlier prints
+pub struct FilePagePrinter{
+    prefix:String,
+    pages_count:u32
+}
+
+impl FilePagePrinter{
+    pub fn new(prefix:&str)->Self{
+        FilePagePrinter{prefix:String::from(prefix), pages_count:0}
+    }
+
+    fn next_path(&mut self)->String{
+        loop{
+            self.pages_count += 1;
+            let path = format!("{}_print_{}.ppm", self.prefix, self.pages_count);
+            if !Path::new(&path).exists(){
+                return path;
+            }
+        }
+    }
+}
+
+impl PagePrinter for FilePagePrinter{
+    fn print_page(&mut self, page:&PrintedPage){
+        let path = self.next_path();
+        match write_ppm(&path, &page.pixels, page.width, page.height){
+            Ok(())=>info!("printed a page to {}", path),
+            Err(err)=>error!("could not write the printed page to {}: {}", path, err)
+        }
+    }
+}
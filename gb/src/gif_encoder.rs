@@ -0,0 +1,252 @@
+use std::{collections::HashMap, io::{Result, Write}};
+
+const GIF_HEADER:&[u8;6] = b"GIF89a";
+const TRAILER:u8 = 0x3B;
+const EXTENSION_INTRODUCER:u8 = 0x21;
+const GRAPHIC_CONTROL_LABEL:u8 = 0xF9;
+const APPLICATION_LABEL:u8 = 0xFF;
+const IMAGE_SEPARATOR:u8 = 0x2C;
+// the frame is left in place for the next frame to draw over it
+const DISPOSAL_DO_NOT_DISPOSE:u8 = 1 << 2;
+const LOCAL_COLOR_TABLE_FLAG:u8 = 1 << 7;
+const MAX_COLORS:usize = 256;
+const MAX_CODE_SIZE:u8 = 12;
+const MAX_SUB_BLOCK_SIZE:usize = 255;
+
+// Writes an endlessly looping animated gif, every frame has its own color table.
+// Frames with more than 256 colors (only possible on the CGB) are reduced to 3 bits red and green and 2 bits blue.
+pub struct GifEncoder<W:Write>{
+    writer:W,
+    width:u16,
+    height:u16
+}
+
+impl<W:Write> GifEncoder<W>{
+    pub fn new(mut writer:W, width:u16, height:u16)->Result<Self>{
+        writer.write_all(GIF_HEADER)?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        // no global color table, background color 0 and no aspect ratio
+        writer.write_all(&[0, 0, 0])?;
+        // the netscape extension with 0 repetitions (loop forever)
+        writer.write_all(&[EXTENSION_INTRODUCER, APPLICATION_LABEL, 11])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[3, 1, 0, 0, 0])?;
+
+        Ok(GifEncoder{writer, width, height})
+    }
+
+    // The delay is in hundredths of a second
+    pub fn write_frame(&mut self, pixels:&[u32], delay:u16)->Result<()>{
+        let (palette, indices) = Self::build_palette(pixels);
+        // the color table size is 2^(size + 1)
+        let table_size_bits = std::cmp::max(1, (usize::BITS - (palette.len() - 1).leading_zeros()) as u8);
+        let table_size = 1 << table_size_bits;
+
+        self.writer.write_all(&[EXTENSION_INTRODUCER, GRAPHIC_CONTROL_LABEL, 4, DISPOSAL_DO_NOT_DISPOSE])?;
+        self.writer.write_all(&delay.to_le_bytes())?;
+        self.writer.write_all(&[0, 0])?;
+
+        self.writer.write_all(&[IMAGE_SEPARATOR, 0, 0, 0, 0])?;
+        self.writer.write_all(&self.width.to_le_bytes())?;
+        self.writer.write_all(&self.height.to_le_bytes())?;
+        self.writer.write_all(&[LOCAL_COLOR_TABLE_FLAG | (table_size_bits - 1)])?;
+        for i in 0..table_size{
+            let color = palette.get(i).copied().unwrap_or(0);
+            self.writer.write_all(&[(color >> 16) as u8, (color >> 8) as u8, color as u8])?;
+        }
+
+        // the minimum code size is at least 2
+        let min_code_size = std::cmp::max(2, table_size_bits);
+        self.writer.write_all(&[min_code_size])?;
+        let data = lzw_encode(&indices, min_code_size);
+        for block in data.chunks(MAX_SUB_BLOCK_SIZE){
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_all(&[0])
+    }
+
+    pub fn finish(mut self)->Result<()>{
+        self.writer.write_all(&[TRAILER])?;
+        self.writer.flush()
+    }
+
+    // Returns the colors and the index of every pixel
+    fn build_palette(pixels:&[u32])->(Vec<u32>, Vec<u8>){
+        let mut palette = Vec::new();
+        let mut color_indices = HashMap::new();
+        let mut indices = Vec::with_capacity(pixels.len());
+        for pixel in pixels{
+            let index = *color_indices.entry(*pixel).or_insert_with(||{
+                palette.push(*pixel);
+                palette.len() - 1
+            });
+            if index >= MAX_COLORS{
+                return Self::build_rgb332_palette(pixels);
+            }
+            indices.push(index as u8);
+        }
+
+        return (palette, indices);
+    }
+
+    fn build_rgb332_palette(pixels:&[u32])->(Vec<u32>, Vec<u8>){
+        let expand = |value:u32, bits:u32|value * 0xFF / ((1 << bits) - 1);
+        let palette = (0..MAX_COLORS as u32)
+            .map(|index|(expand(index >> 5, 3) << 16) | (expand((index >> 2) & 0b111, 3) << 8) | expand(index & 0b11, 2))
+            .collect();
+        let indices = pixels.iter()
+            .map(|pixel|(((pixel >> 16) & 0xE0) | ((pixel >> 11) & 0x1C) | ((pixel >> 6) & 0b11)) as u8)
+            .collect();
+
+        return (palette, indices);
+    }
+}
+
+// The gif flavour of lzw, variable length codes (up to 12 bits) packed LSB first
+fn lzw_encode(indices:&[u8], min_code_size:u8)->Vec<u8>{
+    let clear_code:u16 = 1 << min_code_size;
+    let end_code:u16 = clear_code + 1;
+    let mut output = BitWriter::default();
+    let mut dictionary:HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+
+    output.write(clear_code, code_size);
+    let mut iter = indices.iter();
+    let mut prefix = match iter.next(){
+        Some(index)=>*index as u16,
+        None=>{
+            output.write(end_code, code_size);
+            return output.finish();
+        }
+    };
+    for index in iter{
+        if let Some(code) = dictionary.get(&(prefix, *index)){
+            prefix = *code;
+            continue;
+        }
+        output.write(prefix, code_size);
+        if next_code == 1 << MAX_CODE_SIZE{
+            output.write(clear_code, code_size);
+            dictionary.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        }
+        else{
+            dictionary.insert((prefix, *index), next_code);
+            // the decoder widens the codes once the code it adds does not fit
+            if next_code == 1 << code_size{
+                code_size += 1;
+            }
+            next_code += 1;
+        }
+        prefix = *index as u16;
+    }
+    output.write(prefix, code_size);
+    output.write(end_code, code_size);
+
+    return output.finish();
+}
+
+#[derive(Default)]
+struct BitWriter{
+    bytes:Vec<u8>,
+    buffer:u32,
+    bits:u8
+}
+
+impl BitWriter{
+    fn write(&mut self, code:u16, size:u8){
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8{
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self)->Vec<u8>{
+        if self.bits > 0{
+            self.bytes.push(self.buffer as u8);
+        }
+        return self.bytes;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // A plain gif lzw decoder, returns the indices and the number of clear codes
+    fn lzw_decode(data:&[u8], min_code_size:u8)->(Vec<u8>, usize){
+        let clear_code = 1 << min_code_size;
+        let end_code = clear_code + 1;
+        let initial_dictionary:Vec<Vec<u8>> = (0..=end_code).map(|code|vec![code as u8]).collect();
+        let mut dictionary = initial_dictionary.clone();
+        let mut code_size = min_code_size + 1;
+        let mut previous:Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        let mut clears = 0;
+        let mut position = 0;
+        loop{
+            let mut code = 0;
+            for i in 0..code_size as usize{
+                let bit = (data[(position + i) / 8] >> ((position + i) % 8)) & 1;
+                code |= (bit as usize) << i;
+            }
+            position += code_size as usize;
+
+            if code == clear_code{
+                dictionary = initial_dictionary.clone();
+                code_size = min_code_size + 1;
+                previous = None;
+                clears += 1;
+                continue;
+            }
+            if code == end_code{
+                return (output, clears);
+            }
+            let entry = match (dictionary.get(code), &previous){
+                (Some(entry), _)=>entry.clone(),
+                (None, Some(previous))=>[&previous[..], &previous[..1]].concat(),
+                (None, None)=>panic!("code {} before any other code", code)
+            };
+            output.extend_from_slice(&entry);
+            if let Some(previous) = previous{
+                if dictionary.len() < 1 << MAX_CODE_SIZE{
+                    dictionary.push([&previous[..], &entry[..1]].concat());
+                }
+            }
+            if dictionary.len() == 1 << code_size && code_size < MAX_CODE_SIZE{
+                code_size += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip(){
+        let indices = [0, 1, 1, 1, 1, 2, 3, 0, 1, 1, 1, 1, 2, 3, 3, 3, 3, 3, 3, 3, 0];
+        let (decoded, clears) = lzw_decode(&lzw_encode(&indices, 2), 2);
+        assert_eq!(decoded, indices);
+        assert_eq!(clears, 1);
+
+        assert_eq!(lzw_decode(&lzw_encode(&[], 2), 2), (Vec::new(), 1));
+    }
+
+    #[test]
+    fn test_lzw_round_trip_past_the_full_dictionary(){
+        // noise fills the 4096 codes quickly so the encoder has to clear the dictionary a few times
+        let mut state:u32 = 1;
+        let indices:Vec<u8> = (0..50000).map(|_|{
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect();
+        let (decoded, clears) = lzw_decode(&lzw_encode(&indices, 8), 8);
+        assert_eq!(decoded, indices);
+        assert!(clears > 1);
+    }
+}
@@ -0,0 +1,234 @@
+mod mbc_handler;
+mod audio_resampler;
+mod wav_file_audio_device;
+mod multi_device_audio;
+mod null_audio_device;
+mod scripted_joypad_provider;
+mod logger;
+mod terminal_args;
+mod ppm_file;
+mod png_file;
+mod gif_encoder;
+mod recorder;
+
+use crate::{mbc_handler::*, multi_device_audio::*, null_audio_device::NullAudioDevice, scripted_joypad_provider::*, logger::init_logger, terminal_args::*, ppm_file::write_ppm, png_file::*, recorder::*};
+use lib_gb::{machine::{gameboy::GameBoy, model::Model}, ppu::ppu_renderer::PpuRenderer, mmu::{gb_mmu::{BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE}, carts::RtcClockSource}, GB_FREQUENCY, apu::audio_device::*, serial::capture_serial_device::CaptureSerialDevice};
+use std::{cell::{Cell, RefCell}, env, fs, rc::Rc, result::Result, vec::Vec};
+use log::{info, error};
+
+const DEFAULT_FRAMES_TO_RUN:u32 = 60 * 60;
+const DEFAULT_OUTPUT_FILE:&str = "output.ppm";
+const PNG_EXTENSION:&str = ".png";
+const EXIT_CONDITION_NOT_MET:i32 = 1;
+const EXIT_BAD_ARGUMENTS:i32 = 2;
+const EXIT_CPU_STOPPED:i32 = 3;
+
+fn print_usage(){
+    println!("usage: magenboy_headless <rom_name> [options]");
+    println!("  --frames <n>            number of frames to run (default {})", DEFAULT_FRAMES_TO_RUN);
+    println!("  --until <address>=<val> stop once the memory at address (hex) equals val (hex)");
+    println!("  --until-serial <text>   stop once the serial output contains text");
+    println!("  --serial-output         print the serial output when stopping");
+    println!("  --input <file>          scripted input file, each line is: <frame> [buttons...]");
+    println!("  --audio-file <file>     write the audio to a wav file");
+    println!("  --output <file>         where to write the last frame (png or ppm by the extension, default {})", DEFAULT_OUTPUT_FILE);
+    println!("  --output-scale <n>      scale the last frame of a png output (default 1)");
+    println!("  --record <file>         record the run as a y4m, rgb (raw) or gif video with the audio next to it as a wav");
+    println!("  --record-format <fmt>   record the run as <rom_name>_<time> in y4m, raw or gif");
+    println!("  --screenshot            also save the last frame as <rom_name>_<time>.png");
+    println!("  --bootrom <file>        boot through a dmg or cgb bootrom (chosen by the file size, must match the model)");
+    println!("  --model <model>         dmg0, dmg, mgb, sgb, sgb2, cgb or agb (default detected from the cartridge header)");
+    println!("  --palette <palette>     gray, green, pocket, high-contrast, color-blind or 4/12 hex colors (dmg only)");
+    println!("  --palette-file <file>   read the palette from a file");
+    println!("  --fifo-ppu              render with the pixel fifo ppu (mid line register writes and mode 3 length)");
+    println!("  --log                   write debug logs to output.log");
+}
+
+fn parse_memory_condition(condition:&str)->Option<(u16, u8)>{
+    let mut parts = condition.split('=');
+    let address = u16::from_str_radix(parts.next()?.trim_start_matches("0x"), 16).ok()?;
+    let value = u8::from_str_radix(parts.next()?.trim_start_matches("0x"), 16).ok()?;
+    return Some((address, value));
+}
+
+fn exit_with_error(message:String)->!{
+    error!("{}", message);
+    std::process::exit(EXIT_BAD_ARGUMENTS);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2{
+        print_usage();
+        std::process::exit(EXIT_BAD_ARGUMENTS);
+    }
+
+    let debug_level = check_for_terminal_feature_flag(&args, "--log");
+    match init_logger(debug_level){
+        Result::Ok(())=>{},
+        Result::Err(error)=>std::panic!("error initing logger: {}", error)
+    }
+
+    let frames_to_run = match get_terminal_flag_value(&args, "--frames"){
+        Some(frames)=>frames.parse::<u32>().unwrap_or_else(|_|exit_with_error(format!("bad frames value: {}", frames))),
+        None=>DEFAULT_FRAMES_TO_RUN
+    };
+    let memory_condition = get_terminal_flag_value(&args, "--until")
+        .map(|condition| parse_memory_condition(&condition).unwrap_or_else(||exit_with_error(format!("bad condition: {}", condition))));
+    let serial_condition = get_terminal_flag_value(&args, "--until-serial");
+    let print_serial_output = check_for_terminal_feature_flag(&args, "--serial-output");
+    let output_path = get_terminal_flag_value(&args, "--output").unwrap_or(String::from(DEFAULT_OUTPUT_FILE));
+    let output_scale = match get_terminal_flag_value(&args, "--output-scale"){
+        Some(scale)=>scale.parse::<usize>().ok().filter(|scale|*scale > 0).unwrap_or_else(||exit_with_error(format!("bad output scale value: {}", scale))),
+        None=>1
+    };
+
+    let current_frame = Rc::new(Cell::new(0));
+    let joypad_provider = match get_terminal_flag_value(&args, "--input"){
+        Some(path)=>{
+            let script = fs::read_to_string(&path).unwrap_or_else(|err|exit_with_error(format!("could not read input file {}: {}", path, err)));
+            ScriptedJoypadProvider::new(&script, current_frame.clone()).unwrap_or_else(|err|exit_with_error(err))
+        }
+        None=>ScriptedJoypadProvider::empty(current_frame.clone())
+    };
+
+    let mut devices: Vec::<Box::<dyn AudioDevice>> = Vec::new();
+    match get_terminal_flag_value(&args, "--audio-file"){
+        Some(path)=>devices.push(Box::new(wav_file_audio_device::WavfileAudioDevice::new(44100, GB_FREQUENCY, &path))),
+        None=>devices.push(Box::new(NullAudioDevice))
+    }
+    let recorder = Rc::new(RefCell::new(Recorder::new()));
+    let record_path = match (get_terminal_flag_value(&args, "--record"), get_terminal_flag_value(&args, "--record-format")){
+        (Some(path), _)=>Some(path),
+        (None, Some(format))=>Some(get_recording_path(&args[1], format.parse::<VideoFormat>().unwrap_or_else(|err|exit_with_error(err)))),
+        (None, None)=>None
+    };
+    if record_path.is_some(){
+        devices.push(Box::new(RecorderAudioDevice::new(recorder.clone())));
+    }
+    let audio_devices = MultiAudioDevice::new(devices);
+
+    let program_name = &args[1];
+    // The emulated rtc keeps the runs deterministic
+    let mut mbc = initialize_mbc(program_name, RtcClockSource::Emulated).unwrap_or_else(|err|exit_with_error(format!("could not load the cartridge: {}", err)));
+
+    let model = match get_terminal_flag_value(&args, "--model"){
+        Some(name)=>name.parse::<Model>().unwrap_or_else(|err|exit_with_error(err)),
+        None=>Model::from_cartridge(mbc.as_ref())
+    };
+
+    let mut gameboy = match get_terminal_flag_value(&args, "--bootrom"){
+        Some(path)=>{
+            let file = fs::read(&path).unwrap_or_else(|err|exit_with_error(format!("could not read bootrom {}: {}", path, err)));
+            if file.len() >= CGB_BOOT_ROM_SIZE{
+                if !model.is_cgb(){
+                    exit_with_error(format!("bootrom {} is a cgb bootrom and can not boot a {}", path, model));
+                }
+                let mut bootrom:[u8;CGB_BOOT_ROM_SIZE] = [0;CGB_BOOT_ROM_SIZE];
+                bootrom.copy_from_slice(&file[..CGB_BOOT_ROM_SIZE]);
+
+                GameBoy::new_with_cgb_bootrom(&mut mbc, joypad_provider, audio_devices, CaptureSerialDevice::default(), bootrom)
+            }
+            else if file.len() < BOOT_ROM_SIZE{
+                exit_with_error(format!("bootrom {} is too small", path));
+            }
+
+            else{
+                if model.is_cgb(){
+                    exit_with_error(format!("bootrom {} is a dmg bootrom and can not boot a {} (pass --model to pick a dmg or sgb model)", path, model));
+                }
+                let mut bootrom:[u8;BOOT_ROM_SIZE] = [0;BOOT_ROM_SIZE];
+                bootrom.copy_from_slice(&file[..BOOT_ROM_SIZE]);
+
+                GameBoy::new_with_bootrom(&mut mbc, joypad_provider, audio_devices, CaptureSerialDevice::default(), bootrom, model)
+            }
+        }
+        None=>GameBoy::new_with_model(&mut mbc, joypad_provider, audio_devices, CaptureSerialDevice::default(), model)
+    };
+
+    if let Some(dmg_palette) = get_dmg_palette(&args).unwrap_or_else(|err|exit_with_error(err)){
+        gameboy.set_dmg_palette(dmg_palette);
+    }
+    if check_for_terminal_feature_flag(&args, "--fifo-ppu"){
+        gameboy.set_ppu_renderer(PpuRenderer::PixelFifo);
+    }
+
+    info!("running {} on {} for up to {} frames", program_name, model, frames_to_run);
+
+    let (frame_width, frame_height) = gameboy.get_frame_size();
+    let mut last_frame = vec![0;frame_width * frame_height];
+    if let Some(path) = &record_path{
+        recorder.borrow_mut().start(path, frame_width, frame_height).unwrap_or_else(|err|exit_with_error(format!("could not record to {}: {}", path, err)));
+    }
+    let mut condition_met = memory_condition.is_none() && serial_condition.is_none();
+    while current_frame.get() < frames_to_run{
+        last_frame = gameboy.cycle_frame().to_vec();
+        if let Err(err) = recorder.borrow_mut().push_frame(&last_frame){
+            error!("stopped recording: {}", err);
+        }
+        current_frame.set(current_frame.get() + 1);
+
+        if gameboy.get_stop_reason().is_some(){
+            break;
+        }
+
+        if let Some((address, value)) = memory_condition{
+            if gameboy.read_memory(address) == value{
+                condition_met = true;
+                break;
+            }
+        }
+
+        if let Some(text) = &serial_condition{
+            if gameboy.get_serial_device().get_output().contains(text.as_str()){
+                condition_met = true;
+                break;
+            }
+        }
+    }
+
+    info!("stopped after {} frames", current_frame.get());
+
+    if print_serial_output{
+        print!("{}", gameboy.get_serial_device().get_output());
+    }
+
+    if recorder.borrow().is_recording(){
+        match recorder.borrow_mut().stop(){
+            Ok(audio_path)=>info!("recorded to {} and {}", record_path.unwrap_or_default(), audio_path),
+            Err(err)=>error!("could not finish the recording: {}", err)
+        }
+    }
+
+    let write_result = if output_path.ends_with(PNG_EXTENSION){
+        write_screenshot_file(&output_path, &gameboy, output_scale)
+    }
+    else{
+        write_ppm(&output_path, &last_frame, frame_width, frame_height)
+    };
+    match write_result{
+        Ok(())=>info!("wrote the last frame to {}", output_path),
+        Err(err)=>error!("could not write the last frame to {}: {}", output_path, err)
+    }
+    if check_for_terminal_feature_flag(&args, "--screenshot"){
+        let path = get_screenshot_path(program_name);
+        match write_screenshot_file(&path, &gameboy, output_scale){
+            Ok(())=>info!("saved screenshot to {}", path),
+            Err(err)=>error!("could not save screenshot to {}: {}", path, err)
+        }
+    }
+
+    let stop_reason = gameboy.get_stop_reason();
+    drop(gameboy);
+    release_mbc(program_name, mbc);
+
+    if let Some(reason) = stop_reason{
+        error!("the cpu stopped: {}", reason);
+        std::process::exit(EXIT_CPU_STOPPED);
+    }
+
+    if !condition_met{
+        error!("condition was not met after {} frames", frames_to_run);
+        std::process::exit(EXIT_CONDITION_NOT_MET);
+    }
+}
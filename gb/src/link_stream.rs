@@ -0,0 +1,83 @@
+use std::{io::{Read, Write, Result}, net::{TcpListener, TcpStream}};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use log::info;
+
+const UNIX_SOCKET_PREFIX:&str = "unix:";
+
+// A tcp or unix domain socket stream for the link cable,
+// addresses in the form unix:<path> are unix domain sockets and all the others are tcp addresses (like 127.0.0.1:5000)
+pub enum LinkStream{
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream)
+}
+
+impl LinkStream{
+    // Waits for the other emulator to connect
+    pub fn host(address:&str)->Result<Self>{
+        info!("waiting for a link cable connection on {}", address);
+        let stream = match address.strip_prefix(UNIX_SOCKET_PREFIX){
+            #[cfg(unix)]
+            Some(path)=>{
+                let _ = std::fs::remove_file(path);
+                LinkStream::Unix(UnixListener::bind(path)?.accept()?.0)
+            }
+            _=>LinkStream::Tcp(TcpListener::bind(address)?.accept()?.0)
+        };
+
+        stream.into_nonblocking()
+    }
+
+    pub fn connect(address:&str)->Result<Self>{
+        info!("connecting a link cable to {}", address);
+        let stream = match address.strip_prefix(UNIX_SOCKET_PREFIX){
+            #[cfg(unix)]
+            Some(path)=>LinkStream::Unix(UnixStream::connect(path)?),
+            _=>LinkStream::Tcp(TcpStream::connect(address)?)
+        };
+
+        stream.into_nonblocking()
+    }
+
+    fn into_nonblocking(self)->Result<Self>{
+        match &self{
+            LinkStream::Tcp(stream)=>{
+                stream.set_nodelay(true)?;
+                stream.set_nonblocking(true)?;
+            }
+            #[cfg(unix)]
+            LinkStream::Unix(stream)=>stream.set_nonblocking(true)?
+        }
+
+        Ok(self)
+    }
+}
+
+impl Read for LinkStream{
+    fn read(&mut self, buf:&mut [u8])->Result<usize> {
+        match self{
+            LinkStream::Tcp(stream)=>stream.read(buf),
+            #[cfg(unix)]
+            LinkStream::Unix(stream)=>stream.read(buf)
+        }
+    }
+}
+
+impl Write for LinkStream{
+    fn write(&mut self, buf:&[u8])->Result<usize> {
+        match self{
+            LinkStream::Tcp(stream)=>stream.write(buf),
+            #[cfg(unix)]
+            LinkStream::Unix(stream)=>stream.write(buf)
+        }
+    }
+
+    fn flush(&mut self)->Result<()> {
+        match self{
+            LinkStream::Tcp(stream)=>stream.flush(),
+            #[cfg(unix)]
+            LinkStream::Unix(stream)=>stream.flush()
+        }
+    }
+}
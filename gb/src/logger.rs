@@ -0,0 +1,24 @@
+pub fn init_logger(debug:bool)->Result<(), fern::InitError>{
+    let level = if debug {log::LevelFilter::Debug} else {log::LevelFilter::Info};
+    let mut fern_logger = fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "{}[{}] {}",
+                chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                record.level(),
+                message
+            ))
+        })
+        .level(level);
+
+    if !debug{
+        fern_logger = fern_logger.chain(std::io::stdout());
+    }
+    else{
+        fern_logger = fern_logger.chain(fern::log_file("output.log")?);
+    }
+
+    fern_logger.apply()?;
+
+    Ok(())
+}
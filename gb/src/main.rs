@@ -4,61 +4,32 @@ mod sdl_audio_device;
 mod audio_resampler;
 mod wav_file_audio_device;
 mod multi_device_audio;
+mod logger;
+mod terminal_args;
+mod link_stream;
+mod ppm_file;
+mod file_page_printer;
+mod png_file;
+mod gif_encoder;
+mod recorder;
+mod scaling_filter;
 
-use crate::{mbc_handler::*, sdl_joypad_provider::*, multi_device_audio::*};
-use lib_gb::{keypad::button::Button, machine::gameboy::GameBoy, mmu::gb_mmu::BOOT_ROM_SIZE, ppu::gb_ppu::{SCREEN_HEIGHT, SCREEN_WIDTH}, GB_FREQUENCY, apu::audio_device::*};
+use crate::{mbc_handler::*, sdl_joypad_provider::*, multi_device_audio::*, logger::init_logger, terminal_args::*, link_stream::LinkStream, file_page_printer::FilePagePrinter, png_file::*, recorder::*, scaling_filter::ScalingFilter};
+use lib_gb::{keypad::button::Button, machine::{gameboy::GameBoy, model::Model}, mmu::{gb_mmu::{BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE}, carts::RtcClockSource}, ppu::{gb_ppu::{SCREEN_HEIGHT, SCREEN_WIDTH}, ppu_renderer::PpuRenderer}, GB_FREQUENCY, apu::audio_device::*, serial::{serial_device::SerialDevice, disconnected_serial_device::DisconnectedSerialDevice, link_cable_serial_device::LinkCableSerialDevice, printer_serial_device::PrinterSerialDevice}};
 use std::{
     ffi::{c_void, CString},
-    fs, env, result::Result, vec::Vec
+    cell::RefCell, fs, env, rc::Rc, result::Result, vec::Vec
 };
-use log::info;
+use log::{info, error};
 use sdl2::sys::*;
 
 const FPS:f64 = GB_FREQUENCY as f64 / 70224.0;
 const FRAME_TIME_MS:f64 = (1.0 / FPS) * 1000.0;
+const SAVE_STATE_SUFFIX:&str = ".state";
+const DEFAULT_SCREEN_SCALE:u32 = 4;
+const EXIT_BAD_ARGUMENTS:i32 = 2;
 
 
-fn extend_vec(vec:&[u32], scale:usize, w:usize, h:usize)->Vec<u32>{
-    let mut new_vec = vec![0;vec.len()*scale*scale];
-    for y in 0..h{
-        let sy = y*scale;
-        for x in 0..w{
-            let sx = x*scale;
-            for i in 0..scale{
-                for j in 0..scale{
-                    new_vec[(sy+i)*(w*scale)+sx+j] = vec[y*w+x];
-                }
-            }
-        } 
-    }
-    return new_vec;
-}
-
-fn init_logger(debug:bool)->Result<(), fern::InitError>{
-    let level = if debug {log::LevelFilter::Debug} else {log::LevelFilter::Info};
-    let mut fern_logger = fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{}[{}] {}",
-                chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                record.level(),
-                message
-            ))
-        })
-        .level(level);
-
-    if !debug{
-        fern_logger = fern_logger.chain(std::io::stdout());
-    }
-    else{
-        fern_logger = fern_logger.chain(fern::log_file("output.log")?);
-    }
-
-    fern_logger.apply()?;
-
-    Ok(())
-}
-
 fn buttons_mapper(button:Button)->SDL_Scancode{
     match button{
         Button::A       => SDL_Scancode::SDL_SCANCODE_X,
@@ -72,13 +43,72 @@ fn buttons_mapper(button:Button)->SDL_Scancode{
     }
 }
 
-fn check_for_terminal_feature_flag(args:&Vec::<String>, flag:&str)->bool{
-    args.len() >= 3 && args.contains(&String::from(flag))
+fn init_serial_device(args:&Vec<String>, program_name:&str)->Box<dyn SerialDevice>{
+    if check_for_terminal_feature_flag(args, "--printer"){
+        info!("printer connected");
+        return Box::new(PrinterSerialDevice::new(FilePagePrinter::new(program_name)));
+    }
+
+    let link_stream = match get_terminal_flag_value(args, "--link-host"){
+        Option::Some(address)=>Option::Some(LinkStream::host(&address)),
+        Option::None=>get_terminal_flag_value(args, "--link-connect").map(|address|LinkStream::connect(&address))
+    };
+
+    match link_stream{
+        Option::Some(Result::Ok(stream))=>{
+            info!("link cable connected");
+            Box::new(LinkCableSerialDevice::new(stream))
+        }
+        Option::Some(Result::Err(err))=>std::panic!("error connecting the link cable: {}", err),
+        Option::None=>Box::new(DisconnectedSerialDevice)
+    }
 }
 
-fn main() {
-    let screen_scale:u32 = 4;
+fn create_texture(renderer:*mut SDL_Renderer, filter:ScalingFilter, frame_width:usize, frame_height:usize)->*mut SDL_Texture{
+    let scale = filter.get_scale();
+    unsafe{
+        SDL_CreateTexture(renderer,
+            SDL_PixelFormatEnum::SDL_PIXELFORMAT_ARGB8888 as u32, SDL_TextureAccess::SDL_TEXTUREACCESS_STREAMING as i32,
+            (frame_width * scale) as i32, (frame_height * scale) as i32)
+    }
+}
+
+// The biggest integer scale that fits the window and is a multiple of the filter scale so the filtered pixels stay sharp,
+// a window too small for the filter shows the frame without it
+fn get_display_scale(output_width:i32, output_height:i32, frame_width:usize, frame_height:usize, filter:ScalingFilter)->(ScalingFilter, u32){
+    let max_scale = std::cmp::max(1, std::cmp::min(output_width as u32 / frame_width as u32, output_height as u32 / frame_height as u32));
+    let filter_scale = filter.get_scale() as u32;
+    if max_scale >= filter_scale{
+        return (filter, max_scale / filter_scale * filter_scale);
+    }
+    return (ScalingFilter::Nearest, max_scale);
+}
+
+fn toggle_fullscreen(window:*mut SDL_Window){
+    unsafe{
+        let fullscreen_flag = SDL_WindowFlags::SDL_WINDOW_FULLSCREEN_DESKTOP as u32;
+        let flags = if SDL_GetWindowFlags(window) & fullscreen_flag != 0 {0} else {fullscreen_flag};
+        SDL_SetWindowFullscreen(window, flags);
+    }
+}
+
+fn toggle_recording(recorder:&mut Recorder, program_name:&str, format:VideoFormat, width:usize, height:usize){
+    if recorder.is_recording(){
+        match recorder.stop(){
+            Result::Ok(audio_path)=>info!("stopped recording, the audio was saved to {}", audio_path),
+            Result::Err(err)=>error!("could not finish the recording: {}", err)
+        }
+    }
+    else{
+        let path = get_recording_path(program_name, format);
+        match recorder.start(&path, width, height){
+            Result::Ok(())=>info!("started recording to {}", path),
+            Result::Err(err)=>error!("could not record to {}: {}", path, err)
+        }
+    }
+}
 
+fn main() {
     let args: Vec<String> = env::args().collect();    
 
     let debug_level = check_for_terminal_feature_flag(&args, "--log");
@@ -88,23 +118,28 @@ fn main() {
         Result::Err(error)=>std::panic!("error initing logger: {}", error)
     }
 
+    let screen_scale = match get_terminal_flag_value(&args, "--scale"){
+        Option::Some(scale)=>scale.parse::<u32>().ok().filter(|scale|*scale > 0).unwrap_or_else(||std::panic!("bad scale value: {}", scale)),
+        Option::None=>DEFAULT_SCREEN_SCALE
+    };
+    let mut filter = match get_terminal_flag_value(&args, "--filter"){
+        Option::Some(name)=>name.parse::<ScalingFilter>().unwrap_or_else(|err|std::panic!("{}", err)),
+        Option::None=>ScalingFilter::Nearest
+    };
+
     let buffer_width = SCREEN_WIDTH as u32 * screen_scale;
     let buffer_height = SCREEN_HEIGHT as u32* screen_scale;
     let program_name = CString::new("MagenBoy").unwrap();
-    let (_window, renderer, texture): (*mut SDL_Window, *mut SDL_Renderer, *mut SDL_Texture) = unsafe{
+    let (window, renderer): (*mut SDL_Window, *mut SDL_Renderer) = unsafe{
         SDL_Init(SDL_INIT_VIDEO | SDL_INIT_AUDIO);
         let wind:*mut SDL_Window = SDL_CreateWindow(
             program_name.as_ptr(),
             SDL_WINDOWPOS_UNDEFINED_MASK as i32, SDL_WINDOWPOS_UNDEFINED_MASK as i32,
-            buffer_width as i32, buffer_height as i32, 0);
+            buffer_width as i32, buffer_height as i32, SDL_WindowFlags::SDL_WINDOW_RESIZABLE as u32);
         
         let rend: *mut SDL_Renderer = SDL_CreateRenderer(wind, -1, 0);
-
-        let tex: *mut SDL_Texture = SDL_CreateTexture(rend,
-            SDL_PixelFormatEnum::SDL_PIXELFORMAT_ARGB8888 as u32, SDL_TextureAccess::SDL_TEXTUREACCESS_STREAMING as i32,
-             buffer_width as i32, buffer_height as i32);
         
-        (wind, rend, tex)
+        (wind, rend)
     };
 
     let audio_device = sdl_audio_device::SdlAudioDevie::new(44100);
@@ -114,36 +149,118 @@ fn main() {
         let wav_ad = wav_file_audio_device::WavfileAudioDevice::new(44100, GB_FREQUENCY, "output.wav");
         devices.push(Box::new(wav_ad));
     }
-    
+    // recordings can be started at any time so the recorder always gets the audio
+    let recorder = Rc::new(RefCell::new(Recorder::new()));
+    devices.push(Box::new(RecorderAudioDevice::new(recorder.clone())));
+
     let audio_devices = MultiAudioDevice::new(devices);
 
     let program_name = &args[1];
-    let mut mbc = initialize_mbc(program_name); 
+    let serial_device = init_serial_device(&args, program_name);
+    let rtc_clock_source = if check_for_terminal_feature_flag(&args, "--rtc-emulated") {RtcClockSource::Emulated} else {RtcClockSource::WallClock};
+    let mut mbc = match initialize_mbc(program_name, rtc_clock_source){
+        Result::Ok(mbc)=>mbc,
+        Result::Err(err)=>{
+            error!("could not load the cartridge: {}", err);
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+    };
     let joypad_provider = SdlJoypadProvider::new(buttons_mapper);
 
-    let mut gameboy = match fs::read("Dependencies\\Init\\dmg_boot.bin"){
-        Result::Ok(file)=>{
-            info!("found bootrom!");
+    let model = match get_terminal_flag_value(&args, "--model"){
+        Option::Some(name)=>match name.parse::<Model>(){
+            Result::Ok(model)=>model,
+            Result::Err(err)=>{
+                error!("{}", err);
+                std::process::exit(EXIT_BAD_ARGUMENTS);
+            }
+        },
+        Option::None=>Model::from_cartridge(mbc.as_ref())
+    };
+    info!("running on {}", model);
+
+    let mut gameboy = if model.is_cgb(){
+        match fs::read("Dependencies\\Init\\cgb_boot.bin"){
+            Result::Ok(file) if file.len() >= CGB_BOOT_ROM_SIZE=>{
+                info!("found cgb bootrom!");
 
-            let mut bootrom:[u8;BOOT_ROM_SIZE] = [0;BOOT_ROM_SIZE];
-            for i in 0..BOOT_ROM_SIZE{
-                bootrom[i] = file[i];
+                let mut bootrom:[u8;CGB_BOOT_ROM_SIZE] = [0;CGB_BOOT_ROM_SIZE];
+                bootrom.copy_from_slice(&file[..CGB_BOOT_ROM_SIZE]);
+
+                GameBoy::new_with_cgb_bootrom(&mut mbc, joypad_provider, audio_devices, serial_device, bootrom)
+            }
+            _=>{
+                info!("could not find cgb bootrom... booting directly to rom");
+
+                GameBoy::new_with_model(&mut mbc, joypad_provider, audio_devices, serial_device, model)
             }
-            
-            GameBoy::new_with_bootrom(&mut mbc, joypad_provider,audio_devices, bootrom)
         }
-        Result::Err(_)=>{
-            info!("could not find bootrom... booting directly to rom");
+    }
+    else{
+        match fs::read("Dependencies\\Init\\dmg_boot.bin"){
+            Result::Ok(file) if file.len() >= BOOT_ROM_SIZE=>{
+                info!("found bootrom!");
+
+                let mut bootrom:[u8;BOOT_ROM_SIZE] = [0;BOOT_ROM_SIZE];
+                for i in 0..BOOT_ROM_SIZE{
+                    bootrom[i] = file[i];
+                }
+                
+                GameBoy::new_with_bootrom(&mut mbc, joypad_provider,audio_devices, serial_device, bootrom, model)
+            }
+            _=>{
+                info!("could not find bootrom... booting directly to rom");
 
-            GameBoy::new(&mut mbc, joypad_provider, audio_devices)
+                GameBoy::new_with_model(&mut mbc, joypad_provider, audio_devices, serial_device, model)
+            }
         }
     };
 
+    gameboy.set_color_correction(check_for_terminal_feature_flag(&args, "--color-correction"));
+    match get_dmg_palette(&args){
+        Result::Ok(Option::Some(dmg_palette))=>gameboy.set_dmg_palette(dmg_palette),
+        Result::Ok(Option::None)=>{}
+        Result::Err(err)=>{
+            error!("{}", err);
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+    }
+    if check_for_terminal_feature_flag(&args, "--fifo-ppu"){
+        gameboy.set_ppu_renderer(PpuRenderer::PixelFifo);
+    }
+
+    let record_format = match get_terminal_flag_value(&args, "--record-format"){
+        Option::Some(format)=>format.parse::<VideoFormat>().unwrap_or_else(|err|std::panic!("{}", err)),
+        Option::None=>VideoFormat::Y4m
+    };
+
     info!("initialized gameboy successfully!");
 
+    // the SGB frame includes the border
+    let (frame_width, frame_height) = gameboy.get_frame_size();
+    unsafe{
+        SDL_SetWindowSize(window, (frame_width as u32 * screen_scale) as i32, (frame_height as u32 * screen_scale) as i32);
+        SDL_SetWindowMinimumSize(window, frame_width as i32, frame_height as i32);
+    }
+    if check_for_terminal_feature_flag(&args, "--fullscreen"){
+        toggle_fullscreen(window);
+    }
+    let mut texture_filter = filter;
+    let mut texture = create_texture(renderer, texture_filter, frame_width, frame_height);
+    let mut scaled_buffer = Vec::new();
+    let mut display_scale = screen_scale;
+
+    if let Option::Some(path) = get_terminal_flag_value(&args, "--record"){
+        match recorder.borrow_mut().start(&path, frame_width, frame_height){
+            Result::Ok(())=>info!("started recording to {}", path),
+            Result::Err(err)=>std::panic!("could not record to {}: {}", path, err)
+        }
+    }
+
     unsafe{
         let mut event: std::mem::MaybeUninit<SDL_Event> = std::mem::MaybeUninit::uninit();
         let mut start:u64 = SDL_GetPerformanceCounter();
+        let mut reported_stop_reason = false;
         loop{
 
             if SDL_PollEvent(event.as_mut_ptr()) != 0{
@@ -151,21 +268,93 @@ fn main() {
                 if event.type_ == SDL_EventType::SDL_QUIT as u32{
                     break;
                 }
+                else if event.type_ == SDL_EventType::SDL_KEYDOWN as u32{
+                    let state_path = format!("{}{}", program_name, SAVE_STATE_SUFFIX);
+                    match event.key.keysym.scancode{
+                        SDL_Scancode::SDL_SCANCODE_F5=>match fs::write(&state_path, gameboy.save_state()){
+                            Ok(())=>info!("saved state to {}", state_path),
+                            Err(err)=>error!("could not save state to {}: {}", state_path, err)
+                        },
+                        SDL_Scancode::SDL_SCANCODE_F9=>match fs::read(&state_path){
+                            Ok(state)=>match gameboy.load_state(&state){
+                                Ok(())=>info!("loaded state from {}", state_path),
+                                Err(err)=>error!("could not load state from {}: {}", state_path, err)
+                            },
+                            Err(err)=>error!("could not read state from {}: {}", state_path, err)
+                        },
+                        // recordings start and stop between frames
+                        SDL_Scancode::SDL_SCANCODE_F10=>toggle_recording(&mut recorder.borrow_mut(), program_name, record_format, frame_width, frame_height),
+                        SDL_Scancode::SDL_SCANCODE_F7=>{
+                            filter = filter.next();
+                            info!("scaling filter: {}", filter);
+                        }
+                        SDL_Scancode::SDL_SCANCODE_F11=>toggle_fullscreen(window),
+                        // shift saves the screenshot in the size it is displayed in
+                        SDL_Scancode::SDL_SCANCODE_F12=>{
+                            let shift_mask = SDL_Keymod::KMOD_LSHIFT as u16 | SDL_Keymod::KMOD_RSHIFT as u16;
+                            let scale = if event.key.keysym.mod_ & shift_mask != 0 {display_scale as usize} else {1};
+                            let path = get_screenshot_path(program_name);
+                            match write_screenshot_file(&path, &gameboy, scale){
+                                Ok(())=>info!("saved screenshot to {}", path),
+                                Err(err)=>error!("could not save screenshot to {}: {}", path, err)
+                            }
+                        }
+                        _=>{}
+                    }
+                }
             }
 
             let frame_buffer = gameboy.cycle_frame();
-            let scaled_buffer = extend_vec(frame_buffer, screen_scale as usize, SCREEN_WIDTH, SCREEN_HEIGHT);
+            if let Result::Err(err) = recorder.borrow_mut().push_frame(frame_buffer){
+                error!("stopped recording: {}", err);
+            }
 
+            let (mut output_width, mut output_height) = (0, 0);
+            SDL_GetRendererOutputSize(renderer, &mut output_width, &mut output_height);
+            let (display_filter, scale) = get_display_scale(output_width, output_height, frame_width, frame_height, filter);
+            display_scale = scale;
+            if display_filter != texture_filter{
+                texture_filter = display_filter;
+                SDL_DestroyTexture(texture);
+                texture = create_texture(renderer, texture_filter, frame_width, frame_height);
+            }
+            texture_filter.apply(frame_buffer, frame_width, frame_height, &mut scaled_buffer);
+
+            // the texture rows might be padded so they are copied one by one
+            let scaled_width = frame_width * texture_filter.get_scale();
             let mut pixels: *mut c_void = std::ptr::null_mut();
             let mut length: std::os::raw::c_int = 0;
             SDL_LockTexture(texture, std::ptr::null(), &mut pixels, &mut length);
-            std::ptr::copy_nonoverlapping(scaled_buffer.as_ptr(),pixels as *mut u32,  scaled_buffer.len());
+            for (y, row) in scaled_buffer.chunks_exact(scaled_width).enumerate(){
+                let row_pixels = (pixels as *mut u8).add(y * length as usize) as *mut u32;
+                std::ptr::copy_nonoverlapping(row.as_ptr(), row_pixels, scaled_width);
+            }
             SDL_UnlockTexture(texture);
 
+            // the frame is centered and the rest of the window is left black
+            let display_width = (frame_width as u32 * display_scale) as i32;
+            let display_height = (frame_height as u32 * display_scale) as i32;
+            let display_rect = SDL_Rect{
+                x:(output_width - display_width) / 2,
+                y:(output_height - display_height) / 2,
+                w:display_width,
+                h:display_height
+            };
+
+            SDL_SetRenderDrawColor(renderer, 0, 0, 0, 0xFF);
             SDL_RenderClear(renderer);
-            SDL_RenderCopy(renderer, texture, std::ptr::null(), std::ptr::null());
+            SDL_RenderCopy(renderer, texture, std::ptr::null(), &display_rect);
             SDL_RenderPresent(renderer);
 
+            if !reported_stop_reason{
+                if let Option::Some(reason) = gameboy.get_stop_reason(){
+                    error!("the cpu stopped: {}", reason);
+                    let title = CString::new(format!("MagenBoy - cpu stopped: {}", reason)).unwrap();
+                    SDL_SetWindowTitle(window, title.as_ptr());
+                    reported_stop_reason = true;
+                }
+            }
+
             let end = SDL_GetPerformanceCounter();
             let elapsed_ms:f64 = (end - start) as f64 / SDL_GetPerformanceFrequency() as f64 * 1000.0;
             if elapsed_ms < FRAME_TIME_MS{
@@ -177,6 +366,9 @@ fn main() {
 
         SDL_Quit();
     }
+    if recorder.borrow().is_recording(){
+        toggle_recording(&mut recorder.borrow_mut(), program_name, record_format, frame_width, frame_height);
+    }
     drop(gameboy);
     release_mbc(program_name, mbc);
 }
@@ -1,24 +1,39 @@
 use lib_gb::mmu::carts::*;
 use std::boxed::Box;
 use std::fs;
-use log::info;
+use log::{info, warn};
 
-const CARTRIDGE_TYPE_ADDRESS:usize = 0x147;
 const PROGRAM_SUFFIX:&str = ".gb";
 pub const SAVE_SUFFIX:&str = ".sav";
 
-pub fn initialize_mbc(program_name:&String)->Box<dyn Mbc>{
+struct LogRumbleMotor;
 
-    let program_path = format!("{}{}",program_name,PROGRAM_SUFFIX);
-    let program = fs::read(program_path).expect("No program found, notice that function must have a `.gb` suffix");
+impl RumbleMotor for LogRumbleMotor{
+    fn set_rumble(&mut self, on:bool) {
+        info!("rumble motor {}", if on {"on"} else {"off"});
+    }
+}
+
+// The rom name is given without the `.gb` suffix
+pub fn initialize_mbc(program_name:&String, rtc_clock_source:RtcClockSource)->Result<Box<dyn Mbc>, String>{
 
-    let mbc_type = program[CARTRIDGE_TYPE_ADDRESS];
+    let program_path = format!("{}{}",program_name,PROGRAM_SUFFIX);
+    let program = fs::read(&program_path).map_err(|err|format!("could not read {}: {}", program_path, err))?;
 
-    info!("initializing cartridge of type: {:#X}", mbc_type);
+    let header = CartridgeHeader::parse(&program).map_err(|err|err.to_string())?;
+    info!("cartridge header - {}", header);
+    for error in header.validate(&program){
+        warn!("the rom might be corrupted: {}", error);
+    }
     
     let save_data = try_get_save_data(program_name);
     
-    return lib_gb::machine::mbc_initializer::initialize_mbc(mbc_type, program, save_data);
+    let mut mbc = lib_gb::machine::mbc_initializer::initialize_mbc(&header, program, save_data, rtc_clock_source).map_err(|err|err.to_string())?;
+    if mbc.has_rumble(){
+        mbc.attach_rumble_motor(Box::new(LogRumbleMotor));
+    }
+
+    return Ok(mbc);
 }
 
 fn try_get_save_data(name:&String)->Option<Vec<u8>>{
@@ -31,7 +46,7 @@ fn try_get_save_data(name:&String)->Option<Vec<u8>>{
 
 pub fn release_mbc(program_name:&String, mbc: Box<dyn Mbc>){
     if mbc.has_battery(){
-        while fs::write(format!("{}{}", program_name, ".sav"), mbc.get_ram()).is_err() {}
+        while fs::write(format!("{}{}", program_name, ".sav"), mbc.get_save_data()).is_err() {}
         
         info!("saved succesfully");
     }
@@ -0,0 +1,7 @@
+use lib_gb::apu::audio_device::*;
+
+pub struct NullAudioDevice;
+
+impl AudioDevice for NullAudioDevice{
+    fn push_buffer(&mut self, _buffer:&[Sample]) {}
+}
@@ -0,0 +1,14 @@
+use std::{fs::File, io::{BufWriter, Write}};
+use lib_gb::{apu::audio_device::AudioDevice, keypad::joypad_provider::JoypadProvider, machine::gameboy::GameBoy, serial::serial_device::SerialDevice};
+
+// Screenshots are named after the rom and the local time they were taken at
+pub fn get_screenshot_path(rom_name:&str)->String{
+    format!("{}_{}.png", rom_name, chrono::Local::now().format("%Y%m%d_%H%M%S_%3f"))
+}
+
+// Writes the last frame of the gameboy as a png image scaled by the scale
+pub fn write_screenshot_file<JP:JoypadProvider, AD:AudioDevice, SD:SerialDevice>(path:&str, gameboy:&GameBoy<JP, AD, SD>, scale:usize)->std::io::Result<()>{
+    let mut file = BufWriter::new(File::create(path)?);
+    gameboy.write_screenshot(&mut file, scale)?;
+    file.flush()
+}
@@ -0,0 +1,14 @@
+use std::{fs, io::Write};
+
+// Writes RGB888 pixels as a binary ppm image
+pub fn write_ppm(path:&str, pixels:&[u32], width:usize, height:usize)->std::io::Result<()>{
+    let mut file = fs::File::create(path)?;
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    let mut bytes = Vec::with_capacity(pixels.len() * 3);
+    for pixel in pixels{
+        bytes.push((pixel >> 16) as u8);
+        bytes.push((pixel >> 8) as u8);
+        bytes.push(*pixel as u8);
+    }
+    file.write_all(&bytes)
+}
@@ -0,0 +1,359 @@
+use std::{cell::RefCell, fs::File, io::{BufWriter, Seek, SeekFrom, Write}, rc::Rc, str::FromStr};
+use lib_gb::{apu::audio_device::*, ppu::gb_ppu::CYCLES_PER_FRAME, GB_FREQUENCY};
+use crate::{audio_resampler::AudioResampler, gif_encoder::GifEncoder};
+
+const RECORDING_AUDIO_FREQUENCY:u32 = 44100;
+// 32 bit float stereo, a sample is both channels
+const WAV_FORMAT_IEEE_FLOAT:u16 = 3;
+const WAV_CHANNELS:u16 = 2;
+const WAV_BYTES_PER_SAMPLE:u32 = 8;
+const WAV_HEADER_SIZE:u32 = 44;
+const WAV_RIFF_SIZE_OFFSET:u64 = 4;
+const WAV_DATA_SIZE_OFFSET:u64 = 40;
+// the riff size is a u32 and counts everything after it, about 3.4 hours of audio
+const WAV_MAX_SAMPLES:u64 = (u32::MAX - (WAV_HEADER_SIZE - 8)) as u64 / WAV_BYTES_PER_SAMPLE as u64;
+const T_CYCLES_PER_FRAME:u64 = CYCLES_PER_FRAME as u64 * 4;
+// gif delays are in hundredths of a second and most viewers slow down delays shorter than 2,
+// so every other frame is written with a delay of 3 or 4
+const GIF_FRAMES_STEP:u64 = 2;
+const CENTISECONDS_PER_SECOND:u64 = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VideoFormat{
+    // 4:4:4 full range YUV at exactly 4194304/70224 fps, playable by ffmpeg and most players,
+    // lossy since the conversion to YUV rounds the colors, only raw is lossless
+    Y4m,
+    // 24 bit RGB frames one after the other with no header, lossless
+    Raw,
+    // Plays at half the frame rate
+    Gif
+}
+
+impl VideoFormat{
+    pub fn get_extension(&self)->&'static str{
+        match self{
+            VideoFormat::Y4m=>"y4m",
+            VideoFormat::Raw=>"rgb",
+            VideoFormat::Gif=>"gif"
+        }
+    }
+
+    pub fn from_path(path:&str)->Result<Self, String>{
+        let extension = path.rsplit('.').next().unwrap_or_default();
+        return match extension{
+            "y4m"=>Ok(VideoFormat::Y4m),
+            "rgb" | "raw"=>Ok(VideoFormat::Raw),
+            "gif"=>Ok(VideoFormat::Gif),
+            _=>Err(format!("unsupported recording file: {}, use a .y4m, .rgb or .gif file", path))
+        };
+    }
+}
+
+impl FromStr for VideoFormat{
+    type Err = String;
+
+    fn from_str(s:&str)->Result<Self, Self::Err>{
+        match s{
+            "y4m"=>Ok(VideoFormat::Y4m),
+            "raw"=>Ok(VideoFormat::Raw),
+            "gif"=>Ok(VideoFormat::Gif),
+            _=>Err(format!("unknown recording format: {}, the formats are y4m, raw and gif", s))
+        }
+    }
+}
+
+// Recordings are named after the rom and the local time they were started at
+pub fn get_recording_path(rom_name:&str, format:VideoFormat)->String{
+    format!("{}_{}.{}", rom_name, chrono::Local::now().format("%Y%m%d_%H%M%S_%3f"), format.get_extension())
+}
+
+// The audio of a recording is written next to the video
+pub fn get_recording_audio_path(video_path:&str)->String{
+    let stem = video_path.rsplit_once('.').map(|(stem, _)|stem).unwrap_or(video_path);
+    format!("{}.wav", stem)
+}
+
+enum VideoWriter{
+    Y4m(BufWriter<File>),
+    Raw(BufWriter<File>),
+    Gif(GifEncoder<BufWriter<File>>)
+}
+
+struct Recording{
+    video_path:String,
+    video:VideoWriter,
+    width:usize,
+    height:usize,
+    frames:u64,
+    pixels_buffer:Vec<u8>,
+    resampler:AudioResampler,
+    audio:WavWriter
+}
+
+// Records the frames and the audio of the emulation, both are timed by the emulated clock so they never drift.
+// Recordings start and stop between frames and on stop the audio is cut or padded to the exact length of the video.
+// Both are streamed to their files, the recording stops by itself before the audio gets too big for a wav file.
+pub struct Recorder{
+    recording:Option<Recording>
+}
+
+impl Recorder{
+    pub fn new()->Self{
+        Recorder{recording:None}
+    }
+
+    pub fn is_recording(&self)->bool{
+        self.recording.is_some()
+    }
+
+    pub fn start(&mut self, video_path:&str, width:usize, height:usize)->Result<(), String>{
+        let format = VideoFormat::from_path(video_path)?;
+        let mut file = BufWriter::new(File::create(video_path).map_err(|err|err.to_string())?);
+        let video = match format{
+            VideoFormat::Y4m=>{
+                writeln!(file, "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444 XCOLORRANGE=FULL", width, height, GB_FREQUENCY, T_CYCLES_PER_FRAME).map_err(|err|err.to_string())?;
+                VideoWriter::Y4m(file)
+            }
+            VideoFormat::Raw=>VideoWriter::Raw(file),
+            VideoFormat::Gif=>VideoWriter::Gif(GifEncoder::new(file, width as u16, height as u16).map_err(|err|err.to_string())?)
+        };
+        let audio = WavWriter::create(&get_recording_audio_path(video_path), get_audio_frequency()).map_err(|err|err.to_string())?;
+
+        self.recording = Some(Recording{
+            video_path:video_path.to_string(),
+            video,
+            width,
+            height,
+            frames:0,
+            pixels_buffer:Vec::with_capacity(width * height * 3),
+            resampler:AudioResampler::new(GB_FREQUENCY, RECORDING_AUDIO_FREQUENCY),
+            audio
+        });
+
+        return Ok(());
+    }
+
+    // Returns the path of the audio file
+    pub fn stop(&mut self)->Result<String, String>{
+        let recording = self.recording.take().ok_or(String::from("not recording"))?;
+        let video_frames = match recording.video{
+            VideoWriter::Y4m(mut file) | VideoWriter::Raw(mut file)=>{
+                file.flush().map_err(|err|err.to_string())?;
+                recording.frames
+            }
+            VideoWriter::Gif(encoder)=>{
+                encoder.finish().map_err(|err|err.to_string())?;
+                // the last gif frame is shown for the length of the skipped frame as well
+                recording.frames.div_ceil(GIF_FRAMES_STEP) * GIF_FRAMES_STEP
+            }
+        };
+
+        recording.audio.finish(get_audio_length(video_frames)).map_err(|err|err.to_string())?;
+
+        return Ok(get_recording_audio_path(&recording.video_path));
+    }
+
+    // Called after every frame, stops the recording if the frame could not be written
+    pub fn push_frame(&mut self, frame:&[u32])->Result<(), String>{
+        let recording = match self.recording.as_mut(){
+            Some(recording)=>recording,
+            None=>return Ok(())
+        };
+
+        let result = recording.write_frame(frame);
+        recording.frames += 1;
+        if let Err(err) = result{
+            self.recording = None;
+            return Err(err.to_string());
+        }
+
+        // the next frames could not fit in the wav file (a gif might need one more frame for the skipped one)
+        if get_audio_length(recording.frames + GIF_FRAMES_STEP) > WAV_MAX_SAMPLES{
+            let audio_path = self.stop()?;
+            return Err(format!("the audio reached the size limit of a wav file, the recording was saved with {}", audio_path));
+        }
+
+        return Ok(());
+    }
+
+    // Stops the recording if the samples could not be written
+    pub fn push_audio(&mut self, buffer:&[Sample]){
+        if let Some(recording) = self.recording.as_mut(){
+            let samples = recording.resampler.resample(buffer);
+            if let Err(err) = recording.audio.write_samples(&samples){
+                log::error!("could not write the audio of the recording: {}", err);
+                self.recording = None;
+            }
+        }
+    }
+}
+
+impl Recording{
+    fn write_frame(&mut self, frame:&[u32])->std::io::Result<()>{
+        self.pixels_buffer.clear();
+        match &mut self.video{
+            VideoWriter::Y4m(file)=>{
+                // every plane is written on its own (Y, then U, then V)
+                let planes:[fn(i32, i32, i32)->u8;3] = [rgb_to_y, rgb_to_u, rgb_to_v];
+                for plane in planes.iter(){
+                    self.pixels_buffer.extend(frame.iter().map(|pixel|{
+                        plane(((pixel >> 16) & 0xFF) as i32, ((pixel >> 8) & 0xFF) as i32, (pixel & 0xFF) as i32)
+                    }));
+                }
+                file.write_all(b"FRAME\n")?;
+                file.write_all(&self.pixels_buffer)
+            }
+            VideoWriter::Raw(file)=>{
+                for pixel in frame{
+                    self.pixels_buffer.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8]);
+                }
+                file.write_all(&self.pixels_buffer)
+            }
+            VideoWriter::Gif(encoder)=>{
+                if !self.frames.is_multiple_of(GIF_FRAMES_STEP){
+                    return Ok(());
+                }
+                let delay = get_centiseconds(self.frames + GIF_FRAMES_STEP) - get_centiseconds(self.frames);
+                encoder.write_frame(&frame[..self.width * self.height], delay as u16)
+            }
+        }
+    }
+}
+
+// The resampler averages a whole number of samples so the actual frequency is a bit higher than the requested one
+fn get_audio_frequency()->u32{
+    GB_FREQUENCY / (GB_FREQUENCY / RECORDING_AUDIO_FREQUENCY)
+}
+
+// The samples of the audio that plays along the frames
+fn get_audio_length(frames:u64)->u64{
+    frames * T_CYCLES_PER_FRAME * get_audio_frequency() as u64 / GB_FREQUENCY as u64
+}
+
+// The time the frame starts at, rounded to hundredths of a second
+fn get_centiseconds(frame:u64)->u64{
+    (frame * T_CYCLES_PER_FRAME * CENTISECONDS_PER_SECOND + GB_FREQUENCY as u64 / 2) / GB_FREQUENCY as u64
+}
+
+// BT.601 full range (like jpeg), the header tells the players not to expand it
+fn rgb_to_y(r:i32, g:i32, b:i32)->u8{
+    ((77 * r + 150 * g + 29 * b + 128) >> 8) as u8
+}
+
+fn rgb_to_u(r:i32, g:i32, b:i32)->u8{
+    (((-43 * r - 85 * g + 128 * b + 128) >> 8) + 128).clamp(0, 0xFF) as u8
+}
+
+fn rgb_to_v(r:i32, g:i32, b:i32)->u8{
+    (((128 * r - 107 * g - 21 * b + 128) >> 8) + 128).clamp(0, 0xFF) as u8
+}
+
+// Writes the samples as they come and fills the sizes in the header on finish
+struct WavWriter{
+    file:BufWriter<File>,
+    samples:u64
+}
+
+impl WavWriter{
+    fn create(path:&str, frequency:u32)->std::io::Result<Self>{
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"RIFF")?;
+        // the riff and data sizes are written on finish
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&WAV_FORMAT_IEEE_FLOAT.to_le_bytes())?;
+        file.write_all(&WAV_CHANNELS.to_le_bytes())?;
+        file.write_all(&frequency.to_le_bytes())?;
+        file.write_all(&(frequency * WAV_BYTES_PER_SAMPLE).to_le_bytes())?;
+        file.write_all(&(WAV_BYTES_PER_SAMPLE as u16).to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        return Ok(WavWriter{file, samples:0});
+    }
+
+    fn write_samples(&mut self, samples:&[Sample])->std::io::Result<()>{
+        for sample in samples{
+            self.file.write_all(&sample.left_sample.to_le_bytes())?;
+            self.file.write_all(&sample.right_sample.to_le_bytes())?;
+        }
+        self.samples += samples.len() as u64;
+        return Ok(());
+    }
+
+    // Pads with silence or cuts the samples to the length
+    fn finish(mut self, length:u64)->std::io::Result<()>{
+        if length > WAV_MAX_SAMPLES{
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} samples do not fit in a wav file", length)));
+        }
+        let silence = [Sample{left_sample:0.0, right_sample:0.0};0x400];
+        while self.samples < length{
+            let count = std::cmp::min(length - self.samples, silence.len() as u64) as usize;
+            self.write_samples(&silence[..count])?;
+        }
+        let mut file = self.file.into_inner().map_err(|err|err.into_error())?;
+        // fits since the length is checked above
+        let data_size = (length * WAV_BYTES_PER_SAMPLE as u64) as u32;
+        file.set_len(WAV_HEADER_SIZE as u64 + data_size as u64)?;
+        file.seek(SeekFrom::Start(WAV_RIFF_SIZE_OFFSET))?;
+        file.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        file.seek(SeekFrom::Start(WAV_DATA_SIZE_OFFSET))?;
+        file.write_all(&data_size.to_le_bytes())?;
+        return file.flush();
+    }
+}
+
+// Passes the audio of the emulation to the recorder
+pub struct RecorderAudioDevice{
+    recorder:Rc<RefCell<Recorder>>
+}
+
+impl RecorderAudioDevice{
+    pub fn new(recorder:Rc<RefCell<Recorder>>)->Self{
+        RecorderAudioDevice{recorder}
+    }
+}
+
+impl AudioDevice for RecorderAudioDevice{
+    fn push_buffer(&mut self, buffer:&[Sample]){
+        self.recorder.borrow_mut().push_audio(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn get_temp_wav_path(name:&str)->String{
+        std::env::temp_dir().join(format!("magenboy_{}_{}.wav", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    fn read_u32(file:&[u8], offset:usize)->u32{
+        u32::from_le_bytes([file[offset], file[offset + 1], file[offset + 2], file[offset + 3]])
+    }
+
+    #[test]
+    fn test_wav_sizes_are_written_on_finish(){
+        let path = get_temp_wav_path("sizes");
+        let mut writer = WavWriter::create(&path, 44100).unwrap();
+        writer.write_samples(&[Sample{left_sample:0.5, right_sample:-0.5};10]).unwrap();
+        writer.finish(4).unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(file.len(), WAV_HEADER_SIZE as usize + 4 * WAV_BYTES_PER_SAMPLE as usize);
+        assert_eq!(read_u32(&file, WAV_RIFF_SIZE_OFFSET as usize), file.len() as u32 - 8);
+        assert_eq!(read_u32(&file, WAV_DATA_SIZE_OFFSET as usize), 4 * WAV_BYTES_PER_SAMPLE);
+    }
+
+    #[test]
+    fn test_wav_rejects_a_length_past_the_riff_limit(){
+        let path = get_temp_wav_path("limit");
+        let writer = WavWriter::create(&path, 44100).unwrap();
+        let result = writer.finish(WAV_MAX_SAMPLES + 1);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
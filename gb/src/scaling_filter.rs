@@ -0,0 +1,295 @@
+use std::{fmt::{Display, Formatter}, str::FromStr};
+
+// hqx compares the colors in YUV with these thresholds
+const Y_THRESHOLD:i32 = 0x30;
+const U_THRESHOLD:i32 = 0x07;
+const V_THRESHOLD:i32 = 0x06;
+const LCD_SCALE:usize = 4;
+// the gaps between the lcd dots are dimmed to 3/4 of the dot color
+const LCD_GAP_BRIGHTNESS:u32 = 3;
+const LCD_GAP_BRIGHTNESS_DIVISOR:u32 = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScalingFilter{
+    Nearest,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+    Lcd
+}
+
+impl ScalingFilter{
+    pub const ALL:[ScalingFilter;5] = [ScalingFilter::Nearest, ScalingFilter::Scale2x, ScalingFilter::Scale3x, ScalingFilter::Hq2x, ScalingFilter::Lcd];
+
+    // How many times bigger the output of the filter is
+    pub fn get_scale(&self)->usize{
+        match self{
+            ScalingFilter::Nearest=>1,
+            ScalingFilter::Scale2x | ScalingFilter::Hq2x=>2,
+            ScalingFilter::Scale3x=>3,
+            ScalingFilter::Lcd=>LCD_SCALE
+        }
+    }
+
+    pub fn next(&self)->ScalingFilter{
+        let index = Self::ALL.iter().position(|filter|filter == self).unwrap();
+        return Self::ALL[(index + 1) % Self::ALL.len()];
+    }
+
+    // The output is (width * scale) x (height * scale), the buffer is reused between frames
+    pub fn apply(&self, frame:&[u32], width:usize, height:usize, output:&mut Vec<u32>){
+        let scale = self.get_scale();
+        output.clear();
+        output.resize(width * scale * height * scale, 0);
+        let neighbors = Neighbors{frame, width, height};
+        let mut block = [0;LCD_SCALE * LCD_SCALE];
+        let block_size = scale * scale;
+        for y in 0..height{
+            for x in 0..width{
+                match self{
+                    ScalingFilter::Nearest=>block[0] = frame[y * width + x],
+                    ScalingFilter::Scale2x=>block[..block_size].copy_from_slice(&scale2x(&neighbors.get(x, y))),
+                    ScalingFilter::Scale3x=>block[..block_size].copy_from_slice(&scale3x(&neighbors.get(x, y))),
+                    ScalingFilter::Hq2x=>block[..block_size].copy_from_slice(&hq2x(&neighbors.get(x, y))),
+                    ScalingFilter::Lcd=>block = lcd(frame[y * width + x])
+                }
+                for row in 0..scale{
+                    let start = (y * scale + row) * width * scale + x * scale;
+                    output[start..start + scale].copy_from_slice(&block[row * scale..(row + 1) * scale]);
+                }
+            }
+        }
+    }
+}
+
+impl Display for ScalingFilter{
+    fn fmt(&self, f:&mut Formatter<'_>)->std::fmt::Result{
+        let name = match self{
+            ScalingFilter::Nearest=>"nearest",
+            ScalingFilter::Scale2x=>"scale2x",
+            ScalingFilter::Scale3x=>"scale3x",
+            ScalingFilter::Hq2x=>"hq2x",
+            ScalingFilter::Lcd=>"lcd"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ScalingFilter{
+    type Err = String;
+
+    fn from_str(s:&str)->Result<Self, Self::Err>{
+        ScalingFilter::ALL.iter().find(|filter|filter.to_string() == s).copied()
+            .ok_or(format!("unknown filter: {}, the filters are nearest, scale2x, scale3x, hq2x and lcd", s))
+    }
+}
+
+// The 3x3 pixels around a pixel, the edges of the frame are repeated
+struct Neighbors<'a>{
+    frame:&'a [u32],
+    width:usize,
+    height:usize
+}
+
+impl<'a> Neighbors<'a>{
+    // Returns the pixels in the order of A B C / D E F / G H I
+    fn get(&self, x:usize, y:usize)->[u32;9]{
+        let columns = [x.saturating_sub(1), x, std::cmp::min(x + 1, self.width - 1)];
+        let rows = [y.saturating_sub(1), y, std::cmp::min(y + 1, self.height - 1)];
+        let mut pixels = [0;9];
+        for (i, pixel) in pixels.iter_mut().enumerate(){
+            *pixel = self.frame[rows[i / 3] * self.width + columns[i % 3]];
+        }
+        return pixels;
+    }
+}
+
+// AdvMAME2x, fills the corners along the diagonal edges
+fn scale2x(n:&[u32;9])->[u32;4]{
+    let (b, d, e, f, h) = (n[1], n[3], n[4], n[5], n[7]);
+    if b == h || d == f{
+        return [e;4];
+    }
+    return [
+        if d == b {d} else {e},
+        if b == f {f} else {e},
+        if d == h {d} else {e},
+        if h == f {f} else {e}
+    ];
+}
+
+// AdvMAME3x
+fn scale3x(n:&[u32;9])->[u32;9]{
+    let [a, b, c, d, e, f, g, h, i] = *n;
+    if b == h || d == f{
+        return [e;9];
+    }
+    return [
+        if d == b {d} else {e},
+        if (d == b && e != c) || (b == f && e != a) {b} else {e},
+        if b == f {f} else {e},
+        if (d == b && e != g) || (d == h && e != a) {d} else {e},
+        e,
+        if (b == f && e != i) || (h == f && e != c) {f} else {e},
+        if d == h {d} else {e},
+        if (h == f && e != g) || (d == h && e != i) {h} else {e},
+        if h == f {f} else {e}
+    ];
+}
+
+// A simplified hq2x, every corner is blended with the neighbors of its edge when they are alike and differ from the center,
+// colors are compared by the hqx YUV thresholds so near colors (like in CGB gradients) are also smoothed
+fn hq2x(n:&[u32;9])->[u32;4]{
+    let e = n[4];
+    // the orthogonal neighbors and the diagonal one of every corner
+    let corners = [(n[1], n[3], n[0]), (n[1], n[5], n[2]), (n[7], n[3], n[6]), (n[7], n[5], n[8])];
+    let mut output = [e;4];
+    for (pixel, (vertical, horizontal, diagonal)) in output.iter_mut().zip(corners.iter()){
+        if !is_similar(*vertical, *horizontal) || is_similar(e, *vertical){
+            continue;
+        }
+        *pixel = if is_similar(*diagonal, *vertical){
+            // a solid edge, the corner is mostly the other side
+            blend(&[(e, 2), (*vertical, 1), (*horizontal, 1)])
+        }
+        else{
+            // a thin line ends here, smooth it a bit less
+            blend(&[(e, 6), (*vertical, 1), (*horizontal, 1)])
+        };
+    }
+    return output;
+}
+
+// Every pixel is a dot with a dimmed gap on its right and bottom
+fn lcd(pixel:u32)->[u32;LCD_SCALE * LCD_SCALE]{
+    let gap = blend(&[(pixel, LCD_GAP_BRIGHTNESS), (0, LCD_GAP_BRIGHTNESS_DIVISOR - LCD_GAP_BRIGHTNESS)]);
+    let mut output = [pixel;LCD_SCALE * LCD_SCALE];
+    for i in 0..LCD_SCALE{
+        output[i * LCD_SCALE + LCD_SCALE - 1] = gap;
+        output[(LCD_SCALE - 1) * LCD_SCALE + i] = gap;
+    }
+    return output;
+}
+
+fn to_yuv(pixel:u32)->(i32, i32, i32){
+    let r = ((pixel >> 16) & 0xFF) as i32;
+    let g = ((pixel >> 8) & 0xFF) as i32;
+    let b = (pixel & 0xFF) as i32;
+    let y = (299 * r + 587 * g + 114 * b) / 1000;
+    let u = (-169 * r - 331 * g + 500 * b) / 1000;
+    let v = (500 * r - 419 * g - 81 * b) / 1000;
+    return (y, u, v);
+}
+
+fn is_similar(first:u32, second:u32)->bool{
+    if first == second{
+        return true;
+    }
+    let (y1, u1, v1) = to_yuv(first);
+    let (y2, u2, v2) = to_yuv(second);
+    return (y1 - y2).abs() <= Y_THRESHOLD && (u1 - u2).abs() <= U_THRESHOLD && (v1 - v2).abs() <= V_THRESHOLD;
+}
+
+// A weighted average of every channel
+fn blend(colors:&[(u32, u32)])->u32{
+    let total:u32 = colors.iter().map(|(_, weight)|weight).sum();
+    let mut output = 0;
+    for shift in [16, 8, 0].iter(){
+        let channel:u32 = colors.iter().map(|(color, weight)|((color >> shift) & 0xFF) * weight).sum();
+        output |= (channel / total) << shift;
+    }
+    return output;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const W:u32 = 0xFFFFFF;
+    const K:u32 = 0x000000;
+    // the top left corner of a black frame around a white area
+    const CORNER:[u32;9] = [
+        K, K, K,
+        K, W, W,
+        K, W, W
+    ];
+
+    // The output block of the center pixel of a 3x3 frame
+    fn get_center_block(filter:ScalingFilter, frame:&[u32;9])->Vec<u32>{
+        let scale = filter.get_scale();
+        let mut output = Vec::new();
+        filter.apply(frame, 3, 3, &mut output);
+        assert_eq!(output.len(), 9 * scale * scale);
+        let mut block = Vec::new();
+        for row in 0..scale{
+            let start = (scale + row) * 3 * scale + scale;
+            block.extend_from_slice(&output[start..start + scale]);
+        }
+        return block;
+    }
+
+    #[test]
+    fn test_nearest(){
+        assert_eq!(get_center_block(ScalingFilter::Nearest, &CORNER), [W]);
+    }
+
+    #[test]
+    fn test_scale2x(){
+        assert_eq!(get_center_block(ScalingFilter::Scale2x, &CORNER), [
+            K, W,
+            W, W
+        ]);
+        // a straight edge is not changed
+        assert_eq!(get_center_block(ScalingFilter::Scale2x, &[K, K, K, W, W, W, W, W, W]), [W;4]);
+    }
+
+    #[test]
+    fn test_scale3x(){
+        assert_eq!(get_center_block(ScalingFilter::Scale3x, &CORNER), [
+            K, K, W,
+            K, W, W,
+            W, W, W
+        ]);
+    }
+
+    #[test]
+    fn test_hq2x(){
+        // a solid corner
+        assert_eq!(get_center_block(ScalingFilter::Hq2x, &CORNER), [
+            0x7F7F7F, W,
+            W,        W
+        ]);
+        // the end of a thin line
+        let mut line_end = CORNER;
+        line_end[0] = W;
+        assert_eq!(get_center_block(ScalingFilter::Hq2x, &line_end), [
+            0xBFBFBF, W,
+            W,        W
+        ]);
+        // colors within the thresholds are smoothed as well
+        let mut near_corner = CORNER;
+        near_corner[1] = 0x080808;
+        assert_eq!(get_center_block(ScalingFilter::Hq2x, &near_corner)[0], 0x818181);
+    }
+
+    #[test]
+    fn test_lcd(){
+        let (p, g) = (0x804020, 0x603018);
+        let mut output = Vec::new();
+        ScalingFilter::Lcd.apply(&[p], 1, 1, &mut output);
+        assert_eq!(output, [
+            p, p, p, g,
+            p, p, p, g,
+            p, p, p, g,
+            g, g, g, g
+        ]);
+    }
+
+    #[test]
+    fn test_names(){
+        for filter in ScalingFilter::ALL.iter(){
+            assert!(filter.to_string().parse::<ScalingFilter>() == Ok(*filter));
+        }
+        assert!("hq4x".parse::<ScalingFilter>().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use std::{cell::Cell, rc::Rc};
+use lib_gb::keypad::{
+    joypad::{Joypad, NUM_OF_KEYS},
+    joypad_provider::JoypadProvider,
+    button::Button
+};
+
+// Every line of the script holds a frame number and the buttons held from that frame on, for example:
+// 120 start
+// 125
+// 300 a right
+// An empty buttons list releases all the buttons, lines starting with # are ignored
+struct InputEvent{
+    frame:u32,
+    buttons:[bool;NUM_OF_KEYS]
+}
+
+pub struct ScriptedJoypadProvider{
+    events:Vec<InputEvent>,
+    current_frame:Rc<Cell<u32>>
+}
+
+impl ScriptedJoypadProvider{
+    pub fn new(script:&str, current_frame:Rc<Cell<u32>>)->Result<Self, String>{
+        let mut events = Vec::new();
+        for (line_number, line) in script.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let frame = tokens.next().unwrap().parse::<u32>()
+                .map_err(|err| format!("line {}: bad frame number: {}", line_number + 1, err))?;
+
+            let mut buttons = [false;NUM_OF_KEYS];
+            for token in tokens{
+                let button = Self::parse_button(token)
+                    .ok_or_else(|| format!("line {}: unknown button: {}", line_number + 1, token))?;
+                buttons[button as usize] = true;
+            }
+
+            events.push(InputEvent{frame, buttons});
+        }
+
+        events.sort_by_key(|event| event.frame);
+
+        Ok(ScriptedJoypadProvider{events, current_frame})
+    }
+
+    pub fn empty(current_frame:Rc<Cell<u32>>)->Self{
+        ScriptedJoypadProvider{events:Vec::new(), current_frame}
+    }
+
+    fn parse_button(name:&str)->Option<Button>{
+        match name.to_lowercase().as_str(){
+            "a"         =>Some(Button::A),
+            "b"         =>Some(Button::B),
+            "start"     =>Some(Button::Start),
+            "select"    =>Some(Button::Select),
+            "up"        =>Some(Button::Up),
+            "down"      =>Some(Button::Down),
+            "right"     =>Some(Button::Right),
+            "left"      =>Some(Button::Left),
+            _=>None
+        }
+    }
+}
+
+impl JoypadProvider for ScriptedJoypadProvider{
+    fn provide(&mut self, joypad:&mut Joypad) {
+        let frame = self.current_frame.get();
+        joypad.buttons = match self.events.iter().rev().find(|event| event.frame <= frame){
+            Some(event)=>event.buttons,
+            None=>[false;NUM_OF_KEYS]
+        };
+    }
+}
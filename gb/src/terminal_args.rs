@@ -0,0 +1,20 @@
+use lib_gb::ppu::dmg_palette::DmgPalette;
+
+pub fn check_for_terminal_feature_flag(args:&Vec::<String>, flag:&str)->bool{
+    args.len() >= 3 && args.contains(&String::from(flag))
+}
+
+pub fn get_terminal_flag_value(args:&[String], flag:&str)->Option<String>{
+    let index = args.iter().position(|arg| arg == flag)?;
+    args.get(index + 1).cloned()
+}
+
+// --palette takes a preset name or the colors and --palette-file a file with either of them
+pub fn get_dmg_palette(args:&[String])->Result<Option<DmgPalette>, String>{
+    let text = match (get_terminal_flag_value(args, "--palette"), get_terminal_flag_value(args, "--palette-file")){
+        (Some(value), _)=>value,
+        (None, Some(path))=>std::fs::read_to_string(&path).map_err(|err|format!("could not read palette file {}: {}", path, err))?,
+        (None, None)=>return Ok(None)
+    };
+    return text.trim().parse::<DmgPalette>().map(Some);
+}
@@ -5,14 +5,14 @@ use crate::audio_resampler::AudioResampler;
 pub struct WavfileAudioDevice{
     target_frequency:u32,
     resampler: AudioResampler,
-    filename:&'static str,
+    filename:String,
     samples_buffer:Vec::<Sample>
 }
 
 impl WavfileAudioDevice{
-    pub fn new(target_freq:u32, original_freq:u32, filename:&'static str)->Self{
+    pub fn new(target_freq:u32, original_freq:u32, filename:&str)->Self{
         WavfileAudioDevice{
-            filename,
+            filename: filename.to_string(),
             resampler: AudioResampler::new(original_freq, target_freq),
             samples_buffer: Vec::new(),
             target_frequency: target_freq
@@ -36,7 +36,7 @@ impl Drop for WavfileAudioDevice{
         }
 
         let data = wav::BitDepth::ThirtyTwoFloat(floats);
-        let mut otuput_file = std::fs::File::create(self.filename).unwrap();
+        let mut otuput_file = std::fs::File::create(&self.filename).unwrap();
         wav::write(header, &data, &mut otuput_file).unwrap();
     }
 }
\ No newline at end of file
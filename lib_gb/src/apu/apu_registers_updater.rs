@@ -71,10 +71,11 @@ pub fn set_nr52<AD:AudioDevice>(apu:&mut GbApu<AD>, ports:&mut [u8;IO_PORTS_SIZE
 }
 
 pub fn get_nr52<AD:AudioDevice>(apu:&GbApu<AD>, nr52:&mut u8){
-    set_bit_u8(nr52, 3, apu.noise_channel.enabled && apu.noise_channel.length_enable && apu.noise_channel.sound_length != 0);
-    set_bit_u8(nr52, 2, apu.wave_channel.enabled && apu.wave_channel.length_enable && apu.wave_channel.sound_length != 0);
-    set_bit_u8(nr52, 1, apu.tone_channel.enabled && apu.tone_channel.length_enable && apu.tone_channel.sound_length != 0);
-    set_bit_u8(nr52, 0, apu.sweep_tone_channel.enabled && apu.sweep_tone_channel.length_enable && apu.sweep_tone_channel.sound_length != 0);
+    // a channel reads as on until its length expires or its dac is turned off, with or without the length counter
+    set_bit_u8(nr52, 3, apu.noise_channel.enabled);
+    set_bit_u8(nr52, 2, apu.wave_channel.enabled);
+    set_bit_u8(nr52, 1, apu.tone_channel.enabled);
+    set_bit_u8(nr52, 0, apu.sweep_tone_channel.enabled);
 }
 
 pub fn set_nr30(channel:&mut Channel<WaveSampleProducer>, value:u8){
@@ -1,5 +1,6 @@
 use super::sample_producer::SampleProducer;
 use super::timer::Timer;
+use crate::save_state::*;
 
 pub struct Channel<Procuder: SampleProducer>{
     pub enabled:bool,
@@ -70,3 +71,26 @@ impl<Procuder: SampleProducer> Channel<Procuder>{
         (sample as f32 / 7.5 ) - 1.0
     }
 }
+
+impl<Procuder: SampleProducer + SaveState> SaveState for Channel<Procuder>{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bool(self.enabled);
+        writer.write_u16(self.frequency);
+        writer.write_u16(self.sound_length);
+        writer.write_bool(self.length_enable);
+        self.sample_producer.save_state(writer);
+        self.timer.save_state(writer);
+        writer.write_u8(self.last_sample);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.enabled = reader.read_bool()?;
+        self.frequency = reader.read_u16()?;
+        self.sound_length = reader.read_u16()?;
+        self.length_enable = reader.read_bool()?;
+        self.sample_producer.load_state(reader)?;
+        self.timer.load_state(reader)?;
+        self.last_sample = reader.read_u8()?;
+        Ok(())
+    }
+}
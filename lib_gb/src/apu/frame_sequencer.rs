@@ -1,4 +1,5 @@
 use super::timer::Timer;
+use crate::save_state::*;
 
 pub struct TickType{
     pub length_counter:bool,
@@ -59,4 +60,17 @@ impl FrameSequencer{
         self.timer.update_cycles_to_tick(8192);
         self.counter = 0;
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for FrameSequencer{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.timer.save_state(writer);
+        writer.write_u8(self.counter);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.timer.load_state(reader)?;
+        self.counter = reader.read_u8()?;
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@ use super::{
     wave_sample_producer::WaveSampleProducer,
     sound_utils::NUMBER_OF_CHANNELS
 };
+use crate::save_state::*;
 
 pub const AUDIO_BUFFER_SIZE:usize = 0x400;
 
@@ -155,3 +156,31 @@ impl<Device: AudioDevice> GbApu<Device>{
         }
     }
 }
+
+// The audio buffer is not part of the state, it only holds samples that are yet to be pushed to the device
+impl<Device: AudioDevice> SaveState for GbApu<Device>{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.wave_channel.save_state(writer);
+        self.sweep_tone_channel.save_state(writer);
+        self.tone_channel.save_state(writer);
+        self.noise_channel.save_state(writer);
+        self.frame_sequencer.save_state(writer);
+        self.right_terminal.save_state(writer);
+        self.left_terminal.save_state(writer);
+        writer.write_bool(self.enabled);
+        writer.write_bool(self.last_enabled_state);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.wave_channel.load_state(reader)?;
+        self.sweep_tone_channel.load_state(reader)?;
+        self.tone_channel.load_state(reader)?;
+        self.noise_channel.load_state(reader)?;
+        self.frame_sequencer.load_state(reader)?;
+        self.right_terminal.load_state(reader)?;
+        self.left_terminal.load_state(reader)?;
+        self.enabled = reader.read_bool()?;
+        self.last_enabled_state = reader.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::utils::bit_masks::set_bit_u16;
+use crate::{utils::bit_masks::set_bit_u16, save_state::*};
 
 use super::{sample_producer::SampleProducer, volume_envelop::VolumeEnvlope};
 
@@ -59,4 +59,23 @@ impl SampleProducer for NoiseSampleProducer{
 
         divisor << self.bits_to_shift_divisor
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for NoiseSampleProducer{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.envelop.save_state(writer);
+        writer.write_u16(self.lfsr);
+        writer.write_u8(self.bits_to_shift_divisor);
+        writer.write_bool(self.width_mode);
+        writer.write_u8(self.divisor_code);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.envelop.load_state(reader)?;
+        self.lfsr = reader.read_u16()?;
+        self.bits_to_shift_divisor = reader.read_u8()?;
+        self.width_mode = reader.read_bool()?;
+        self.divisor_code = reader.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::sound_utils::NUMBER_OF_CHANNELS;
+use crate::save_state::*;
 
 pub struct SoundTerminal{
     pub enabled:bool,
@@ -29,4 +30,23 @@ impl SoundTerminal{
 
         return mixed_sample * (self.volume as f32 + 1.0);
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for SoundTerminal{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bool(self.enabled);
+        writer.write_u8(self.volume);
+        for channel in self.channels.iter(){
+            writer.write_bool(*channel);
+        }
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.enabled = reader.read_bool()?;
+        self.volume = reader.read_u8()?;
+        for channel in self.channels.iter_mut(){
+            *channel = reader.read_bool()?;
+        }
+        Ok(())
+    }
+}
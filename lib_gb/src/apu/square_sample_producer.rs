@@ -1,6 +1,7 @@
 use super::{sample_producer::SampleProducer, sound_utils::DUTY_TABLE};
 use super::freq_sweep::FreqSweep;
 use super::volume_envelop::VolumeEnvlope;
+use crate::save_state::*;
 
 pub struct SquareSampleProducer{
     pub wave_duty:u8,
@@ -62,3 +63,26 @@ impl SampleProducer for SquareSampleProducer{
     }
 }
 
+impl SaveState for SquareSampleProducer{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u8(self.wave_duty);
+        if let Some(sweep) = self.sweep.as_ref(){
+            sweep.save_state(writer);
+        }
+        self.envelop.save_state(writer);
+        writer.write_u8(self.duty_sample_pointer);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.wave_duty = reader.read_u8()?;
+        if let Some(sweep) = self.sweep.as_mut(){
+            sweep.load_state(reader)?;
+        }
+        self.envelop.load_state(reader)?;
+        self.duty_sample_pointer = reader.read_u8()?;
+        if self.wave_duty as usize >= DUTY_TABLE.len() || self.duty_sample_pointer >= 8{
+            return Err(SaveStateError::InvalidValue("square channel duty"));
+        }
+        Ok(())
+    }
+}
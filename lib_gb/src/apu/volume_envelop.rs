@@ -1,3 +1,5 @@
+use crate::save_state::*;
+
 pub struct VolumeEnvlope{
     pub volume:u8,
     pub current_volume:u8,
@@ -45,4 +47,23 @@ impl Default for VolumeEnvlope{
             envelop_duration_counter:0
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for VolumeEnvlope{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u8(self.volume);
+        writer.write_u8(self.current_volume);
+        writer.write_bool(self.increase_envelope);
+        writer.write_u8(self.number_of_envelope_sweep);
+        writer.write_u8(self.envelop_duration_counter);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.volume = reader.read_u8()?;
+        self.current_volume = reader.read_u8()?;
+        self.increase_envelope = reader.read_bool()?;
+        self.number_of_envelope_sweep = reader.read_u8()?;
+        self.envelop_duration_counter = reader.read_u8()?;
+        Ok(())
+    }
+}
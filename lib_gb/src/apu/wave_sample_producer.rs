@@ -1,4 +1,5 @@
 use super::sample_producer::SampleProducer;
+use crate::save_state::*;
 
 pub struct WaveSampleProducer{
     pub wave_samples:[u8;16],
@@ -57,4 +58,22 @@ impl WaveSampleProducer{
             _=>std::panic!("wave channel volume value is invalid {}", self.volume)
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for WaveSampleProducer{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.wave_samples);
+        writer.write_u8(self.volume);
+        writer.write_u8(self.sample_counter);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.wave_samples, "wave ram")?;
+        self.volume = reader.read_u8()?;
+        self.sample_counter = reader.read_u8()?;
+        if self.volume > 3 || self.sample_counter >= 32{
+            return Err(SaveStateError::InvalidValue("wave channel"));
+        }
+        Ok(())
+    }
+}
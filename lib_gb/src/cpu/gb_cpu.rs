@@ -1,5 +1,10 @@
 use super::register::Reg;
 use super::flag::Flag;
+use crate::{save_state::*, error::StopReason};
+
+const NO_STOP_REASON:u8 = 0;
+const ILLEGAL_OPCODE_STOP_REASON:u8 = 1;
+const INVALID_STOP_STOP_REASON:u8 = 2;
 
 pub struct GbCpu {
     pub af: Reg,
@@ -12,7 +17,9 @@ pub struct GbCpu {
     pub halt:bool,
     pub stop:bool,
     pub cgb_mode:bool,
-    pub double_speed:bool
+    pub double_speed:bool,
+    // Set once the cpu locked up, a locked cpu never executes again
+    pub stop_reason:Option<StopReason>
 }
 
 impl Default for GbCpu {
@@ -28,7 +35,8 @@ impl Default for GbCpu {
             halt:false,
             stop:false,
             cgb_mode:false,
-            double_speed:false
+            double_speed:false,
+            stop_reason:None
         }
     }
 }
@@ -64,3 +72,53 @@ impl GbCpu {
         *self.hl.value() = (*self.hl.value()).wrapping_sub(1);
     }
 }
+
+impl SaveState for GbCpu{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.af.save_state(writer);
+        self.bc.save_state(writer);
+        self.de.save_state(writer);
+        self.hl.save_state(writer);
+        writer.write_u16(self.stack_pointer);
+        writer.write_u16(self.program_counter);
+        writer.write_bool(self.mie);
+        writer.write_bool(self.halt);
+        writer.write_bool(self.stop);
+        writer.write_bool(self.cgb_mode);
+        writer.write_bool(self.double_speed);
+        match self.stop_reason{
+            None=>writer.write_u8(NO_STOP_REASON),
+            Some(StopReason::IllegalOpcode{opcode, address})=>{
+                writer.write_u8(ILLEGAL_OPCODE_STOP_REASON);
+                writer.write_u8(opcode);
+                writer.write_u16(address);
+            }
+            Some(StopReason::InvalidStop{second_byte, address})=>{
+                writer.write_u8(INVALID_STOP_STOP_REASON);
+                writer.write_u8(second_byte);
+                writer.write_u16(address);
+            }
+        }
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.af.load_state(reader)?;
+        self.bc.load_state(reader)?;
+        self.de.load_state(reader)?;
+        self.hl.load_state(reader)?;
+        self.stack_pointer = reader.read_u16()?;
+        self.program_counter = reader.read_u16()?;
+        self.mie = reader.read_bool()?;
+        self.halt = reader.read_bool()?;
+        self.stop = reader.read_bool()?;
+        self.cgb_mode = reader.read_bool()?;
+        self.double_speed = reader.read_bool()?;
+        self.stop_reason = match reader.read_u8()?{
+            NO_STOP_REASON=>None,
+            ILLEGAL_OPCODE_STOP_REASON=>Some(StopReason::IllegalOpcode{opcode:reader.read_u8()?, address:reader.read_u16()?}),
+            INVALID_STOP_STOP_REASON=>Some(StopReason::InvalidStop{second_byte:reader.read_u8()?, address:reader.read_u16()?}),
+            _=>return Err(SaveStateError::InvalidValue("cpu stop reason"))
+        };
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::mmu::memory::Memory;
+use crate::{mmu::memory::Memory, error::StopReason};
 use super::{
     gb_cpu::GbCpu, 
     opcodes::{
@@ -29,7 +29,8 @@ impl GbCpu{
                     stop(self, memory)
                 }
                 else{
-                    std::panic!("Invalid stop opcode, second byte: {:#X}", next_byte);
+                    self.stop_reason = Some(StopReason::InvalidStop{second_byte:next_byte, address:self.program_counter.wrapping_sub(2)});
+                    1
                 }
             }
     
@@ -166,7 +167,11 @@ impl GbCpu{
                 }
             },
     
-            _=>std::panic!("Unsupported opcode:{:#X}", opcode)
+            // The illegal opcodes lock the cpu
+            _=>{
+                self.stop_reason = Some(StopReason::IllegalOpcode{opcode, address:self.program_counter.wrapping_sub(1)});
+                1
+            }
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::{cpu::gb_cpu::GbCpu, utils::memory_registers::{IE_REGISTER_ADDRESS, JOYP_REGISTER_ADDRESS}};
+use crate::{cpu::gb_cpu::GbCpu, utils::{bit_masks::BIT_0_MASK, memory_registers::{IE_REGISTER_ADDRESS, JOYP_REGISTER_ADDRESS, KEY1_REGISTER_ADDRESS}}};
 use crate::cpu::flag::Flag;
 use crate::mmu::memory::Memory;
 
@@ -29,6 +29,15 @@ pub fn halt(cpu:&mut GbCpu)->u8{
 }
 
 pub fn stop(cpu:&mut GbCpu, memory: &mut impl Memory)->u8{
+    // On CGB stop switches the cpu speed when the switch is armed in KEY1
+    if cpu.cgb_mode && memory.read(KEY1_REGISTER_ADDRESS) & BIT_0_MASK != 0{
+        cpu.double_speed = !cpu.double_speed;
+        memory.write(KEY1_REGISTER_ADDRESS, 0);
+
+        //cycles
+        return 1;
+    }
+
     if (memory.read(IE_REGISTER_ADDRESS) & 0b11111 == 0) && (memory.read(JOYP_REGISTER_ADDRESS) & 0b1111 == 0){
         cpu.stop = true;
     }
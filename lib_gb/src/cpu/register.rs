@@ -1,3 +1,4 @@
+use crate::save_state::*;
 
 const LOW_POSITION:isize = 0;
 const HIGH_POSITION:isize = 1;
@@ -49,4 +50,15 @@ impl Reg{
     fn mask(&mut self){
         self.value &= self.read_only_mask;
     }
-} 
\ No newline at end of file
+} 
+
+impl SaveState for Reg{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u16(self.value);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.value = reader.read_u16()? & self.read_only_mask;
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError{
+    UnsupportedCartridgeType(u8),
+    RomTooSmall(usize),
+    InvalidRamSize(u8),
+    SaveDataSizeMismatch{expected:usize, found:usize},
+    InvalidRomSize(u8),
+    RomSizeMismatch{expected:usize, found:usize},
+    HeaderChecksumMismatch{expected:u8, found:u8},
+    GlobalChecksumMismatch{expected:u16, found:u16}
+}
+
+impl fmt::Display for CartridgeError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            CartridgeError::UnsupportedCartridgeType(cartridge_type)=>write!(f, "cartridge type {:#X} is not supported", cartridge_type),
+            CartridgeError::RomTooSmall(size)=>write!(f, "the rom is too small to contain a cartridge header ({} bytes)", size),
+            CartridgeError::InvalidRamSize(ram_size_register)=>write!(f, "invalid ram size register {:#X}", ram_size_register),
+            CartridgeError::SaveDataSizeMismatch{expected, found}=>
+                write!(f, "the save data size ({:#X}) does not match the cartridge ram size ({:#X})", found, expected),
+            CartridgeError::InvalidRomSize(rom_size_register)=>write!(f, "invalid rom size register {:#X}", rom_size_register),
+            CartridgeError::RomSizeMismatch{expected, found}=>
+                write!(f, "the rom size ({:#X}) does not match the size in the header ({:#X})", found, expected),
+            CartridgeError::HeaderChecksumMismatch{expected, found}=>
+                write!(f, "header checksum mismatch, header says {:#X} but the calculated checksum is {:#X}", expected, found),
+            CartridgeError::GlobalChecksumMismatch{expected, found}=>
+                write!(f, "global checksum mismatch, header says {:#X} but the calculated checksum is {:#X}", expected, found)
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError{}
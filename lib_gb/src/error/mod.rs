@@ -0,0 +1,5 @@
+pub mod cartridge_error;
+pub mod stop_reason;
+
+pub use cartridge_error::CartridgeError;
+pub use stop_reason::StopReason;
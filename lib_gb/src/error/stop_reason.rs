@@ -0,0 +1,17 @@
+use std::fmt;
+
+// The reasons the cpu could lock up, on real hardware the cpu just hangs until the gameboy is turned off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason{
+    IllegalOpcode{opcode:u8, address:u16},
+    InvalidStop{second_byte:u8, address:u16}
+}
+
+impl fmt::Display for StopReason{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            StopReason::IllegalOpcode{opcode, address}=>write!(f, "illegal opcode {:#X} at {:#X}", opcode, address),
+            StopReason::InvalidStop{second_byte, address}=>write!(f, "invalid stop opcode second byte {:#X} at {:#X}", second_byte, address)
+        }
+    }
+}
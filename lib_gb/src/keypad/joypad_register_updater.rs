@@ -1,4 +1,4 @@
-use crate::{mmu::memory::UnprotectedMemory, 
+use crate::{mmu::memory::UnprotectedMemory, sgb::gb_sgb::GbSgb,
     utils::{bit_masks::{BIT_4_MASK, BIT_5_MASK, set_bit_u8}, memory_registers::JOYP_REGISTER_ADDRESS}};
 use super::{button::Button, joypad::Joypad};
 
@@ -22,4 +22,22 @@ pub fn update_joypad_registers(joypad:&Joypad, memory:&mut impl UnprotectedMemor
     }
 
     memory.write_unprotected(JOYP_REGISTER_ADDRESS, state);
-}
\ No newline at end of file
+}
+
+// On the SGB the JOYP writes also send packets and with multiple controllers (MLT_REQ) the lower nibble reads
+// the current controller id while no line is selected, only the first controller is connected to the joypad provider
+pub fn update_sgb_joypad_registers(joypad:&Joypad, sgb:&mut GbSgb, memory:&mut impl UnprotectedMemory){
+    let state = memory.read_unprotected(JOYP_REGISTER_ADDRESS);
+    let select = state & (BIT_4_MASK | BIT_5_MASK);
+    sgb.update_joypad_select(select);
+
+    if select == BIT_4_MASK | BIT_5_MASK{
+        memory.write_unprotected(JOYP_REGISTER_ADDRESS, (state & 0xF0) | (0xF - sgb.get_current_player()));
+    }
+    else if sgb.get_current_player() == 0{
+        update_joypad_registers(joypad, memory);
+    }
+    else{
+        update_joypad_registers(&Joypad::default(), memory);
+    }
+}
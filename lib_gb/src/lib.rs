@@ -5,6 +5,10 @@ pub mod mmu;
 pub mod keypad;
 pub mod apu;
 pub mod timer;
+pub mod serial;
+pub mod sgb;
+pub mod save_state;
+pub mod error;
 
 mod utils;
 pub use utils::GB_FREQUENCY;
\ No newline at end of file
@@ -2,77 +2,152 @@ use crate::{
     apu::{audio_device::AudioDevice, gb_apu::GbApu}, 
     cpu::gb_cpu::GbCpu, 
     keypad::{joypad::Joypad, joypad_provider::JoypadProvider, joypad_register_updater},
-    mmu::{carts::mbc::Mbc, gb_mmu::{GbMmu, BOOT_ROM_SIZE}, memory::Memory}, 
-    ppu::{gb_ppu::{CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH}}
+    mmu::{carts::mbc::Mbc, gb_mmu::{GbMmu, BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE}, memory::{Memory, UnprotectedMemory}}, 
+    ppu::{gb_ppu::{CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH}, compatibility_palettes::*, ppu_renderer::PpuRenderer, dmg_palette::DmgPalette, frame_sink::*, png_writer::write_png},
+    save_state::*,
+    serial::{gb_serial::GbSerial, serial_device::SerialDevice},
+    sgb::gb_sgb::{GbSgb, SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH},
+    error::StopReason,
+    utils::{bit_masks::{BIT_0_MASK, BIT_7_MASK}, memory_registers::*}
 };
-use super::interrupts_handler::InterruptsHandler;
-use std::boxed::Box;
+use super::{interrupts_handler::InterruptsHandler, model::Model};
+use std::{boxed::Box, io::Write};
 use log::debug;
 
+const SAVE_STATE_MAGIC:[u8;4] = *b"MGBS";
+// Bump this on every change to the layout of the state of any component
+pub const SAVE_STATE_VERSION:u16 = 11;
+const HEADER_CHECKSUM_ADDRESS:u16 = 0x14D;
+const GLOBAL_CHECKSUM_ADDRESS:u16 = 0x14E;
+const CGB_FLAG_ADDRESS:u16 = 0x143;
+const TITLE_ADDRESS:u16 = 0x134;
+const NEW_LICENSEE_CODE_ADDRESS:u16 = 0x144;
+const OLD_LICENSEE_CODE_ADDRESS:u16 = 0x14B;
+// The IO registers the bootroms leave with the same values on every model, NR52 is written before them and depends on the model
+const POST_BOOT_IO_REGISTERS:[(u16, u8);21] = [
+    (NR10_REGISTER_ADDRESS, 0x80), (NR11_REGISTER_ADDRESS, 0xBF), (NR12_REGISTER_ADDRESS, 0xF3),
+    (NR13_REGISTER_ADDRESS, 0xFF), (NR14_REGISTER_ADDRESS, 0x3F), (NR21_REGISTER_ADDRESS, 0x3F), (NR22_REGISTER_ADDRESS, 0x00),
+    (NR23_REGISTER_ADDRESS, 0xFF), (NR24_REGISTER_ADDRESS, 0x3F), (NR30_REGISTER_ADDRESS, 0x7F), (NR31_REGISTER_ADDRESS, 0xFF),
+    (NR32_REGISTER_ADDRESS, 0x9F), (NR33_REGISTER_ADDRESS, 0xFF), (NR34_REGISTER_ADDRESS, 0x3F), (NR41_REGISTER_ADDRESS, 0xFF),
+    (NR44_REGISTER_ADDRESS, 0x3F), (NR50_REGISTER_ADDRESS, 0x77), (NR51_REGISTER_ADDRESS, 0xF3),
+    (IF_REGISTER_ADDRESS, 0xE1), (BGP_REGISTER_ADDRESS, 0xFC), (LCDC_REGISTER_ADDRESS, 0x91)
+];
+// The frame is counted in half cycles since in double speed every cpu cycle is half of a normal cycle
+const HALF_CYCLES_PER_FRAME:u32 = CYCLES_PER_FRAME * 2;
 
-pub struct GameBoy<'a, JP: JoypadProvider, AD:AudioDevice> {
+pub struct GameBoy<'a, JP: JoypadProvider, AD:AudioDevice, SD:SerialDevice> {
     cpu: GbCpu,
-    mmu: GbMmu::<'a, AD>,
+    mmu: GbMmu::<'a, AD, SD>,
     interrupts_handler:InterruptsHandler,
     cycles_counter:u32, 
-    joypad_provider: JP
+    joypad_provider: JP,
+    // Some when running on a SGB
+    sgb:Option<GbSgb>,
+    frame_output:Option<FrameOutput<'a>>
 }
 
-impl<'a, JP:JoypadProvider, AD:AudioDevice> GameBoy<'a, JP, AD>{
+impl<'a, JP:JoypadProvider, AD:AudioDevice, SD:SerialDevice> GameBoy<'a, JP, AD, SD>{
 
-    pub fn new_with_bootrom(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD, boot_rom:[u8;BOOT_ROM_SIZE])->GameBoy<JP, AD>{
-        GameBoy{
+    // Runs on a DMG or SGB model (the CGB models boot only through the CGB bootrom), the bootrom sets the rest of the state
+    pub fn new_with_bootrom(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD, serial_device:SD, boot_rom:[u8;BOOT_ROM_SIZE], model:Model)->GameBoy<JP, AD, SD>{
+        let gameboy = GameBoy{
             cpu:GbCpu::default(),
-            mmu:GbMmu::new_with_bootrom(mbc, boot_rom, GbApu::new(audio_device)),
+            mmu:GbMmu::new_with_bootrom(mbc, boot_rom.to_vec(), GbApu::new(audio_device), GbSerial::new(serial_device)),
             interrupts_handler: InterruptsHandler::default(),
             cycles_counter:0,
-            joypad_provider: joypad_provider
-        }
+            joypad_provider: joypad_provider,
+            sgb:if model.is_sgb() {Some(GbSgb::default())} else {None},
+            frame_output:None
+        };
+
+        return gameboy;
     }
 
-    pub fn new(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD)->GameBoy<JP, AD>{
-        let mut cpu = GbCpu::default();
-        //Values after the bootrom
-        *cpu.af.value() = 0x190;
-        *cpu.bc.value() = 0x13;
-        *cpu.de.value() = 0xD8;
-        *cpu.hl.value() = 0x14D;
-        cpu.stack_pointer = 0xFFFE;
-        cpu.program_counter = 0x100;
+    // Runs on a CGB, the bootrom runs in CGB mode and selects the mode of the cartridge when it finishes
+    pub fn new_with_cgb_bootrom(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD, serial_device:SD, boot_rom:[u8;CGB_BOOT_ROM_SIZE])->GameBoy<JP, AD, SD>{
+        let mut gameboy = GameBoy{
+            cpu:GbCpu::default(),
+            mmu:GbMmu::new_with_bootrom(mbc, boot_rom.to_vec(), GbApu::new(audio_device), GbSerial::new(serial_device)),
+            interrupts_handler: InterruptsHandler::default(),
+            cycles_counter:0,
+            joypad_provider: joypad_provider,
+            sgb:None,
+            frame_output:None
+        };
+        gameboy.mmu.io_components.cgb_hardware = true;
+        gameboy.set_cgb_mode(true);
+
+        return gameboy;
+    }
+
+    // Runs on the model the cartridge header asks for
+    pub fn new(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD, serial_device:SD)->GameBoy<JP, AD, SD>{
+        let model = Model::from_cartridge(mbc.as_ref());
+        return Self::new_with_model(mbc, joypad_provider, audio_device, serial_device, model);
+    }
 
-        GameBoy{
-            cpu:cpu,
-            mmu:GbMmu::new(mbc, GbApu::new(audio_device)),
+    // Starts at the cartridge with the state the bootrom of the model leaves,
+    // DMG cartridges on a CGB run in the DMG compatibility mode with the palettes the bootrom would select
+    pub fn new_with_model(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD, serial_device:SD, model:Model)->GameBoy<JP, AD, SD>{
+        let mut gameboy = GameBoy{
+            cpu:GbCpu::default(),
+            mmu:GbMmu::new(mbc, GbApu::new(audio_device), GbSerial::new(serial_device)),
             interrupts_handler: InterruptsHandler::default(),
             cycles_counter:0,
             joypad_provider: joypad_provider,
-        }
+            sgb:if model.is_sgb() {Some(GbSgb::default())} else {None},
+            frame_output:None
+        };
+        gameboy.init_post_boot_state(model);
+
+        return gameboy;
+    }
+
+    // Like new_with_model and every frame is also pushed to the sink in its pixel format
+    pub fn new_with_frame_sink(mbc:&'a mut Box<dyn Mbc>,joypad_provider:JP, audio_device:AD, serial_device:SD, model:Model, frame_sink:Box<dyn FrameSink + 'a>)->GameBoy<'a, JP, AD, SD>{
+        let mut gameboy = Self::new_with_model(mbc, joypad_provider, audio_device, serial_device, model);
+        gameboy.frame_output = Some(FrameOutput::new(frame_sink));
+
+        return gameboy;
     }
 
-    pub fn cycle_frame(&mut self)->&[u32;SCREEN_HEIGHT*SCREEN_WIDTH]{
+    // The frame is get_frame_size() pixels, on the SGB it includes the border
+    pub fn cycle_frame(&mut self)->&[u32]{
         let mut joypad = Joypad::default();
 
         let mut last_ppu_power_state:bool = self.mmu.io_components.ppu.screen_enable;
 
-        while self.cycles_counter < CYCLES_PER_FRAME{
+        while self.cycles_counter < HALF_CYCLES_PER_FRAME{
             self.joypad_provider.provide(&mut joypad);
-            joypad_register_updater::update_joypad_registers(&joypad, &mut self.mmu);
+            match &mut self.sgb{
+                Some(sgb)=>joypad_register_updater::update_sgb_joypad_registers(&joypad, sgb, &mut self.mmu),
+                None=>joypad_register_updater::update_joypad_registers(&joypad, &mut self.mmu)
+            }
 
             //CPU
             let mut cpu_cycles_passed = 1;
-            if !self.cpu.halt{
+            let cpu_stalled = self.mmu.is_cpu_stalled();
+            if !self.cpu.halt && self.cpu.stop_reason.is_none() && !cpu_stalled{
                 cpu_cycles_passed = self.execute_opcode();
             }
             
-            self.mmu.cycle(cpu_cycles_passed);
+            self.mmu.cycle(cpu_cycles_passed, self.cpu.double_speed);
+            //the cgb bootrom can switch to the dmg compatibility mode
+            self.cpu.cgb_mode = self.mmu.io_components.cgb_mode;
             
             //interrupts
-            let interrupt_cycles = self.interrupts_handler.handle_interrupts(&mut self.cpu, &mut self.mmu);
+            let mut interrupt_cycles = 0;
+            if self.cpu.stop_reason.is_none() && !cpu_stalled{
+                interrupt_cycles = self.interrupts_handler.handle_interrupts(&mut self.cpu, &mut self.mmu);
+            }
             if interrupt_cycles != 0{                
-                self.mmu.cycle(interrupt_cycles);
+                self.mmu.cycle(interrupt_cycles, self.cpu.double_speed);
             }
             
-            let iter_total_cycles= cpu_cycles_passed as u32 + interrupt_cycles as u32;
+            let mut iter_total_cycles= cpu_cycles_passed as u32 + interrupt_cycles as u32;
+            if !self.cpu.double_speed{
+                iter_total_cycles *= 2;
+            }
             
 
             //In case the ppu just turned I want to keep it sync with the actual screen and thats why Im reseting the loop to finish
@@ -85,11 +160,220 @@ impl<'a, JP:JoypadProvider, AD:AudioDevice> GameBoy<'a, JP, AD>{
             last_ppu_power_state = self.mmu.io_components.ppu.screen_enable;
         }
 
-        if self.cycles_counter >= CYCLES_PER_FRAME{
-            self.cycles_counter -= CYCLES_PER_FRAME; 
+        if self.cycles_counter >= HALF_CYCLES_PER_FRAME{
+            self.cycles_counter -= HALF_CYCLES_PER_FRAME; 
+        }
+
+        if let Some(sgb) = &mut self.sgb{
+            sgb.cycle_frame(&self.mmu.io_components.ppu.shade_buffer);
+        }
+        let (width, height) = self.get_frame_size();
+        let frame = match &self.sgb{
+            Some(sgb)=>sgb.get_frame_buffer(),
+            None=>self.mmu.io_components.ppu.get_frame_buffer()
+        };
+        if let Some(frame_output) = &mut self.frame_output{
+            frame_output.push_frame(frame, &self.mmu.io_components.ppu.shade_buffer, width, height);
+        }
+
+        return frame;
+    }
+
+    // The last frame cycle_frame returned
+    pub fn get_frame_buffer(&self)->&[u32]{
+        match &self.sgb{
+            Some(sgb)=>sgb.get_frame_buffer(),
+            None=>self.mmu.io_components.ppu.get_frame_buffer()
+        }
+    }
+
+    // Writes the last frame as a png image, scaled by the scale
+    pub fn write_screenshot<W:Write>(&self, writer:&mut W, scale:usize)->std::io::Result<()>{
+        let (width, height) = self.get_frame_size();
+        return write_png(writer, self.get_frame_buffer(), width, height, scale);
+    }
+
+    // The (width, height) of the frames cycle_frame returns
+    pub fn get_frame_size(&self)->(usize, usize){
+        match self.sgb{
+            Some(_)=>(SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT),
+            None=>(SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    // Mimics the colors of the CGB lcd instead of the raw RGB555 colors
+    pub fn set_color_correction(&mut self, color_correction:bool){
+        self.mmu.io_components.ppu.color_correction = color_correction;
+    }
+
+    // The colors of the DMG shades, the CGB uses its palette ram and the SGB its palettes packets
+    pub fn set_dmg_palette(&mut self, dmg_palette:DmgPalette){
+        self.mmu.io_components.ppu.set_dmg_palette(dmg_palette);
+    }
+
+    // The pixel fifo renderer is slower but shows mid line register writes
+    pub fn set_ppu_renderer(&mut self, renderer:PpuRenderer){
+        self.mmu.io_components.ppu.set_renderer(renderer);
+    }
+
+    pub fn get_serial_device(&self)->&SD{
+        &self.mmu.io_components.serial.device
+    }
+
+    // Some once the cpu locked up (the rest of the hardware keeps running like on a real gameboy)
+    pub fn get_stop_reason(&self)->Option<StopReason>{
+        self.cpu.stop_reason
+    }
+
+    //Reads the memory the same way the hardware would see it without any bus locking (for debuggers and test runners)
+    pub fn read_memory(&self, address:u16)->u8{
+        self.mmu.read_unprotected(address)
+    }
+
+    pub fn save_state(&self)->Vec<u8>{
+        let mut writer = StateWriter::default();
+        for byte in SAVE_STATE_MAGIC.iter(){
+            writer.write_u8(*byte);
+        }
+        writer.write_u16(SAVE_STATE_VERSION);
+        let (header_checksum, global_checksum) = self.get_cartridge_checksums();
+        writer.write_u8(header_checksum);
+        writer.write_u16(global_checksum);
+
+        self.save_machine_state(&mut writer);
+
+        return writer.into_buffer();
+    }
+
+    // On error the machine is left as it was before the call
+    pub fn load_state(&mut self, state:&[u8])->Result<(), SaveStateError>{
+        let mut reader = StateReader::new(state);
+        for byte in SAVE_STATE_MAGIC.iter(){
+            if reader.read_u8().map_err(|_|SaveStateError::InvalidHeader)? != *byte{
+                return Err(SaveStateError::InvalidHeader);
+            }
+        }
+        let version = reader.read_u16()?;
+        if version != SAVE_STATE_VERSION{
+            return Err(SaveStateError::UnsupportedVersion{found:version, supported:SAVE_STATE_VERSION});
+        }
+        if (reader.read_u8()?, reader.read_u16()?) != self.get_cartridge_checksums(){
+            return Err(SaveStateError::CartridgeMismatch);
+        }
+
+        let mut backup = StateWriter::default();
+        self.save_machine_state(&mut backup);
+
+        let result = self.load_machine_state(&mut reader).and_then(|_|{
+            if reader.is_finished() {Ok(())} else {Err(SaveStateError::InvalidValue("state length"))}
+        });
+        if result.is_err(){
+            let backup = backup.into_buffer();
+            self.load_machine_state(&mut StateReader::new(&backup)).expect("restoring the machine state should never fail");
+        }
+
+        return result;
+    }
+
+    fn save_machine_state(&self, writer:&mut StateWriter){
+        self.cpu.save_state(writer);
+        self.interrupts_handler.save_state(writer);
+        writer.write_u32(self.cycles_counter);
+        self.mmu.save_state(writer);
+        writer.write_bool(self.sgb.is_some());
+        if let Some(sgb) = &self.sgb{
+            sgb.save_state(writer);
+        }
+    }
+
+    fn load_machine_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.cpu.load_state(reader)?;
+        self.interrupts_handler.load_state(reader)?;
+        self.cycles_counter = reader.read_u32()?;
+        self.mmu.load_state(reader)?;
+        if reader.read_bool()? != self.sgb.is_some(){
+            return Err(SaveStateError::InvalidValue("sgb"));
+        }
+        if let Some(sgb) = &mut self.sgb{
+            sgb.load_state(reader)?;
+        }
+        Ok(())
+    }
+
+    // CGB cartridges (with 0x80 or 0xC0 in the cgb flag) runs with the CGB registers
+    fn init_post_boot_state(&mut self, model:Model){
+        let cgb_cartridge = self.mmu.read_unprotected(CGB_FLAG_ADDRESS) & BIT_7_MASK != 0;
+        let dmg_compatibility = model.is_cgb() && !cgb_cartridge;
+        let registers = if dmg_compatibility{
+            let (title, old_licensee_code, new_licensee_code) = self.get_title_and_licensee_codes();
+            let title_checksum = if is_nintendo_licensee(old_licensee_code, new_licensee_code) {get_title_checksum(&title)} else {0};
+            model.get_dmg_compatibility_cpu_registers(title_checksum)
         }
+        else{
+            model.get_cpu_registers(self.mmu.read_unprotected(HEADER_CHECKSUM_ADDRESS))
+        };
+        *self.cpu.af.value() = registers[0];
+        *self.cpu.bc.value() = registers[1];
+        *self.cpu.de.value() = registers[2];
+        *self.cpu.hl.value() = registers[3];
+        self.cpu.stack_pointer = 0xFFFE;
+        self.cpu.program_counter = 0x100;
+
+        self.mmu.io_components.cgb_hardware = model.is_cgb();
+        self.set_cgb_mode(model.is_cgb() && cgb_cartridge);
+        if dmg_compatibility{
+            self.init_dmg_compatibility_mode();
+        }
+
+        let nr52 = model.get_nr52();
+        self.mmu.write(NR52_REGISTER_ADDRESS, nr52);
+        for (address, value) in POST_BOOT_IO_REGISTERS.iter(){
+            self.mmu.write(*address, *value);
+        }
+        // the registers are written without triggering the channels, channel 1 is still on at volume 0 from the boot sound
+        self.mmu.io_components.apu.sweep_tone_channel.enabled = nr52 & BIT_0_MASK != 0;
+        self.mmu.write(SC_REGISTER_ADDRESS, if model.is_cgb() {0x7F} else {0x7E});
+        // writing it would start a dma transfer
+        self.mmu.write_unprotected(DMA_REGISTER_ADDRESS, if model.is_cgb() {0x00} else {0xFF});
+
+        self.mmu.io_components.timer.system_counter = model.get_div_counter();
+        self.mmu.io_components.ppu.set_frame_cycle(model.get_ppu_frame_cycle());
+    }
+
+    fn get_title_and_licensee_codes(&self)->([u8;TITLE_LENGTH], u8, [u8;2]){
+        let mut title = [0;TITLE_LENGTH];
+        for i in 0..TITLE_LENGTH{
+            title[i] = self.mmu.read_unprotected(TITLE_ADDRESS + i as u16);
+        }
+        let new_licensee_code = [self.mmu.read_unprotected(NEW_LICENSEE_CODE_ADDRESS), self.mmu.read_unprotected(NEW_LICENSEE_CODE_ADDRESS + 1)];
+        return (title, self.mmu.read_unprotected(OLD_LICENSEE_CODE_ADDRESS), new_licensee_code);
+    }
+
+    fn init_dmg_compatibility_mode(&mut self){
+        let (title, old_licensee_code, new_licensee_code) = self.get_title_and_licensee_codes();
+        let palettes = get_compatibility_palettes(&title, old_licensee_code, new_licensee_code);
+
+        let ppu = &mut self.mmu.io_components.ppu;
+        for i in 0..4{
+            ppu.bg_color_ram.set_color(0, i as u8, rgb888_to_rgb555(palettes.bg[i]));
+            ppu.obj_color_ram.set_color(0, i as u8, rgb888_to_rgb555(palettes.obj0[i]));
+            ppu.obj_color_ram.set_color(1, i as u8, rgb888_to_rgb555(palettes.obj1[i]));
+        }
+        self.mmu.io_components.enter_dmg_compatibility_mode();
+        self.cpu.cgb_mode = false;
+    }
+
+    fn set_cgb_mode(&mut self, cgb_mode:bool){
+        self.cpu.cgb_mode = cgb_mode;
+        self.mmu.io_components.cgb_mode = cgb_mode;
+        self.mmu.io_components.serial.cgb_mode = cgb_mode;
+        self.mmu.io_components.ppu.gbc_mode = cgb_mode;
+    }
 
-        return self.mmu.io_components.ppu.get_frame_buffer();
+    fn get_cartridge_checksums(&self)->(u8, u16){
+        let header_checksum = self.mmu.read_unprotected(HEADER_CHECKSUM_ADDRESS);
+        let global_checksum = ((self.mmu.read_unprotected(GLOBAL_CHECKSUM_ADDRESS) as u16) << 8) | self.mmu.read_unprotected(GLOBAL_CHECKSUM_ADDRESS + 1) as u16;
+        return (header_checksum, global_checksum);
     }
 
     fn execute_opcode(&mut self)->u8{
@@ -8,6 +8,7 @@ use crate::{cpu::gb_cpu::GbCpu, utils::{
 }};
 use crate::cpu::opcodes::opcodes_utils::push;
 use crate::mmu::memory::Memory;
+use crate::save_state::*;
 
 const V_BLANK_INTERRUPT_ADDERESS:u16    = 0x40;
 const LCD_STAT_INTERRUPT_ADDERESS:u16   = 0x48;
@@ -86,4 +87,15 @@ impl InterruptsHandler{
         //cycles passed
         return 5;
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for InterruptsHandler{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bool(self.ei_triggered);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.ei_triggered = reader.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,13 +1,23 @@
-use crate::mmu::carts::*;
+use crate::{mmu::carts::*, error::CartridgeError};
 
-pub fn initialize_mbc(mbc_type:u8, program:Vec<u8>, save_data:Option<Vec<u8>>)->Box<dyn Mbc>{
-    match mbc_type{
-        0x0|0x8=>Box::new(Rom::new(program,false, None)),
-        0x9=>Box::new(Rom::new(program, true, save_data)),
-        0x1|0x2=>Box::new(Mbc1::new(program,false, None)),
-        0x3=>Box::new(Mbc1::new(program,true, save_data)),
-        0x11|0x12=>Box::new(Mbc3::new(program,false,Option::None)),
-        0x13=>Box::new(Mbc3::new(program, true, save_data)),
-        _=>std::panic!("not supported cartridge: {}",mbc_type)
-    }
+// The rtc clock source is used only by cartridges with a real time clock
+pub fn initialize_mbc(header:&CartridgeHeader, program:Vec<u8>, save_data:Option<Vec<u8>>, rtc_clock_source:RtcClockSource)->Result<Box<dyn Mbc>, CartridgeError>{
+    let mbc:Box<dyn Mbc> = match header.cartridge_type{
+        0x0|0x8=>Box::new(Rom::new(program,false, None)?),
+        0x9=>Box::new(Rom::new(program, true, save_data)?),
+        0x1|0x2=>Box::new(Mbc1::new(program,false, None)?),
+        0x3=>Box::new(Mbc1::new(program,true, save_data)?),
+        0x5=>Box::new(Mbc2::new(program, false, None)?),
+        0x6=>Box::new(Mbc2::new(program, true, save_data)?),
+        0x0F|0x10=>Box::new(Mbc3::new(program, true, Some(rtc_clock_source), save_data)?),
+        0x11|0x12=>Box::new(Mbc3::new(program,false, None, Option::None)?),
+        0x13=>Box::new(Mbc3::new(program, true, None, save_data)?),
+        0x19|0x1A=>Box::new(Mbc5::new(program, false, false, None)?),
+        0x1B=>Box::new(Mbc5::new(program, true, false, save_data)?),
+        0x1C|0x1D=>Box::new(Mbc5::new(program, false, true, None)?),
+        0x1E=>Box::new(Mbc5::new(program, true, true, save_data)?),
+        _=>return Err(CartridgeError::UnsupportedCartridgeType(header.cartridge_type))
+    };
+
+    return Ok(mbc);
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
 pub mod interrupts_handler;
 pub mod gameboy;
-pub mod mbc_initializer;
\ No newline at end of file
+pub mod mbc_initializer;
+pub mod model;
\ No newline at end of file
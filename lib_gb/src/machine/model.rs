@@ -0,0 +1,133 @@
+use std::{fmt, str::FromStr};
+use crate::{mmu::carts::{CartridgeHeader, Mbc}, ppu::gb_ppu::CYCLES_PER_FRAME, utils::bit_masks::BIT_7_MASK};
+
+const CGB_FLAG_ADDRESS:u16 = 0x143;
+const HEADER_END:u16 = 0x150;
+const CYCLES_PER_LINE:u32 = CYCLES_PER_FRAME / 154;
+
+// The hardware revisions, each bootrom leaves the machine in a slightly different state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model{
+    DMG0,
+    DMG,
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+    AGB
+}
+
+// The registers as [AF, BC, DE, HL]
+pub type CpuRegisters = [u16;4];
+
+impl Model{
+    pub const ALL:[Model;7] = [Model::DMG0, Model::DMG, Model::MGB, Model::SGB, Model::SGB2, Model::CGB, Model::AGB];
+
+    // CGB cartridges (with 0x80 or 0xC0 in the cgb flag) runs on a CGB and SGB cartridges on a SGB,
+    // the SGB bootrom also checks the old licensee code (see CartridgeHeader::supports_sgb)
+    pub fn from_cartridge(mbc:&dyn Mbc)->Model{
+        if mbc.read_bank0(CGB_FLAG_ADDRESS) & BIT_7_MASK != 0{
+            return Model::CGB;
+        }
+        let header:Vec<u8> = (0..HEADER_END).map(|address|mbc.read_bank0(address)).collect();
+        if CartridgeHeader::parse(&header).is_ok_and(|header|header.supports_sgb()){
+            return Model::SGB;
+        }
+        return Model::DMG;
+    }
+
+    pub fn is_cgb(&self)->bool{
+        match self{
+            Model::CGB | Model::AGB => true,
+            _=>false
+        }
+    }
+
+    pub fn is_sgb(&self)->bool{
+        match self{
+            Model::SGB | Model::SGB2 => true,
+            _=>false
+        }
+    }
+
+    // The flags of the DMG and MGB bootroms are left from the header checksum check
+    pub fn get_cpu_registers(&self, header_checksum:u8)->CpuRegisters{
+        let dmg_flags = if header_checksum == 0 {0x80} else {0xB0};
+        match self{
+            Model::DMG0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+            Model::DMG  => [0x0100 | dmg_flags, 0x0013, 0x00D8, 0x014D],
+            Model::MGB  => [0xFF00 | dmg_flags, 0x0013, 0x00D8, 0x014D],
+            Model::SGB  => [0x0100, 0x0014, 0x0000, 0xC060],
+            Model::SGB2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+            Model::CGB  => [0x1180, 0x0000, 0xFF56, 0x000D],
+            Model::AGB  => [0x1100, 0x0100, 0xFF56, 0x000D]
+        }
+    }
+
+    // The CGB bootrom leaves the title checksum in B when running a DMG cartridge (0 for non Nintendo cartridges),
+    // the AGB bootrom increments it afterwards
+    pub fn get_dmg_compatibility_cpu_registers(&self, title_checksum:u8)->CpuRegisters{
+        let hl = if title_checksum == 0x43 || title_checksum == 0x58 {0x991A} else {0x007C};
+        match self{
+            Model::AGB => {
+                let b = title_checksum.wrapping_add(1);
+                let zero_flag = if b == 0 {0x80} else {0};
+                let half_carry_flag = if b & 0xF == 0 {0x20} else {0};
+                [0x1100 | zero_flag | half_carry_flag, (b as u16) << 8, 0x0008, hl]
+            }
+            _=>[0x1180, (title_checksum as u16) << 8, 0x0008, hl]
+        }
+    }
+
+    // The SGB bootrom does not play the boot sound so channel 1 is off, on the rest of the models it is left playing at volume 0
+    pub fn get_nr52(&self)->u8{
+        match self{
+            Model::SGB | Model::SGB2 => 0xF0,
+            _ => 0xF1
+        }
+    }
+
+    // The internal counter of the timer (DIV is the upper byte), the SGB and CGB bootroms take a different time
+    // depending on the header so those are the values for a common cartridge
+    pub fn get_div_counter(&self)->u16{
+        match self{
+            Model::DMG0 => 0x182C,
+            Model::DMG | Model::MGB => 0xABCC,
+            Model::SGB | Model::SGB2 => 0xD86C,
+            Model::CGB | Model::AGB => 0x1EA0
+        }
+    }
+
+    // The m cycles since the start of the frame when the bootrom jumps to the cartridge
+    pub fn get_ppu_frame_cycle(&self)->u32{
+        match self{
+            Model::DMG0 => 145 * CYCLES_PER_LINE,
+            Model::CGB | Model::AGB => 144 * CYCLES_PER_LINE + 41,
+            _ => 153 * CYCLES_PER_LINE + 99
+        }
+    }
+}
+
+impl fmt::Display for Model{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self{
+            Model::DMG0 => "dmg0",
+            Model::DMG => "dmg",
+            Model::MGB => "mgb",
+            Model::SGB => "sgb",
+            Model::SGB2 => "sgb2",
+            Model::CGB => "cgb",
+            Model::AGB => "agb"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Model{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        Model::ALL.iter().find(|model|model.to_string() == name).copied().ok_or(format!("unknown model {}", s))
+    }
+}
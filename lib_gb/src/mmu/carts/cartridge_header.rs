@@ -0,0 +1,162 @@
+use std::fmt;
+use crate::error::CartridgeError;
+use super::mbc::ROM_BANK_SIZE;
+
+pub const NINTENDO_LOGO_ADDRESS:usize = 0x104;
+pub const NINTENDO_LOGO:[u8;48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E
+];
+
+const TITLE_START:usize = 0x134;
+const TITLE_END:usize = 0x143;
+const MANUFACTURER_CODE_START:usize = 0x13F;
+const CGB_FLAG_ADDRESS:usize = 0x143;
+const NEW_LICENSEE_CODE_START:usize = 0x144;
+const SGB_FLAG_ADDRESS:usize = 0x146;
+const CARTRIDGE_TYPE_ADDRESS:usize = 0x147;
+const ROM_SIZE_ADDRESS:usize = 0x148;
+const RAM_SIZE_ADDRESS:usize = 0x149;
+const DESTINATION_CODE_ADDRESS:usize = 0x14A;
+const OLD_LICENSEE_CODE_ADDRESS:usize = 0x14B;
+const MASK_ROM_VERSION_ADDRESS:usize = 0x14C;
+const HEADER_CHECKSUM_ADDRESS:usize = 0x14D;
+const GLOBAL_CHECKSUM_ADDRESS:usize = 0x14E;
+const HEADER_END:usize = 0x150;
+
+const CGB_SUPPORT_FLAG:u8 = 0x80;
+const CGB_ONLY_FLAG:u8 = 0xC0;
+const SGB_SUPPORT_FLAG:u8 = 0x03;
+// The new licensee code is used only when the old one has this value
+const USE_NEW_LICENSEE_CODE:u8 = 0x33;
+
+pub struct CartridgeHeader{
+    pub title:String,
+    // Only newer cartridges (CGB ones) has this code, it is a part of the title in older cartridges
+    pub manufacturer_code:Option<String>,
+    pub cgb_flag:u8,
+    pub new_licensee_code:String,
+    pub sgb_flag:u8,
+    pub cartridge_type:u8,
+    pub rom_size_register:u8,
+    pub ram_size_register:u8,
+    pub destination_code:u8,
+    pub old_licensee_code:u8,
+    pub mask_rom_version:u8,
+    pub header_checksum:u8,
+    pub global_checksum:u16
+}
+
+impl CartridgeHeader{
+    pub fn parse(program:&[u8])->Result<Self, CartridgeError>{
+        if program.len() < HEADER_END{
+            return Err(CartridgeError::RomTooSmall(program.len()));
+        }
+
+        let cgb_flag = program[CGB_FLAG_ADDRESS];
+        let is_cgb_cartridge = cgb_flag == CGB_SUPPORT_FLAG || cgb_flag == CGB_ONLY_FLAG;
+        let (title, manufacturer_code) = if is_cgb_cartridge{
+            (Self::parse_text(&program[TITLE_START..MANUFACTURER_CODE_START]), Some(Self::parse_text(&program[MANUFACTURER_CODE_START..CGB_FLAG_ADDRESS])))
+        }
+        else{
+            (Self::parse_text(&program[TITLE_START..=TITLE_END]), None)
+        };
+
+        return Ok(CartridgeHeader{
+            title,
+            manufacturer_code,
+            cgb_flag,
+            new_licensee_code:Self::parse_text(&program[NEW_LICENSEE_CODE_START..SGB_FLAG_ADDRESS]),
+            sgb_flag:program[SGB_FLAG_ADDRESS],
+            cartridge_type:program[CARTRIDGE_TYPE_ADDRESS],
+            rom_size_register:program[ROM_SIZE_ADDRESS],
+            ram_size_register:program[RAM_SIZE_ADDRESS],
+            destination_code:program[DESTINATION_CODE_ADDRESS],
+            old_licensee_code:program[OLD_LICENSEE_CODE_ADDRESS],
+            mask_rom_version:program[MASK_ROM_VERSION_ADDRESS],
+            header_checksum:program[HEADER_CHECKSUM_ADDRESS],
+            global_checksum:u16::from_be_bytes([program[GLOBAL_CHECKSUM_ADDRESS], program[GLOBAL_CHECKSUM_ADDRESS + 1]])
+        });
+    }
+
+    // Returns every problem found in the rom, those are not fatal since most emulators (and the hardware)
+    // does not verify anything except for the header checksum
+    pub fn validate(&self, program:&[u8])->Vec<CartridgeError>{
+        let mut errors = Vec::new();
+
+        let header_checksum = Self::calculate_header_checksum(program);
+        if header_checksum != self.header_checksum{
+            errors.push(CartridgeError::HeaderChecksumMismatch{expected:self.header_checksum, found:header_checksum});
+        }
+        let global_checksum = Self::calculate_global_checksum(program);
+        if global_checksum != self.global_checksum{
+            errors.push(CartridgeError::GlobalChecksumMismatch{expected:self.global_checksum, found:global_checksum});
+        }
+        match self.get_rom_size(){
+            Ok(rom_size)=>if rom_size != program.len(){
+                errors.push(CartridgeError::RomSizeMismatch{expected:rom_size, found:program.len()});
+            },
+            Err(err)=>errors.push(err)
+        }
+
+        return errors;
+    }
+
+    pub fn get_rom_size(&self)->Result<usize, CartridgeError>{
+        let banks = match self.rom_size_register{
+            0..=8=>2 << self.rom_size_register,
+            0x52=>72,
+            0x53=>80,
+            0x54=>96,
+            _=>return Err(CartridgeError::InvalidRomSize(self.rom_size_register))
+        };
+
+        return Ok(banks * ROM_BANK_SIZE as usize);
+    }
+
+    pub fn supports_cgb(&self)->bool{
+        self.cgb_flag == CGB_SUPPORT_FLAG || self.cgb_flag == CGB_ONLY_FLAG
+    }
+
+    pub fn is_cgb_only(&self)->bool{
+        self.cgb_flag == CGB_ONLY_FLAG
+    }
+
+    pub fn supports_sgb(&self)->bool{
+        self.sgb_flag == SGB_SUPPORT_FLAG && self.old_licensee_code == USE_NEW_LICENSEE_CODE
+    }
+
+    pub fn calculate_header_checksum(program:&[u8])->u8{
+        program[TITLE_START..HEADER_CHECKSUM_ADDRESS].iter().fold(0, |checksum:u8, byte| checksum.wrapping_sub(*byte).wrapping_sub(1))
+    }
+
+    // The sum of all the bytes in the rom except for the checksum itself
+    pub fn calculate_global_checksum(program:&[u8])->u16{
+        let checksum_bytes = program[GLOBAL_CHECKSUM_ADDRESS] as u16 + program[GLOBAL_CHECKSUM_ADDRESS + 1] as u16;
+        let sum = program.iter().fold(0, |sum:u16, byte| sum.wrapping_add(*byte as u16));
+        return sum.wrapping_sub(checksum_bytes);
+    }
+
+    fn parse_text(bytes:&[u8])->String{
+        bytes.iter().take_while(|byte| **byte != 0).map(|byte| *byte as char).collect()
+    }
+}
+
+impl fmt::Display for CartridgeHeader{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "title: {}, ", self.title)?;
+        if let Some(code) = &self.manufacturer_code{
+            write!(f, "manufacturer: {}, ", code)?;
+        }
+        if self.old_licensee_code == USE_NEW_LICENSEE_CODE{
+            write!(f, "licensee: {}, ", self.new_licensee_code)?;
+        }
+        else{
+            write!(f, "licensee: {:#X}, ", self.old_licensee_code)?;
+        }
+        write!(f, "cgb: {:#X}, sgb: {:#X}, type: {:#X}, rom size: {:#X}, ram size: {:#X}, destination: {:#X}, version: {}, header checksum: {:#X}, global checksum: {:#X}",
+            self.cgb_flag, self.sgb_flag, self.cartridge_type, self.rom_size_register, self.ram_size_register,
+            self.destination_code, self.mask_rom_version, self.header_checksum, self.global_checksum)
+    }
+}
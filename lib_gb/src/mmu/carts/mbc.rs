@@ -1,39 +1,62 @@
+use crate::{save_state::SaveState, error::CartridgeError};
+use super::rumble_motor::RumbleMotor;
 
 pub const ROM_BANK_SIZE:u16 = 0x4000;
 pub const RAM_BANK_SIZE:u16 = 0x2000;
 pub const MBC_RAM_SIZE_LOCATION:usize = 0x149;
+// older versions treated the 8KB ram size code (0x2) as 16KB and saved the ram with 8KB of zeros after it
+const LEGACY_8KB_RAM_SAVE_SIZE:usize = 0x4000;
 
-pub fn get_ram_size(ram_size_register:u8)->usize{
+pub fn get_ram_size(ram_size_register:u8)->Result<usize, CartridgeError>{
     match ram_size_register{
-        0x0=>0,
-        0x1=>0x800,
-        0x2=>0x4000,
-        0x3=>0x8000,
-        0x4=>0x20000,
-        0x5=>0x10000,
-        _=>std::panic!("invalid ram size register {:#X}", ram_size_register)
+        0x0=>Ok(0),
+        0x1=>Ok(0x800),
+        0x2=>Ok(0x2000),
+        0x3=>Ok(0x8000),
+        0x4=>Ok(0x20000),
+        0x5=>Ok(0x10000),
+        _=>Err(CartridgeError::InvalidRamSize(ram_size_register))
     }
 }
 
-pub fn init_ram(ram_reg:u8, external_ram:Option<Vec<u8>>)->Vec<u8>{
-    let ram_size = get_ram_size(ram_reg);
+pub fn init_ram(ram_reg:u8, external_ram:Option<Vec<u8>>)->Result<Vec<u8>, CartridgeError>{
+    let ram_size = get_ram_size(ram_reg)?;
     
     match external_ram{
-        Some(ram)=>{
+        Some(mut ram)=>{
+            if ram_reg == 0x2 && ram.len() == LEGACY_8KB_RAM_SAVE_SIZE{
+                ram.truncate(ram_size);
+            }
             if ram.len() != ram_size{
-                std::panic!("external rom is not in the correct size for the cartridge");
+                return Err(CartridgeError::SaveDataSizeMismatch{expected:ram_size, found:ram.len()});
             }
 
-            return ram;
+            return Ok(ram);
         }
-        None=>vec![0;ram_size]
+        None=>Ok(vec![0;ram_size])
     }
 }
 
-pub trait Mbc{
+// Every cartridge saves its own banking registers and ram as part of the machine save state
+pub trait Mbc: SaveState{
     fn get_ram(&self)->&[u8];
     fn has_battery(&self)->bool;
 
+    fn has_rumble(&self)->bool{
+        false
+    }
+
+    // The data to store in the save file of battery backed cartridges
+    fn get_save_data(&self)->Vec<u8>{
+        self.get_ram().to_vec()
+    }
+
+    // Called with the cycles passed for cartridges with their own hardware (like the MBC3 rtc)
+    fn cycle(&mut self, _m_cycles:u8){}
+
+    // Cartridges without a rumble motor just ignore it
+    fn attach_rumble_motor(&mut self, _motor:Box<dyn RumbleMotor>){}
+
     fn read_bank0(&self, address:u16)->u8;
     fn read_current_bank(&self, address:u16)->u8;
     fn write_rom(&mut self, address:u16, value:u8);
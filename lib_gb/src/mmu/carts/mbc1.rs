@@ -1,6 +1,17 @@
 use std::vec::Vec;
-use super::mbc::*;
+use super::{mbc::*, cartridge_header::{NINTENDO_LOGO, NINTENDO_LOGO_ADDRESS}};
+use crate::{save_state::*, error::CartridgeError};
 
+const RAM_ENABLE_VALUE:u8 = 0xA;
+const EXTERNAL_RAM_READ_ERROR_VALUE:u8 = 0xFF;
+const BANK1_REGISTER_MASK:u8 = 0b1_1111;
+// Multicarts (MBC1M) wire only 4 bits of the bank1 register to the rom
+const MULTICART_BANK1_MASK:u8 = 0b1111;
+const BANK2_REGISTER_MASK:u8 = 0b11;
+
+// Multicarts are 1MB roms that contains a header at the start of every game (in bank 0x10 for the second game)
+const MULTICART_ROM_SIZE:usize = 0x10_0000;
+const MULTICART_SECOND_GAME_BANK:usize = 0x10;
 
 pub struct Mbc1{
     program:Vec<u8>,
@@ -9,7 +20,8 @@ pub struct Mbc1{
     register1:u8,
     register2:u8,
     register3:u8,
-    battery:bool
+    battery:bool,
+    multicart:bool
 }
 
 impl Mbc for Mbc1{
@@ -22,38 +34,45 @@ impl Mbc for Mbc1{
     }
 
     fn read_bank0(&self, address: u16)->u8{
-        self.program[address as usize]
+        let bank = self.get_bank0_area_rom_bank();
+        return self.program[self.get_rom_address(bank, address)];
     }
 
     fn read_current_bank(&self, address:u16)->u8{
-        let bank:u16 = self.get_current_rom_bank() as u16;
-        return self.program[(ROM_BANK_SIZE * bank + address) as usize];
+        let bank = self.get_current_rom_bank();
+        return self.program[self.get_rom_address(bank, address)];
     }
 
     fn write_rom(&mut self, address: u16, value: u8){
         match address{
             0..=0x1FFF      =>self.register0 = value,
-            0x2000..=0x3FFF =>self.register1 = value,
-            0x4000..=0x5FFF =>self.register2 = value,
-            0x6000..=0x7FFF =>self.register3 = value,
+            0x2000..=0x3FFF =>self.register1 = value & BANK1_REGISTER_MASK,
+            0x4000..=0x5FFF =>self.register2 = value & BANK2_REGISTER_MASK,
+            0x6000..=0x7FFF =>self.register3 = value & 1,
             _=>std::panic!("cannot write to this address in bank0 in mbc1 cartridge")
         }
     }
 
     fn read_external_ram(&self, address: u16)->u8{
-        let bank:u16 = self.get_current_ram_bank() as u16;
-        return self.ram[(bank * RAM_BANK_SIZE + address) as usize];
+        if !self.is_ram_enabled(){
+            return EXTERNAL_RAM_READ_ERROR_VALUE;
+        }
+
+        return self.ram[self.get_ram_address(address)];
     }
 
     fn write_external_ram(&mut self, address: u16, value: u8){
-        let bank:u16 = self.get_current_ram_bank() as u16;
-        self.ram[(bank * RAM_BANK_SIZE + address) as usize] = value;
+        if self.is_ram_enabled(){
+            let internal_address = self.get_ram_address(address);
+            self.ram[internal_address] = value;
+        }
     }
 }
 
 impl Mbc1{
-    pub fn new(v:Vec<u8>, battery:bool, ram:Option<Vec<u8>>)->Self{
+    pub fn new(v:Vec<u8>, battery:bool, ram:Option<Vec<u8>>)->Result<Self, CartridgeError>{
         let mut mbc = Mbc1{
+            multicart:Self::is_multicart(&v),
             program:v,
             ram:Vec::new(),
             register0:0,
@@ -63,30 +82,82 @@ impl Mbc1{
             battery:battery
         };
 
-        mbc.ram = init_ram(mbc.program[MBC_RAM_SIZE_LOCATION], ram);
+        mbc.ram = init_ram(mbc.program[MBC_RAM_SIZE_LOCATION], ram)?;
 
-        return mbc;
+        return Ok(mbc);
     }
 
-    fn get_current_rom_bank(&self)->u8{
-        let mut bank = self.register1 & 0b11111;
+    fn is_multicart(program:&[u8])->bool{
+        if program.len() != MULTICART_ROM_SIZE{
+            return false;
+        }
+
+        let logo_address = MULTICART_SECOND_GAME_BANK * ROM_BANK_SIZE as usize + NINTENDO_LOGO_ADDRESS;
+        return program[logo_address..logo_address + NINTENDO_LOGO.len()] == NINTENDO_LOGO[..];
+    }
+
+    fn is_ram_enabled(&self)->bool{
+        self.register0 & 0xF == RAM_ENABLE_VALUE && !self.ram.is_empty()
+    }
+
+    // The bank2 register is wired to the upper rom bank bits, on multicarts those are the 2 bits after the 4 bits of bank1
+    fn get_upper_rom_bank_bits(&self)->usize{
+        let shift = if self.multicart {4} else {5};
+        return (self.register2 as usize) << shift;
+    }
+
+    fn get_bank0_area_rom_bank(&self)->usize{
+        // In mode 1 the upper bits affects the bank0 area as well
+        if self.register3 == 1{
+            return self.get_upper_rom_bank_bits();
+        }
+
+        return 0;
+    }
 
-        //banks 0x0 0x20 0x40 0x60 are not avaalible through this method
-        if bank == 0{
-            bank+=1;
+    fn get_current_rom_bank(&self)->usize{
+        // Bank1 is checked for 0 with all of its 5 bits, even on multicarts, this is why banks 0x0 0x20 0x40 0x60 are not avaliable
+        let mut bank1 = self.register1;
+        if bank1 == 0{
+            bank1 = 1;
         }
-        if self.register3 == 0{
-            bank |= (self.register2 & 0b11)<<5;
+        if self.multicart{
+            bank1 &= MULTICART_BANK1_MASK;
         }
 
-        return bank;
+        return self.get_upper_rom_bank_bits() | bank1 as usize;
     }
 
-    fn get_current_ram_bank(&self)->u8{
+    fn get_rom_address(&self, bank:usize, address:u16)->usize{
+        // Banks out of the rom range wrap since the upper address lines are not connected
+        return (bank * ROM_BANK_SIZE as usize + address as usize) % self.program.len();
+    }
+
+    fn get_ram_address(&self, address:u16)->usize{
+        let mut bank = 0;
         if self.register3 == 1{
-            return self.register2 & 0b11;
+            bank = self.register2 as usize;
         }
 
-        return 0;
+        return (bank * RAM_BANK_SIZE as usize + address as usize) % self.ram.len();
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Mbc1{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.ram);
+        writer.write_u8(self.register0);
+        writer.write_u8(self.register1);
+        writer.write_u8(self.register2);
+        writer.write_u8(self.register3);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.ram, "cartridge ram")?;
+        self.register0 = reader.read_u8()?;
+        self.register1 = reader.read_u8()? & BANK1_REGISTER_MASK;
+        self.register2 = reader.read_u8()? & BANK2_REGISTER_MASK;
+        self.register3 = reader.read_u8()? & 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use super::mbc::*;
+use crate::{save_state::*, error::CartridgeError};
+
+// The MBC2 has a built in 512x4 bits ram, only the lower nibble is stored and the upper one reads as 1s
+pub const MBC2_RAM_SIZE:usize = 0x200;
+const RAM_ENABLE_VALUE:u8 = 0xA;
+const EXTERNAL_RAM_READ_ERROR_VALUE:u8 = 0xFF;
+const RAM_UPPER_NIBBLE:u8 = 0xF0;
+// Bit 8 of the address selects between the ram enable and rom bank registers
+const REGISTER_SELECT_MASK:u16 = 0x100;
+
+pub struct Mbc2{
+    program:Vec<u8>,
+    ram:Vec<u8>,
+    battery:bool,
+    ram_enable:bool,
+    rom_bank:u8
+}
+
+impl Mbc for Mbc2{
+    fn get_ram(&self) ->&[u8] {
+        self.ram.as_slice()
+    }
+
+    fn has_battery(&self) ->bool {
+        self.battery
+    }
+
+    fn read_bank0(&self, address:u16)->u8{
+        self.program[address as usize]
+    }
+
+    fn read_current_bank(&self, address:u16)->u8{
+        let internal_address = ROM_BANK_SIZE as usize * self.rom_bank as usize + address as usize;
+        self.program[internal_address % self.program.len()]
+    }
+
+    fn write_rom(&mut self, address:u16, value:u8){
+        // Only the bank0 area has registers
+        if address > 0x3FFF{
+            return;
+        }
+
+        if address & REGISTER_SELECT_MASK == 0{
+            self.ram_enable = value & 0xF == RAM_ENABLE_VALUE;
+        }
+        else{
+            self.rom_bank = value & 0xF;
+            if self.rom_bank == 0{
+                self.rom_bank = 1;
+            }
+        }
+    }
+
+    fn read_external_ram(&self, address:u16)->u8{
+        if !self.ram_enable{
+            return EXTERNAL_RAM_READ_ERROR_VALUE;
+        }
+
+        // The ram is mirrored across the whole external ram area
+        return self.ram[address as usize % MBC2_RAM_SIZE] | RAM_UPPER_NIBBLE;
+    }
+
+    fn write_external_ram(&mut self, address:u16, value:u8){
+        if self.ram_enable{
+            self.ram[address as usize % MBC2_RAM_SIZE] = value & 0xF;
+        }
+    }
+}
+
+impl Mbc2{
+    pub fn new(program:Vec<u8>, battery:bool, ram:Option<Vec<u8>>)->Result<Self, CartridgeError>{
+        let ram = match ram{
+            Some(ram)=>{
+                if ram.len() != MBC2_RAM_SIZE{
+                    return Err(CartridgeError::SaveDataSizeMismatch{expected:MBC2_RAM_SIZE, found:ram.len()});
+                }
+                ram.iter().map(|value| value & 0xF).collect()
+            }
+            None=>vec![0;MBC2_RAM_SIZE]
+        };
+
+        Ok(Mbc2{
+            program,
+            ram,
+            battery,
+            ram_enable:false,
+            rom_bank:1
+        })
+    }
+}
+
+impl SaveState for Mbc2{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.ram);
+        writer.write_bool(self.ram_enable);
+        writer.write_u8(self.rom_bank);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.ram, "cartridge ram")?;
+        self.ram_enable = reader.read_bool()?;
+        self.rom_bank = reader.read_u8()?;
+        if self.rom_bank == 0 || self.rom_bank > 0xF{
+            return Err(SaveStateError::InvalidValue("mbc2 rom bank"));
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use super::mbc::*;
+use super::{mbc::*, rtc::*};
+use crate::{save_state::*, error::CartridgeError};
 
 const RAM_TIMER_ENABLE_VALUE:u8 = 0xA;
 const EXTERNAL_RAM_READ_ERROR_VALUE:u8 = 0xFF;
@@ -10,8 +11,7 @@ pub struct Mbc3{
     current_bank:u8, 
     ram_timer_enable:u8,
     ram_rtc_select:u8,
-    latch_clock_data:u8,
-    rtc_registers:[u8;4]
+    rtc:Option<Rtc>
 }
 
 impl Mbc for Mbc3{
@@ -24,15 +24,31 @@ impl Mbc for Mbc3{
         self.battery
     }
 
+    fn get_save_data(&self)->Vec<u8>{
+        let mut data = self.ram.clone();
+        if let Some(rtc) = &self.rtc{
+            rtc.append_footer(&mut data);
+        }
+
+        return data;
+    }
+
+    fn cycle(&mut self, m_cycles:u8){
+        if let Some(rtc) = &mut self.rtc{
+            rtc.cycle(m_cycles);
+        }
+    }
+
     fn read_bank0(&self, address:u16)->u8{
-        self.program[address as usize]
+        self.program[address as usize % self.program.len()]
     }
 
     fn read_current_bank(&self, address: u16)->u8{
         let current_bank = self.get_current_rom_bank() as u16;
         let internal_address:usize = (ROM_BANK_SIZE as usize* current_bank as usize) + address as usize;
 
-        self.program[internal_address]
+        // banks past the end of the rom wrap around
+        self.program[internal_address % self.program.len()]
     }
 
     fn write_rom(&mut self, address: u16, value: u8){
@@ -40,7 +56,9 @@ impl Mbc for Mbc3{
             0..=0x1FFF=>self.ram_timer_enable = value,
             0x2000..=0x3FFF=>self.current_bank = value,
             0x4000..=0x5FFF=>self.ram_rtc_select = value,
-            0x6000..=0x7FFF=>self.latch_clock_data = value,
+            0x6000..=0x7FFF=>if let Some(rtc) = &mut self.rtc{
+                rtc.write_latch(value);
+            },
             _=>std::panic!("cannot write to this address in mbc3 cartridge")
         }
     }
@@ -51,11 +69,11 @@ impl Mbc for Mbc3{
         }
         
         return match self.ram_rtc_select{
-            0..=3=>{
-                let internal_address = self.ram_rtc_select as usize * RAM_BANK_SIZE as usize +  address as usize;
-                return self.ram[internal_address];
+            0..=3 if !self.ram.is_empty()=>self.ram[self.get_ram_address(address)],
+            0x8..=0xC=>match &self.rtc{
+                Some(rtc)=>rtc.read_register(self.ram_rtc_select),
+                None=>EXTERNAL_RAM_READ_ERROR_VALUE
             },
-            0x8..=0xC=>self.rtc_registers[self.ram_rtc_select as usize],
             _=>EXTERNAL_RAM_READ_ERROR_VALUE
         };
     }
@@ -63,11 +81,13 @@ impl Mbc for Mbc3{
     fn write_external_ram(&mut self, address: u16, value: u8){
         if self.ram_timer_enable == RAM_TIMER_ENABLE_VALUE{
             match self.ram_rtc_select{
-                0..=3=>{
-                    let internal_address = self.ram_rtc_select as usize * RAM_BANK_SIZE as usize +  address as usize;
+                0..=3 if !self.ram.is_empty()=>{
+                    let internal_address = self.get_ram_address(address);
                     self.ram[internal_address] = value;
                 },
-                0x8..=0xC=>self.rtc_registers[self.ram_rtc_select as usize] = value,
+                0x8..=0xC=>if let Some(rtc) = &mut self.rtc{
+                    rtc.write_register(self.ram_rtc_select, value);
+                },
                 _=>{}
             }
         }
@@ -76,21 +96,34 @@ impl Mbc for Mbc3{
 
 impl Mbc3{
 
-    pub fn new(program:Vec<u8>, battery:bool, ram:Option<Vec<u8>>)->Self{
+    // Cartridges with a timer should pass the clock source for the rtc,
+    // the save data could contain the rtc footer after the ram
+    pub fn new(program:Vec<u8>, battery:bool, rtc_clock_source:Option<RtcClockSource>, ram:Option<Vec<u8>>)->Result<Self, CartridgeError>{
         let mut mbc = Mbc3{
             current_bank:0,
             battery:battery,
-            latch_clock_data:0,
             program:program,
             ram:Vec::new(),
             ram_rtc_select:0,
             ram_timer_enable:0,
-            rtc_registers:[0;4]
+            rtc:rtc_clock_source.map(Rtc::new)
         };
 
-        mbc.ram = init_ram(mbc.program[MBC_RAM_SIZE_LOCATION], ram);
+        let ram_size = get_ram_size(mbc.program[MBC_RAM_SIZE_LOCATION])?;
+        let mut ram = ram;
+        if let (Some(rtc), Some(data)) = (&mut mbc.rtc, &mut ram){
+            if data.len() == ram_size + RTC_FOOTER_SIZE || data.len() == ram_size + RTC_SHORT_FOOTER_SIZE{
+                rtc.load_footer(&data[ram_size..]);
+                data.truncate(ram_size);
+            }
+        }
+        mbc.ram = init_ram(mbc.program[MBC_RAM_SIZE_LOCATION], ram)?;
 
-        mbc
+        Ok(mbc)
+    }
+
+    fn get_ram_address(&self, address:u16)->usize{
+        (self.ram_rtc_select as usize * RAM_BANK_SIZE as usize + address as usize) % self.ram.len()
     }
 
     fn get_current_rom_bank(&self)->u8{
@@ -102,4 +135,27 @@ impl Mbc3{
 
         value
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Mbc3{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.ram);
+        writer.write_u8(self.current_bank);
+        writer.write_u8(self.ram_timer_enable);
+        writer.write_u8(self.ram_rtc_select);
+        if let Some(rtc) = &self.rtc{
+            rtc.save_state(writer);
+        }
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.ram, "cartridge ram")?;
+        self.current_bank = reader.read_u8()?;
+        self.ram_timer_enable = reader.read_u8()?;
+        self.ram_rtc_select = reader.read_u8()?;
+        if let Some(rtc) = &mut self.rtc{
+            rtc.load_state(reader)?;
+        }
+        Ok(())
+    }
+}
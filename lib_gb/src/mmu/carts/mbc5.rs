@@ -0,0 +1,138 @@
+use super::{mbc::*, rumble_motor::RumbleMotor};
+use crate::{save_state::*, error::CartridgeError};
+
+const RAM_ENABLE_VALUE:u8 = 0xA;
+const EXTERNAL_RAM_READ_ERROR_VALUE:u8 = 0xFF;
+const RAM_BANK_MASK:u8 = 0xF;
+// On rumble cartridges bit 3 of the ram bank register drives the motor instead of selecting the bank
+const RUMBLE_RAM_BANK_MASK:u8 = 0b111;
+const RUMBLE_MOTOR_MASK:u8 = 0b1000;
+
+pub struct Mbc5{
+    program:Vec<u8>,
+    ram:Vec<u8>,
+    battery:bool,
+    rumble:bool,
+    rumble_motor:Option<Box<dyn RumbleMotor>>,
+    ram_enable:bool,
+    // 9 bits bank number, unlike the older mbcs bank 0 can be mapped to the switchable area
+    rom_bank:u16,
+    ram_bank:u8,
+    rumble_on:bool
+}
+
+impl Mbc for Mbc5{
+    fn get_ram(&self) ->&[u8] {
+        self.ram.as_slice()
+    }
+
+    fn has_battery(&self) ->bool {
+        self.battery
+    }
+
+    fn has_rumble(&self)->bool{
+        self.rumble
+    }
+
+    fn attach_rumble_motor(&mut self, motor:Box<dyn RumbleMotor>){
+        self.rumble_motor = Some(motor);
+    }
+
+    fn read_bank0(&self, address:u16)->u8{
+        self.program[address as usize]
+    }
+
+    fn read_current_bank(&self, address:u16)->u8{
+        let internal_address = ROM_BANK_SIZE as usize * self.rom_bank as usize + address as usize;
+        self.program[internal_address % self.program.len()]
+    }
+
+    fn write_rom(&mut self, address:u16, value:u8){
+        match address{
+            0..=0x1FFF=>self.ram_enable = value & 0xF == RAM_ENABLE_VALUE,
+            0x2000..=0x2FFF=>self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF=>self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 1) << 8),
+            0x4000..=0x5FFF=>{
+                if self.rumble{
+                    self.ram_bank = value & RUMBLE_RAM_BANK_MASK;
+                    self.set_rumble(value & RUMBLE_MOTOR_MASK != 0);
+                }
+                else{
+                    self.ram_bank = value & RAM_BANK_MASK;
+                }
+            }
+            _=>{}
+        }
+    }
+
+    fn read_external_ram(&self, address:u16)->u8{
+        if !self.ram_enable || self.ram.is_empty(){
+            return EXTERNAL_RAM_READ_ERROR_VALUE;
+        }
+
+        return self.ram[self.get_ram_address(address)];
+    }
+
+    fn write_external_ram(&mut self, address:u16, value:u8){
+        if self.ram_enable && !self.ram.is_empty(){
+            let internal_address = self.get_ram_address(address);
+            self.ram[internal_address] = value;
+        }
+    }
+}
+
+impl Mbc5{
+    pub fn new(program:Vec<u8>, battery:bool, rumble:bool, ram:Option<Vec<u8>>)->Result<Self, CartridgeError>{
+        let mut mbc = Mbc5{
+            program,
+            ram:Vec::new(),
+            battery,
+            rumble,
+            rumble_motor:None,
+            ram_enable:false,
+            rom_bank:1,
+            ram_bank:0,
+            rumble_on:false
+        };
+
+        mbc.ram = init_ram(mbc.program[MBC_RAM_SIZE_LOCATION], ram)?;
+
+        Ok(mbc)
+    }
+
+    fn get_ram_address(&self, address:u16)->usize{
+        (self.ram_bank as usize * RAM_BANK_SIZE as usize + address as usize) % self.ram.len()
+    }
+
+    fn set_rumble(&mut self, on:bool){
+        if self.rumble_on != on{
+            self.rumble_on = on;
+            if let Some(motor) = &mut self.rumble_motor{
+                motor.set_rumble(on);
+            }
+        }
+    }
+}
+
+impl SaveState for Mbc5{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.ram);
+        writer.write_bool(self.ram_enable);
+        writer.write_u16(self.rom_bank);
+        writer.write_u8(self.ram_bank);
+        writer.write_bool(self.rumble_on);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.ram, "cartridge ram")?;
+        self.ram_enable = reader.read_bool()?;
+        self.rom_bank = reader.read_u16()?;
+        self.ram_bank = reader.read_u8()?;
+        let rumble_on = reader.read_bool()?;
+        if self.rom_bank > 0x1FF || self.ram_bank > RAM_BANK_MASK{
+            return Err(SaveStateError::InvalidValue("mbc5 banks"));
+        }
+        self.set_rumble(rumble_on);
+        Ok(())
+    }
+}
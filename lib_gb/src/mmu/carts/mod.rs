@@ -1,9 +1,19 @@
 pub mod mbc;
 pub mod rom;
 pub mod mbc1;
+pub mod mbc2;
 pub mod mbc3;
+pub mod mbc5;
+pub mod rtc;
+pub mod rumble_motor;
+pub mod cartridge_header;
 
 pub use mbc::Mbc;
 pub use rom::Rom;
 pub use mbc1::Mbc1;
-pub use mbc3::Mbc3;
\ No newline at end of file
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use rumble_motor::RumbleMotor;
+pub use rtc::RtcClockSource;
+pub use cartridge_header::CartridgeHeader;
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::vec::Vec;
 use super::mbc::Mbc;
 use super::mbc::*;
+use crate::{save_state::*, error::CartridgeError};
 
 pub struct Rom{
     program: Vec<u8>,
@@ -18,8 +19,9 @@ impl Mbc for Rom{
         self.battery
     }
 
+    // roms shorter than 32KB are mirrored
     fn read_bank0(&self, address:u16)->u8{
-        return self.program[address as usize];
+        return self.program[address as usize % self.program.len()];
     }
 
     fn write_rom(&mut self, _address: u16, _value: u8){
@@ -27,7 +29,7 @@ impl Mbc for Rom{
     }
 
     fn read_current_bank(&self, address:u16)->u8{
-        return self.program[(ROM_BANK_SIZE + address) as usize];
+        return self.program[(ROM_BANK_SIZE + address) as usize % self.program.len()];
     }
 
     fn read_external_ram(&self, address:u16)->u8{
@@ -42,15 +44,25 @@ impl Mbc for Rom{
 
 impl Rom{
     
-    pub fn new(vec:Vec<u8>, battery:bool, ram:Option<Vec<u8>>)->Rom{
+    pub fn new(vec:Vec<u8>, battery:bool, ram:Option<Vec<u8>>)->Result<Rom, CartridgeError>{
         let mut rom = Rom{
             program:vec,
             external_ram:Vec::new(),
             battery:battery
         };
 
-        rom.external_ram = init_ram(rom.program[MBC_RAM_SIZE_LOCATION], ram);
+        rom.external_ram = init_ram(rom.program[MBC_RAM_SIZE_LOCATION], ram)?;
 
-        rom
+        Ok(rom)
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Rom{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.external_ram);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.external_ram, "cartridge ram")
+    }
+}
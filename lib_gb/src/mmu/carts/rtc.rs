@@ -0,0 +1,297 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{save_state::*, utils::GB_FREQUENCY};
+
+// The footer most emulators (VBA, BGB, mGBA, SameBoy) append to the battery ram of MBC3 cartridges with a clock:
+// 5 u32 of the current registers, 5 u32 of the latched registers and a u64 unix timestamp (all little endian).
+// Some emulators write a 32 bit timestamp instead, those are supported when loading.
+pub const RTC_FOOTER_SIZE:usize = 48;
+pub const RTC_SHORT_FOOTER_SIZE:usize = 44;
+const RTC_REGISTERS_COUNT:usize = 5;
+
+const M_CYCLES_PER_SECOND:u32 = GB_FREQUENCY / 4;
+const SECONDS_PER_MINUTE:u64 = 60;
+const SECONDS_PER_HOUR:u64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY:u64 = 24 * SECONDS_PER_HOUR;
+const DAYS_COUNTER_SIZE:u64 = 0x200;
+
+const SECONDS_MASK:u8 = 0x3F;
+const MINUTES_MASK:u8 = 0x3F;
+const HOURS_MASK:u8 = 0x1F;
+const DAY_HIGH_MASK:u8 = 0b1;
+const HALT_MASK:u8 = 0b100_0000;
+const DAY_CARRY_MASK:u8 = 0b1000_0000;
+const DAYS_HIGH_REGISTER_MASK:u8 = DAY_HIGH_MASK | HALT_MASK | DAY_CARRY_MASK;
+
+const SECONDS_REGISTER:u8 = 0x8;
+const MINUTES_REGISTER:u8 = 0x9;
+const HOURS_REGISTER:u8 = 0xA;
+const DAYS_LOW_REGISTER:u8 = 0xB;
+const DAYS_HIGH_REGISTER:u8 = 0xC;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RtcClockSource{
+    // Advances with the emulated cpu clock, deterministic but does not advance while the emulator is closed
+    Emulated,
+    // Advances with the host clock, including the time the emulator was closed
+    WallClock
+}
+
+#[derive(Clone, Copy, Default)]
+struct RtcRegisters{
+    seconds:u8,
+    minutes:u8,
+    hours:u8,
+    days_low:u8,
+    days_high:u8
+}
+
+impl RtcRegisters{
+    fn to_array(&self)->[u8;RTC_REGISTERS_COUNT]{
+        [self.seconds, self.minutes, self.hours, self.days_low, self.days_high]
+    }
+
+    fn from_array(values:[u8;RTC_REGISTERS_COUNT])->Self{
+        RtcRegisters{
+            seconds:values[0] & SECONDS_MASK,
+            minutes:values[1] & MINUTES_MASK,
+            hours:values[2] & HOURS_MASK,
+            days_low:values[3],
+            days_high:values[4] & DAYS_HIGH_REGISTER_MASK
+        }
+    }
+
+    fn get_days(&self)->u64{
+        self.days_low as u64 | ((self.days_high & DAY_HIGH_MASK) as u64) << 8
+    }
+
+    fn set_days(&mut self, days:u64){
+        self.days_low = days as u8;
+        self.days_high = (self.days_high & !DAY_HIGH_MASK) | ((days >> 8) as u8 & DAY_HIGH_MASK);
+    }
+
+    fn is_halted(&self)->bool{
+        self.days_high & HALT_MASK != 0
+    }
+
+    fn is_in_range(&self)->bool{
+        (self.seconds as u64) < SECONDS_PER_MINUTE && (self.minutes as u64) < 60 && self.hours < 24
+    }
+
+    // The counters can be written with out of range values, those count up to the register limit and
+    // then overflow to 0 without incrementing the next counter
+    fn tick_second(&mut self){
+        self.seconds = (self.seconds + 1) & SECONDS_MASK;
+        if self.seconds != 60{
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & MINUTES_MASK;
+        if self.minutes != 60{
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & HOURS_MASK;
+        if self.hours != 24{
+            return;
+        }
+        self.hours = 0;
+
+        self.add_days(1);
+    }
+
+    fn add_days(&mut self, days:u64){
+        let days = self.get_days() + days;
+        if days >= DAYS_COUNTER_SIZE{
+            self.days_high |= DAY_CARRY_MASK;
+        }
+        self.set_days(days % DAYS_COUNTER_SIZE);
+    }
+
+    fn advance(&mut self, mut seconds:u64){
+        if self.is_halted(){
+            return;
+        }
+
+        while seconds > 0 && !self.is_in_range(){
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0{
+            return;
+        }
+
+        let total_seconds = self.seconds as u64 + self.minutes as u64 * SECONDS_PER_MINUTE + self.hours as u64 * SECONDS_PER_HOUR + seconds;
+        self.seconds = (total_seconds % SECONDS_PER_MINUTE) as u8;
+        self.minutes = (total_seconds / SECONDS_PER_MINUTE % 60) as u8;
+        self.hours = (total_seconds / SECONDS_PER_HOUR % 24) as u8;
+        self.add_days(total_seconds / SECONDS_PER_DAY);
+    }
+}
+
+pub struct Rtc{
+    clock_source:RtcClockSource,
+    registers:RtcRegisters,
+    latched_registers:RtcRegisters,
+    latch_register:u8,
+    cycles_counter:u32,
+    // The host time the registers were last updated to (used only with the wall clock)
+    last_timestamp:u64
+}
+
+impl Rtc{
+    pub fn new(clock_source:RtcClockSource)->Self{
+        Rtc{
+            clock_source,
+            registers:RtcRegisters::default(),
+            latched_registers:RtcRegisters::default(),
+            latch_register:0xFF,
+            cycles_counter:0,
+            last_timestamp:get_current_timestamp()
+        }
+    }
+
+    // The cpu reads the latched registers
+    pub fn read_register(&self, register:u8)->u8{
+        let registers = &self.latched_registers;
+        match register{
+            SECONDS_REGISTER=>registers.seconds,
+            MINUTES_REGISTER=>registers.minutes,
+            HOURS_REGISTER=>registers.hours,
+            DAYS_LOW_REGISTER=>registers.days_low,
+            DAYS_HIGH_REGISTER=>registers.days_high,
+            _=>std::panic!("invalid rtc register: {:#X}", register)
+        }
+    }
+
+    // Writes affect both the counters and the latched registers
+    pub fn write_register(&mut self, register:u8, value:u8){
+        self.update_wall_clock();
+        match register{
+            SECONDS_REGISTER=>{
+                self.registers.seconds = value & SECONDS_MASK;
+                self.cycles_counter = 0;
+            }
+            MINUTES_REGISTER=>self.registers.minutes = value & MINUTES_MASK,
+            HOURS_REGISTER=>self.registers.hours = value & HOURS_MASK,
+            DAYS_LOW_REGISTER=>self.registers.days_low = value,
+            DAYS_HIGH_REGISTER=>self.registers.days_high = value & DAYS_HIGH_REGISTER_MASK,
+            _=>std::panic!("invalid rtc register: {:#X}", register)
+        }
+        self.latched_registers = self.registers;
+    }
+
+    // Writing 0 and then 1 latches the current time
+    pub fn write_latch(&mut self, value:u8){
+        if self.latch_register == 0 && value == 1{
+            self.update_wall_clock();
+            self.latched_registers = self.registers;
+        }
+        self.latch_register = value;
+    }
+
+    pub fn cycle(&mut self, m_cycles:u8){
+        if self.clock_source != RtcClockSource::Emulated || self.registers.is_halted(){
+            return;
+        }
+
+        self.cycles_counter += m_cycles as u32;
+        if self.cycles_counter >= M_CYCLES_PER_SECOND{
+            self.cycles_counter -= M_CYCLES_PER_SECOND;
+            self.registers.tick_second();
+        }
+    }
+
+    pub fn append_footer(&self, data:&mut Vec<u8>){
+        let mut registers = self.registers;
+        let mut timestamp = self.last_timestamp;
+        if self.clock_source == RtcClockSource::Emulated{
+            timestamp = get_current_timestamp();
+        }
+        else{
+            let now = get_current_timestamp();
+            registers.advance(now.saturating_sub(timestamp));
+            timestamp = now;
+        }
+
+        for register in registers.to_array().iter().chain(self.latched_registers.to_array().iter()){
+            data.extend_from_slice(&(*register as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&timestamp.to_le_bytes());
+    }
+
+    // With the wall clock the time passed since the footer was saved is added to the clock
+    pub fn load_footer(&mut self, footer:&[u8]){
+        let read_u32 = |index:usize| u32::from_le_bytes([footer[index * 4], footer[index * 4 + 1], footer[index * 4 + 2], footer[index * 4 + 3]]);
+        let mut registers = [0;RTC_REGISTERS_COUNT];
+        let mut latched_registers = [0;RTC_REGISTERS_COUNT];
+        for i in 0..RTC_REGISTERS_COUNT{
+            registers[i] = read_u32(i) as u8;
+            latched_registers[i] = read_u32(i + RTC_REGISTERS_COUNT) as u8;
+        }
+        self.registers = RtcRegisters::from_array(registers);
+        self.latched_registers = RtcRegisters::from_array(latched_registers);
+
+        let timestamp = if footer.len() >= RTC_FOOTER_SIZE{
+            let mut bytes = [0;8];
+            bytes.copy_from_slice(&footer[40..48]);
+            u64::from_le_bytes(bytes)
+        }
+        else{
+            read_u32(10) as u64
+        };
+
+        self.last_timestamp = timestamp;
+        self.update_wall_clock();
+        self.last_timestamp = get_current_timestamp();
+    }
+
+    fn update_wall_clock(&mut self){
+        if self.clock_source != RtcClockSource::WallClock{
+            return;
+        }
+
+        let now = get_current_timestamp();
+        self.registers.advance(now.saturating_sub(self.last_timestamp));
+        self.last_timestamp = now;
+    }
+}
+
+fn get_current_timestamp()->u64{
+    match SystemTime::now().duration_since(UNIX_EPOCH){
+        Ok(duration)=>duration.as_secs(),
+        Err(_)=>0
+    }
+}
+
+impl SaveState for Rtc{
+    fn save_state(&self, writer:&mut StateWriter){
+        for register in self.registers.to_array().iter().chain(self.latched_registers.to_array().iter()){
+            writer.write_u8(*register);
+        }
+        writer.write_u8(self.latch_register);
+        writer.write_u32(self.cycles_counter);
+        writer.write_u64(self.last_timestamp);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        let mut registers = [0;RTC_REGISTERS_COUNT];
+        for register in registers.iter_mut(){
+            *register = reader.read_u8()?;
+        }
+        let mut latched_registers = [0;RTC_REGISTERS_COUNT];
+        for register in latched_registers.iter_mut(){
+            *register = reader.read_u8()?;
+        }
+        self.registers = RtcRegisters::from_array(registers);
+        self.latched_registers = RtcRegisters::from_array(latched_registers);
+        self.latch_register = reader.read_u8()?;
+        self.cycles_counter = reader.read_u32()?;
+        self.last_timestamp = reader.read_u64()?;
+        if self.cycles_counter >= M_CYCLES_PER_SECOND{
+            return Err(SaveStateError::InvalidValue("rtc cycles counter"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+// Implemented by frontends that want to react to the rumble motor of rumble cartridges (MBC5 rumble variants)
+pub trait RumbleMotor{
+    // Called only when the motor state changes
+    fn set_rumble(&mut self, on:bool);
+}
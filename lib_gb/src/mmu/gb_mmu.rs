@@ -1,20 +1,27 @@
 use super::{io_components::IoComponents, memory::*};
 use super::access_bus::AccessBus;
+use super::vram_dma_transfer::{VramDmaMode, VRAM_DMA_BLOCK_SIZE};
 use crate::{apu::{audio_device::AudioDevice, gb_apu::GbApu}, utils::memory_registers::BOOT_REGISTER_ADDRESS};
 use super::carts::mbc::Mbc;
 use crate::ppu::ppu_state::PpuState;
+use crate::save_state::*;
+use crate::serial::{gb_serial::GbSerial, serial_device::SerialDevice};
 use std::boxed::Box;
 
 pub const BOOT_ROM_SIZE:usize = 0x100;
+// The cgb bootrom is mapped to 0x0-0xFF and 0x200-0x8FF, the cartridge header is visible between them
+pub const CGB_BOOT_ROM_SIZE:usize = 0x900;
 const HRAM_SIZE:usize = 0x7F;
 const DMA_SIZE:u16 = 0xA0;
 const DMA_DEST:u16 = 0xFE00;
+// In normal speed cycles
+const VRAM_DMA_BLOCK_CYCLES:u32 = 8;
 
 const BAD_READ_VALUE:u8 = 0xFF;
 
-pub struct GbMmu<'a, D:AudioDevice>{
-    pub io_components: IoComponents<D>,
-    boot_rom:[u8;BOOT_ROM_SIZE],
+pub struct GbMmu<'a, D:AudioDevice, S:SerialDevice>{
+    pub io_components: IoComponents<D, S>,
+    boot_rom:Vec<u8>,
     mbc: &'a mut Box<dyn Mbc>,
     hram: [u8;HRAM_SIZE],
     interupt_enable_register:u8
@@ -22,7 +29,7 @@ pub struct GbMmu<'a, D:AudioDevice>{
 
 
 //DMA only locks the used bus. there 2 possible used buses: extrnal (wram, rom, sram) and video (vram)
-impl<'a, D:AudioDevice> Memory for GbMmu<'a, D>{
+impl<'a, D:AudioDevice, S:SerialDevice> Memory for GbMmu<'a, D, S>{
     fn read(&self, address:u16)->u8{
         if let Some (bus) = &self.io_components.dma.enable{
             return match address{
@@ -92,7 +99,7 @@ impl<'a, D:AudioDevice> Memory for GbMmu<'a, D>{
     }
 }
 
-impl<'a, D:AudioDevice> UnprotectedMemory for GbMmu<'a, D>{
+impl<'a, D:AudioDevice, S:SerialDevice> UnprotectedMemory for GbMmu<'a, D, S>{
     fn read_unprotected(&self, address:u16) ->u8 {
         return match address{
             0x0..=0xFF=>{
@@ -102,13 +109,15 @@ impl<'a, D:AudioDevice> UnprotectedMemory for GbMmu<'a, D>{
                 
                 return self.boot_rom[address as usize];
             },
+            0x200..=0x8FF if !self.io_components.finished_boot && self.boot_rom.len() == CGB_BOOT_ROM_SIZE=>self.boot_rom[address as usize],
             0x100..=0x3FFF=>self.mbc.read_bank0(address),
             0x4000..=0x7FFF=>self.mbc.read_current_bank(address-0x4000),
             0x8000..=0x9FFF=>self.io_components.ppu.vram.read_current_bank(address-0x8000),
             0xA000..=0xBFFF=>self.mbc.read_external_ram(address-0xA000),
             0xC000..=0xCFFF =>self.io_components.ram.read_bank0(address - 0xC000), 
             0xD000..=0xDFFF=>self.io_components.ram.read_current_bank(address-0xD000),
-            0xE000..=0xFDFF=>self.io_components.ram.read_bank0(address - 0xE000),
+            0xE000..=0xEFFF=>self.io_components.ram.read_bank0(address - 0xE000),
+            0xF000..=0xFDFF=>self.io_components.ram.read_current_bank(address - 0xF000),
             0xFE00..=0xFE9F=>self.io_components.ppu.sprite_attribute_table[(address-0xFE00) as usize],
             0xFEA0..=0xFEFF=>0x0,
             0xFF00..=0xFF7F=>self.io_components.read_unprotected(address - 0xFF00),
@@ -123,8 +132,9 @@ impl<'a, D:AudioDevice> UnprotectedMemory for GbMmu<'a, D>{
             0x8000..=0x9FFF=>self.io_components.ppu.vram.write_current_bank(address-0x8000, value),
             0xA000..=0xBFFF=>self.mbc.write_external_ram(address-0xA000,value),
             0xC000..=0xCFFF =>self.io_components.ram.write_bank0(address - 0xC000,value), 
-            0xE000..=0xFDFF=>self.io_components.ram.write_bank0(address - 0xE000,value),
             0xD000..=0xDFFF=>self.io_components.ram.write_current_bank(address-0xD000,value),
+            0xE000..=0xEFFF=>self.io_components.ram.write_bank0(address - 0xE000,value),
+            0xF000..=0xFDFF=>self.io_components.ram.write_current_bank(address - 0xF000,value),
             0xFE00..=0xFE9F=>self.io_components.ppu.sprite_attribute_table[(address-0xFE00) as usize] = value,
             0xFEA0..=0xFEFF=>{},
             0xFF00..=0xFF7F=>self.io_components.write_unprotected(address - 0xFF00, value),
@@ -134,10 +144,11 @@ impl<'a, D:AudioDevice> UnprotectedMemory for GbMmu<'a, D>{
     }
 }
 
-impl<'a, D:AudioDevice> GbMmu<'a, D>{
-    pub fn new_with_bootrom(mbc:&'a mut Box<dyn Mbc>, boot_rom:[u8;BOOT_ROM_SIZE], apu:GbApu<D>)->Self{
+impl<'a, D:AudioDevice, S:SerialDevice> GbMmu<'a, D, S>{
+    // The bootrom should be BOOT_ROM_SIZE bytes for DMG or CGB_BOOT_ROM_SIZE bytes for CGB
+    pub fn new_with_bootrom(mbc:&'a mut Box<dyn Mbc>, boot_rom:Vec<u8>, apu:GbApu<D>, serial:GbSerial<S>)->Self{
         GbMmu{
-            io_components:IoComponents::new(apu),
+            io_components:IoComponents::new(apu, serial),
             mbc:mbc,
             hram:[0;HRAM_SIZE],
             interupt_enable_register:0,
@@ -145,13 +156,13 @@ impl<'a, D:AudioDevice> GbMmu<'a, D>{
         }
     }
 
-    pub fn new(mbc:&'a mut Box<dyn Mbc>, apu:GbApu<D>)->Self{
+    pub fn new(mbc:&'a mut Box<dyn Mbc>, apu:GbApu<D>, serial:GbSerial<S>)->Self{
         let mut mmu = GbMmu{
-            io_components:IoComponents::new(apu),
+            io_components:IoComponents::new(apu, serial),
             mbc:mbc,
             hram:[0;HRAM_SIZE],
             interupt_enable_register:0,
-            boot_rom:[0;BOOT_ROM_SIZE],
+            boot_rom:Vec::new(),
         };
 
         //Setting the bootrom register to be set (the boot sequence has over)
@@ -160,9 +171,42 @@ impl<'a, D:AudioDevice> GbMmu<'a, D>{
         mmu
     }
 
-    pub fn cycle(&mut self, cycles:u8){
+    // The oam dma runs at the cpu speed, cartridge hardware (like the rtc) has its own clock
+    pub fn cycle(&mut self, cycles:u8, double_speed:bool){
         self.handle_dma_trasnfer(cycles);
-        self.io_components.cycle(cycles as u32);
+        let normal_speed_cycles = self.io_components.cycle(cycles as u32, double_speed);
+        self.mbc.cycle(normal_speed_cycles as u8);
+        self.handle_vram_dma_transfer(cycles, double_speed);
+    }
+
+    // The cpu is halted while the hdma copies
+    pub fn is_cpu_stalled(&self)->bool{
+        self.io_components.vram_dma.is_stalling_cpu()
+    }
+
+    fn handle_vram_dma_transfer(&mut self, cycles:u8, double_speed:bool){
+        let hblank_started = self.io_components.ppu.hblank_started;
+        self.io_components.ppu.hblank_started = false;
+
+        let vram_dma = &mut self.io_components.vram_dma;
+        vram_dma.stall_cycles = vram_dma.stall_cycles.saturating_sub(cycles as u32);
+        let blocks = match vram_dma.mode{
+            Some(VramDmaMode::GeneralPurpose)=>vram_dma.remaining_blocks,
+            Some(VramDmaMode::Hblank) if hblank_started=>1,
+            _=>return
+        };
+
+        for _ in 0..blocks{
+            for i in 0..VRAM_DMA_BLOCK_SIZE{
+                let value = self.read_unprotected(self.io_components.vram_dma.source_address.wrapping_add(i));
+                let destination = self.io_components.vram_dma.destination_address + i;
+                self.io_components.ppu.vram.write_current_bank(destination, value);
+            }
+            self.io_components.vram_dma.finish_block();
+        }
+
+        let block_cycles = if double_speed {VRAM_DMA_BLOCK_CYCLES * 2} else {VRAM_DMA_BLOCK_CYCLES};
+        self.io_components.vram_dma.stall_cycles += blocks as u32 * block_cycles;
     }
 
     fn handle_dma_trasnfer(&mut self, cycles: u8) {
@@ -197,4 +241,22 @@ impl<'a, D:AudioDevice> GbMmu<'a, D>{
     fn bad_dma_write(address:u16){
         log::warn!("bad memory write during dma. {:#X}", address)
     }
-}
\ No newline at end of file
+}
+
+impl<'a, D:AudioDevice, S:SerialDevice> SaveState for GbMmu<'a, D, S>{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.io_components.save_state(writer);
+        writer.write_bytes(&self.boot_rom);
+        writer.write_bytes(&self.hram);
+        writer.write_u8(self.interupt_enable_register);
+        self.mbc.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.io_components.load_state(reader)?;
+        reader.read_bytes_into(&mut self.boot_rom, "boot rom")?;
+        reader.read_bytes_into(&mut self.hram, "hram")?;
+        self.interupt_enable_register = reader.read_u8()?;
+        self.mbc.load_state(reader)
+    }
+}
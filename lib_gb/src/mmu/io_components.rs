@@ -1,22 +1,36 @@
-use crate::{apu::{audio_device::AudioDevice, gb_apu::GbApu, set_nr11, set_nr12, set_nr13}, ppu::ppu_register_updater::*, timer::timer_register_updater::*, utils::memory_registers::*};
+use crate::{apu::{audio_device::AudioDevice, gb_apu::GbApu, set_nr11, set_nr12, set_nr13}, ppu::ppu_register_updater::*, timer::timer_register_updater::*, utils::{memory_registers::*, bit_masks::BIT_0_MASK}};
 use crate::ppu::gb_ppu::GbPpu;
 use crate::apu::*;
 use crate::timer::gb_timer::GbTimer;
-use super::{access_bus::AccessBus, memory::*, oam_dma_transfer::OamDmaTransfer, ram::Ram};
+use crate::serial::{gb_serial::GbSerial, serial_device::SerialDevice, serial_register_updater::*};
+use super::{access_bus::AccessBus, memory::*, oam_dma_transfer::OamDmaTransfer, vram_dma_transfer::VramDmaTransfer, ram::Ram};
 use super::io_ports::*;
+use crate::save_state::*;
 
 
 pub const IO_PORTS_SIZE:usize = 0x80;
+const VBK_BANK_MASK:u8 = 0b1;
+const SVBK_BANK_MASK:u8 = 0b111;
+const KEY0_DMG_COMPATIBILITY_MASK:u8 = 0b100;
 
 
-pub struct IoComponents<AD:AudioDevice>{
+pub struct IoComponents<AD:AudioDevice, SD:SerialDevice>{
     pub ram: Ram,
     pub apu: GbApu<AD>,
     pub timer: GbTimer,
+    pub serial: GbSerial<SD>,
     pub ppu:GbPpu,
     ports:[u8;IO_PORTS_SIZE],
     pub dma:OamDmaTransfer,
+    pub vram_dma:VramDmaTransfer,
     pub finished_boot:bool,
+    // The hardware is a CGB, even when running a DMG cartridge
+    pub cgb_hardware:bool,
+    pub cgb_mode:bool,
+    // Updated from the cpu every cycle
+    pub double_speed:bool,
+    pub speed_switch_armed:bool,
+    double_speed_cycles:u32
 }
 
 io_port_index!(LCDC_REGISTER_INDEX, LCDC_REGISTER_ADDRESS);
@@ -33,16 +47,34 @@ io_port_index!(BGP_REGISTER_INDEX, BGP_REGISTER_ADDRESS);
 io_port_index!(OBP0_REGISTER_INDEX, OBP0_REGISTER_ADDRESS);
 io_port_index!(OBP1_REGISTER_INDEX, OBP1_REGISTER_ADDRESS);
 io_port_index!(IF_REGISTER_INDEX, IF_REGISTER_ADDRESS);
+io_port_index!(SB_REGISTER_INDEX, SB_REGISTER_ADDRESS);
+io_port_index!(SC_REGISTER_INDEX, SC_REGISTER_ADDRESS);
+io_port_index!(KEY0_REGISTER_INDEX, KEY0_REGISTER_ADDRESS);
+io_port_index!(KEY1_REGISTER_INDEX, KEY1_REGISTER_ADDRESS);
+io_port_index!(VBK_REGISTER_INDEX, VBK_REGISTER_ADDRESS);
+io_port_index!(SVBK_REGISTER_INDEX, SVBK_REGISTER_ADDRESS);
+io_port_index!(HDMA1_REGISTER_INDEX, HDMA1_REGISTER_ADDRESS);
+io_port_index!(HDMA2_REGISTER_INDEX, HDMA2_REGISTER_ADDRESS);
+io_port_index!(HDMA3_REGISTER_INDEX, HDMA3_REGISTER_ADDRESS);
+io_port_index!(HDMA4_REGISTER_INDEX, HDMA4_REGISTER_ADDRESS);
+io_port_index!(HDMA5_REGISTER_INDEX, HDMA5_REGISTER_ADDRESS);
+io_port_index!(BCPS_REGISTER_INDEX, BCPS_REGISTER_ADDRESS);
+io_port_index!(BCPD_REGISTER_INDEX, BCPD_REGISTER_ADDRESS);
+io_port_index!(OCPS_REGISTER_INDEX, OCPS_REGISTER_ADDRESS);
+io_port_index!(OCPD_REGISTER_INDEX, OCPD_REGISTER_ADDRESS);
 
 
-impl<AD:AudioDevice> Memory for IoComponents<AD>{
+impl<AD:AudioDevice, SD:SerialDevice> Memory for IoComponents<AD, SD>{
     fn read(&self, address:u16)->u8 {
         let mut value = self.ports[address as usize];
         return match address {
             //Timer
-            TAC_REGISTER_INDEX=> value & 0b111,
+            TAC_REGISTER_INDEX=> value | 0b1111_1000,
             DIV_REGISTER_INDEX=> get_div(&self.timer),
             TIMA_REGISTER_INDEX=> self.timer.tima_register,
+            //Serial
+            SB_REGISTER_INDEX=> get_sb(&self.serial),
+            SC_REGISTER_INDEX=> get_sc(&self.serial),
             //APU
             NR10_REGISTER_INDEX=>value | 0b1000_0000,
             NR11_REGISTER_INDEX=> value | 0b0011_1111,
@@ -73,6 +105,20 @@ impl<AD:AudioDevice> Memory for IoComponents<AD>{
                 let joypad_value = self.ports[JOYP_REGISTER_INDEX as usize];
                 (joypad_value & 0xF) | (value & 0xF0)
             }
+            //CGB
+            KEY0_REGISTER_INDEX if self.cgb_hardware && !self.finished_boot => value,
+            KEY0_REGISTER_INDEX => 0xFF,
+            KEY1_REGISTER_INDEX if self.cgb_mode => 0x7E | ((self.double_speed as u8) << 7) | self.speed_switch_armed as u8,
+            KEY1_REGISTER_INDEX => 0xFF,
+            VBK_REGISTER_INDEX if self.cgb_mode => value | 0b1111_1110,
+            SVBK_REGISTER_INDEX if self.cgb_mode => value | 0b1111_1000,
+            BCPS_REGISTER_INDEX if self.cgb_mode => self.ppu.bg_color_ram.read_index_register(),
+            BCPD_REGISTER_INDEX if self.cgb_mode => get_bcpd(&self.ppu),
+            OCPS_REGISTER_INDEX if self.cgb_mode => self.ppu.obj_color_ram.read_index_register(),
+            OCPD_REGISTER_INDEX if self.cgb_mode => get_ocpd(&self.ppu),
+            HDMA5_REGISTER_INDEX if self.cgb_mode => self.vram_dma.get_hdma5(),
+            //the hdma addresses are write only
+            VBK_REGISTER_INDEX | SVBK_REGISTER_INDEX | BCPS_REGISTER_INDEX..=OCPD_REGISTER_INDEX | HDMA1_REGISTER_INDEX..=HDMA5_REGISTER_INDEX => 0xFF,
             _=>value
         };
     }
@@ -90,6 +136,9 @@ impl<AD:AudioDevice> Memory for IoComponents<AD>{
                 set_tac(&mut self.timer, value);
                 value &= 0b111;
             }
+            //Serial
+            SB_REGISTER_INDEX=> set_sb(&mut self.serial, value),
+            SC_REGISTER_INDEX=> set_sc(&mut self.serial, value),
             //APU
             NR10_REGISTER_INDEX=> set_nr10(&mut self.apu.sweep_tone_channel, value),
             NR11_REGISTER_INDEX=> set_nr11(&mut self.apu.sweep_tone_channel, value),
@@ -130,17 +179,47 @@ impl<AD:AudioDevice> Memory for IoComponents<AD>{
                     0xA0..=0xFF=>Some(AccessBus::External)
                 }
             }
-            BGP_REGISTER_INDEX=> handle_bg_pallet_register(value,&mut self.ppu.bg_color_mapping),
-            OBP0_REGISTER_INDEX=> handle_obp_pallet_register(value,&mut self.ppu.obj_color_mapping0),
-            OBP1_REGISTER_INDEX=> handle_obp_pallet_register(value,&mut self.ppu.obj_color_mapping1),
+            BGP_REGISTER_INDEX=> set_bgp(&mut self.ppu, value),
+            OBP0_REGISTER_INDEX=> set_obp0(&mut self.ppu, value),
+            OBP1_REGISTER_INDEX=> set_obp1(&mut self.ppu, value),
             WY_REGISTER_INDEX=> handle_wy_register(value, &mut self.ppu),
             WX_REGISTER_INDEX=> handle_wx_register(value, &mut self.ppu),
-            BOOT_REGISTER_INDEX=> self.finished_boot = value != 0,
+            BOOT_REGISTER_INDEX=> {
+                // the cgb bootrom selects the mode through KEY0 before it unmaps itself
+                if value != 0 && !self.finished_boot && self.cgb_hardware && self.ports[KEY0_REGISTER_INDEX as usize] & KEY0_DMG_COMPATIBILITY_MASK != 0{
+                    self.enter_dmg_compatibility_mode();
+                }
+                self.finished_boot = value != 0;
+            }
+            // KEY0 is writable only by the cgb bootrom
+            KEY0_REGISTER_INDEX=> if !self.cgb_hardware || self.finished_boot{
+                value = self.ports[KEY0_REGISTER_INDEX as usize];
+            },
             JOYP_REGISTER_INDEX => {
                 let joypad_value = self.ports[JOYP_REGISTER_INDEX as usize];
                 value = (joypad_value & 0xF) | (value & 0xF0);
             }
-            // TODO: handle gbc registers (expecailly ram and vram)
+            KEY1_REGISTER_INDEX=> if self.cgb_mode{
+                self.speed_switch_armed = value & BIT_0_MASK != 0;
+            },
+            // In DMG mode the banks stay fixed (vram bank 0 and wram bank 1)
+            VBK_REGISTER_INDEX=> if self.cgb_mode{
+                value &= VBK_BANK_MASK;
+                self.ppu.vram.set_bank(value);
+            },
+            SVBK_REGISTER_INDEX=> if self.cgb_mode{
+                value &= SVBK_BANK_MASK;
+                self.ram.set_bank(value);
+            },
+            HDMA1_REGISTER_INDEX=> if self.cgb_mode {self.vram_dma.set_source_high(value)},
+            HDMA2_REGISTER_INDEX=> if self.cgb_mode {self.vram_dma.set_source_low(value)},
+            HDMA3_REGISTER_INDEX=> if self.cgb_mode {self.vram_dma.set_destination_high(value)},
+            HDMA4_REGISTER_INDEX=> if self.cgb_mode {self.vram_dma.set_destination_low(value)},
+            HDMA5_REGISTER_INDEX=> if self.cgb_mode {self.vram_dma.set_hdma5(value)},
+            BCPS_REGISTER_INDEX=> if self.cgb_mode {self.ppu.bg_color_ram.write_index_register(value)},
+            BCPD_REGISTER_INDEX=> if self.cgb_mode {set_bcpd(&mut self.ppu, value)},
+            OCPS_REGISTER_INDEX=> if self.cgb_mode {self.ppu.obj_color_ram.write_index_register(value)},
+            OCPD_REGISTER_INDEX=> if self.cgb_mode {set_ocpd(&mut self.ppu, value)},
             _=>{}
         }
 
@@ -148,7 +227,7 @@ impl<AD:AudioDevice> Memory for IoComponents<AD>{
     }
 }
 
-impl<AD:AudioDevice> UnprotectedMemory for IoComponents<AD>{
+impl<AD:AudioDevice, SD:SerialDevice> UnprotectedMemory for IoComponents<AD, SD>{
     fn read_unprotected(&self, address:u16)->u8 {
         self.ports[address as usize]
     }
@@ -158,16 +237,83 @@ impl<AD:AudioDevice> UnprotectedMemory for IoComponents<AD>{
     }
 }
 
-impl<AD:AudioDevice> IoComponents<AD>{
-    pub fn new(apu:GbApu<AD>)->Self{
-        Self{apu, ports:[0;IO_PORTS_SIZE], timer:GbTimer::default(), ppu:GbPpu::default(), dma:OamDmaTransfer::default(),finished_boot:false, ram:Ram::default()}
+impl<AD:AudioDevice, SD:SerialDevice> IoComponents<AD, SD>{
+    pub fn new(apu:GbApu<AD>, serial:GbSerial<SD>)->Self{
+        Self{apu, serial, ports:[0;IO_PORTS_SIZE], timer:GbTimer::default(), ppu:GbPpu::default(), dma:OamDmaTransfer::default(), vram_dma:VramDmaTransfer::default(),finished_boot:false, ram:Ram::default(),
+            cgb_hardware:false, cgb_mode:false, double_speed:false, speed_switch_armed:false, double_speed_cycles:0}
+    }
+
+    // The CGB registers are disabled and the DMG palettes select colors from the CGB palettes
+    pub fn enter_dmg_compatibility_mode(&mut self){
+        self.cgb_mode = false;
+        self.serial.cgb_mode = false;
+        self.ppu.gbc_mode = false;
+        self.ppu.dmg_compatibility = true;
     }
 
-    pub fn cycle(&mut self, cycles:u32){
+    // The timer and serial run at the cpu speed while the apu and ppu keep their normal speed,
+    // returns the cycles passed at normal speed
+    pub fn cycle(&mut self, cycles:u32, double_speed:bool)->u32{
+        self.double_speed = double_speed;
+        let normal_speed_cycles = if double_speed{
+            self.double_speed_cycles += cycles;
+            let normal_speed_cycles = self.double_speed_cycles / 2;
+            self.double_speed_cycles %= 2;
+            normal_speed_cycles
+        }
+        else{
+            cycles
+        };
+
         let mut if_register = self.ports[IF_REGISTER_INDEX as usize];
         self.timer.cycle(&mut if_register, cycles as u8);
-        self.apu.cycle(cycles as u8);
-        self.ppu.update_gb_screen(&mut if_register, cycles);
+        self.serial.cycle(&mut if_register, cycles as u8);
+        if normal_speed_cycles != 0{
+            self.apu.cycle(normal_speed_cycles as u8);
+            self.ppu.update_gb_screen(&mut if_register, normal_speed_cycles);
+        }
         self.ports[IF_REGISTER_INDEX as usize] = if_register;
+
+        return normal_speed_cycles;
+    }
+}
+
+impl<AD:AudioDevice, SD:SerialDevice> SaveState for IoComponents<AD, SD>{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.ram.save_state(writer);
+        self.apu.save_state(writer);
+        self.timer.save_state(writer);
+        self.serial.save_state(writer);
+        self.ppu.save_state(writer);
+        writer.write_bytes(&self.ports);
+        self.dma.save_state(writer);
+        self.vram_dma.save_state(writer);
+        writer.write_bool(self.finished_boot);
+        writer.write_bool(self.cgb_hardware);
+        writer.write_bool(self.cgb_mode);
+        writer.write_bool(self.double_speed);
+        writer.write_bool(self.speed_switch_armed);
+        writer.write_u32(self.double_speed_cycles);
     }
-}
\ No newline at end of file
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.ram.load_state(reader)?;
+        self.apu.load_state(reader)?;
+        self.timer.load_state(reader)?;
+        self.serial.load_state(reader)?;
+        self.ppu.load_state(reader)?;
+        reader.read_bytes_into(&mut self.ports, "io ports")?;
+        self.dma.load_state(reader)?;
+        self.vram_dma.load_state(reader)?;
+        self.finished_boot = reader.read_bool()?;
+        self.cgb_hardware = reader.read_bool()?;
+        self.cgb_mode = reader.read_bool()?;
+        self.double_speed = reader.read_bool()?;
+        self.speed_switch_armed = reader.read_bool()?;
+        self.double_speed_cycles = reader.read_u32()?;
+        if self.double_speed_cycles > 1{
+            return Err(SaveStateError::InvalidValue("double speed cycles"));
+        }
+        Ok(())
+    }
+}
@@ -7,4 +7,5 @@ pub mod io_ports;
 pub mod carts;
 pub mod access_bus;
 pub mod oam_dma_transfer;
+pub mod vram_dma_transfer;
 pub mod io_components;
\ No newline at end of file
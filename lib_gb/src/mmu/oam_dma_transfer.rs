@@ -1,4 +1,5 @@
 use super::access_bus::AccessBus;
+use crate::save_state::*;
 
 pub struct OamDmaTransfer{
     pub soure_address:u16,
@@ -10,4 +11,28 @@ impl Default for OamDmaTransfer{
     fn default() -> Self {
         OamDmaTransfer{dma_cycle_counter:0, enable:None, soure_address:0}
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for OamDmaTransfer{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u16(self.soure_address);
+        writer.write_u8(match self.enable{
+            None=>0,
+            Some(AccessBus::External)=>1,
+            Some(AccessBus::Video)=>2
+        });
+        writer.write_u16(self.dma_cycle_counter);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.soure_address = reader.read_u16()?;
+        self.enable = match reader.read_u8()?{
+            0=>None,
+            1=>Some(AccessBus::External),
+            2=>Some(AccessBus::Video),
+            _=>return Err(SaveStateError::InvalidValue("oam dma bus"))
+        };
+        self.dma_cycle_counter = reader.read_u16()?;
+        Ok(())
+    }
+}
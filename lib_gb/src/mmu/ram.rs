@@ -1,3 +1,4 @@
+use crate::save_state::*;
 
 const RAM_SZIE:usize = 0x8000;
 const BANK_SIZE:usize = 0x1000;
@@ -44,4 +45,20 @@ impl Default for Ram{
             ram_bank_register:1
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Ram{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.memory);
+        writer.write_u8(self.ram_bank_register);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.memory, "wram")?;
+        self.ram_bank_register = reader.read_u8()?;
+        if self.ram_bank_register == 0 || self.ram_bank_register as usize >= RAM_SZIE / BANK_SIZE{
+            return Err(SaveStateError::InvalidValue("wram bank"));
+        }
+        Ok(())
+    }
+}
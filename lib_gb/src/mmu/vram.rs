@@ -1,3 +1,5 @@
+use crate::save_state::*;
+
 const VRAM_SIZE:usize = 0x4000;
 const VRAM_BANK_SIZE:usize = 0x2000;
 pub struct VRam{
@@ -10,6 +12,11 @@ impl VRam{
         self.current_bank_register = bank;
     }
 
+    // The ppu reads the banks directly regardless of the bank register
+    pub fn read_bank(&self, bank:u8, address:u16)->u8{
+        return self.memory[(address as usize) + ((bank as usize)*VRAM_BANK_SIZE)];
+    }
+
     pub fn read_current_bank(&self, address:u16)->u8{
         return self.memory[self.get_valid_address(address)];
     }
@@ -39,4 +46,20 @@ impl Clone for VRam{
             current_bank_register:self.current_bank_register
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for VRam{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.memory);
+        writer.write_u8(self.current_bank_register);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.memory, "vram")?;
+        self.current_bank_register = reader.read_u8()?;
+        if self.current_bank_register as usize >= VRAM_SIZE / VRAM_BANK_SIZE{
+            return Err(SaveStateError::InvalidValue("vram bank"));
+        }
+        Ok(())
+    }
+}
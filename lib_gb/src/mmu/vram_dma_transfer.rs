@@ -0,0 +1,127 @@
+use crate::save_state::*;
+use crate::utils::bit_masks::BIT_7_MASK;
+
+pub const VRAM_DMA_BLOCK_SIZE:u16 = 0x10;
+const SOURCE_ADDRESS_MASK:u16 = 0xFFF0;
+const DESTINATION_ADDRESS_MASK:u16 = 0x1FF0;
+const LENGTH_MASK:u8 = 0x7F;
+
+pub enum VramDmaMode{
+    // Copies all the blocks at once while the cpu is halted
+    GeneralPurpose,
+    // Copies a block on every hblank
+    Hblank
+}
+
+impl Clone for VramDmaMode{
+    fn clone(&self) -> Self {
+        match *self{
+            VramDmaMode::GeneralPurpose=>VramDmaMode::GeneralPurpose,
+            VramDmaMode::Hblank=>VramDmaMode::Hblank
+        }
+    }
+}
+
+impl Copy for VramDmaMode{}
+
+// The CGB HDMA, copies blocks of 0x10 bytes into the current vram bank
+pub struct VramDmaTransfer{
+    pub source_address:u16,
+    // Relative to the start of the vram
+    pub destination_address:u16,
+    pub remaining_blocks:u8,
+    pub mode:Option<VramDmaMode>,
+    // The cpu cycles the cpu is halted for the copied blocks
+    pub stall_cycles:u32
+}
+
+impl Default for VramDmaTransfer{
+    fn default() -> Self {
+        VramDmaTransfer{source_address:0, destination_address:0, remaining_blocks:0, mode:None, stall_cycles:0}
+    }
+}
+
+impl VramDmaTransfer{
+    pub fn set_source_high(&mut self, value:u8){
+        self.source_address = ((value as u16) << 8 | (self.source_address & 0xFF)) & SOURCE_ADDRESS_MASK;
+    }
+
+    pub fn set_source_low(&mut self, value:u8){
+        self.source_address = ((self.source_address & 0xFF00) | value as u16) & SOURCE_ADDRESS_MASK;
+    }
+
+    pub fn set_destination_high(&mut self, value:u8){
+        self.destination_address = ((value as u16) << 8 | (self.destination_address & 0xFF)) & DESTINATION_ADDRESS_MASK;
+    }
+
+    pub fn set_destination_low(&mut self, value:u8){
+        self.destination_address = ((self.destination_address & 0xFF00) | value as u16) & DESTINATION_ADDRESS_MASK;
+    }
+
+    // Bit 7 is cleared while an hblank transfer is active, the lower bits are the remaining blocks minus 1
+    // (0xFF once a transfer is done)
+    pub fn get_hdma5(&self)->u8{
+        let remaining_length = self.remaining_blocks.wrapping_sub(1) & LENGTH_MASK;
+        return match self.mode{
+            Some(VramDmaMode::Hblank)=>remaining_length,
+            _=>remaining_length | BIT_7_MASK
+        };
+    }
+
+    // Clearing bit 7 while an hblank transfer is active cancels it
+    pub fn set_hdma5(&mut self, value:u8){
+        if let Some(VramDmaMode::Hblank) = self.mode{
+            if value & BIT_7_MASK == 0{
+                self.mode = None;
+                return;
+            }
+        }
+
+        self.remaining_blocks = (value & LENGTH_MASK) + 1;
+        self.mode = if value & BIT_7_MASK == 0 {Some(VramDmaMode::GeneralPurpose)} else {Some(VramDmaMode::Hblank)};
+    }
+
+    pub fn is_stalling_cpu(&self)->bool{
+        self.stall_cycles != 0
+    }
+
+    pub fn finish_block(&mut self){
+        self.source_address = self.source_address.wrapping_add(VRAM_DMA_BLOCK_SIZE);
+        self.destination_address = (self.destination_address + VRAM_DMA_BLOCK_SIZE) & DESTINATION_ADDRESS_MASK;
+        self.remaining_blocks -= 1;
+        if self.remaining_blocks == 0{
+            self.mode = None;
+        }
+    }
+}
+
+impl SaveState for VramDmaTransfer{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u16(self.source_address);
+        writer.write_u16(self.destination_address);
+        writer.write_u8(self.remaining_blocks);
+        writer.write_u8(match self.mode{
+            None=>0,
+            Some(VramDmaMode::GeneralPurpose)=>1,
+            Some(VramDmaMode::Hblank)=>2
+        });
+        writer.write_u32(self.stall_cycles);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.source_address = reader.read_u16()? & SOURCE_ADDRESS_MASK;
+        self.destination_address = reader.read_u16()? & DESTINATION_ADDRESS_MASK;
+        self.remaining_blocks = reader.read_u8()?;
+        self.mode = match reader.read_u8()?{
+            0=>None,
+            1=>Some(VramDmaMode::GeneralPurpose),
+            2=>Some(VramDmaMode::Hblank),
+            _=>return Err(SaveStateError::InvalidValue("vram dma mode"))
+        };
+        self.stall_cycles = reader.read_u32()?;
+        if self.mode.is_some() && self.remaining_blocks == 0{
+            return Err(SaveStateError::InvalidValue("vram dma length"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::utils::bit_masks::*;
+
+// The CGB keeps an attribute byte for every tile of the background maps in vram bank 1
+pub struct BgTileAttribute{
+    pub palette_number:u8,
+    pub vram_bank:u8,
+    pub flip_x:bool,
+    pub flip_y:bool,
+    pub bg_priority:bool
+}
+
+impl BgTileAttribute{
+    pub fn new(attributes:u8)->Self{
+        BgTileAttribute{
+            palette_number: attributes & 0b111,
+            vram_bank: (attributes & BIT_3_MASK) >> 3,
+            flip_x: attributes & BIT_5_MASK != 0,
+            flip_y: attributes & BIT_6_MASK != 0,
+            bg_priority: attributes & BIT_7_MASK != 0
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{save_state::*, utils::bit_masks::*};
+
+const PALETTES_COUNT:usize = 8;
+const COLORS_PER_PALETTE:usize = 4;
+const PALETTE_RAM_SIZE:usize = PALETTES_COUNT * COLORS_PER_PALETTE * 2;
+const INDEX_MASK:u8 = 0b11_1111;
+const RGB555_MASK:u16 = 0x7FFF;
+
+// 8 palettes of 4 RGB555 colors (little endian), accessed through an index register and a data register
+pub struct CgbPaletteRam{
+    memory:[u8;PALETTE_RAM_SIZE],
+    index:u8,
+    auto_increment:bool
+}
+
+impl CgbPaletteRam{
+    pub fn read_index_register(&self)->u8{
+        self.index | BIT_6_MASK | ((self.auto_increment as u8) << 7)
+    }
+
+    pub fn write_index_register(&mut self, value:u8){
+        self.index = value & INDEX_MASK;
+        self.auto_increment = value & BIT_7_MASK != 0;
+    }
+
+    pub fn read_data_register(&self)->u8{
+        self.memory[self.index as usize]
+    }
+
+    // Blocked writes (during pixel transfer) are ignored but still increment the index
+    pub fn write_data_register(&mut self, value:u8, blocked:bool){
+        if !blocked{
+            self.memory[self.index as usize] = value;
+        }
+        if self.auto_increment{
+            self.index = (self.index + 1) & INDEX_MASK;
+        }
+    }
+
+    pub fn set_color(&mut self, palette:u8, color:u8, value:u16){
+        let address = Self::get_color_address(palette, color);
+        self.memory[address..address + 2].copy_from_slice(&(value & RGB555_MASK).to_le_bytes());
+    }
+
+    pub fn get_color(&self, palette:u8, color:u8)->u16{
+        let address = Self::get_color_address(palette, color);
+        return u16::from_le_bytes([self.memory[address], self.memory[address + 1]]) & RGB555_MASK;
+    }
+
+    fn get_color_address(palette:u8, color:u8)->usize{
+        (palette as usize * COLORS_PER_PALETTE + color as usize) * 2
+    }
+}
+
+impl Default for CgbPaletteRam{
+    fn default()->Self{
+        // all white, like the cgb bootrom leaves the background palettes
+        CgbPaletteRam{
+            memory:[0xFF;PALETTE_RAM_SIZE],
+            index:0,
+            auto_increment:false
+        }
+    }
+}
+
+impl SaveState for CgbPaletteRam{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.memory);
+        writer.write_u8(self.index);
+        writer.write_bool(self.auto_increment);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.memory, "cgb palette ram")?;
+        self.index = reader.read_u8()?;
+        self.auto_increment = reader.read_bool()?;
+        if self.index > INDEX_MASK{
+            return Err(SaveStateError::InvalidValue("cgb palette index"));
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,54 @@
+use crate::save_state::*;
+
 pub struct Color{
     pub r:u8,
     pub g:u8,
     pub b:u8
 }
 
+const RGB555_CHANNEL_MASK:u16 = 0b1_1111;
+const COLOR_CORRECTION_MAX_VALUE:u16 = 960;
+
+impl Color{
+    // The color correction mimics the CGB lcd where the channels bleed into each other and the colors are less saturated
+    pub fn from_rgb555(value:u16, color_correction:bool)->Color{
+        let r = value & RGB555_CHANNEL_MASK;
+        let g = (value >> 5) & RGB555_CHANNEL_MASK;
+        let b = (value >> 10) & RGB555_CHANNEL_MASK;
+
+        if color_correction{
+            let correct = |value:u16| (std::cmp::min(value, COLOR_CORRECTION_MAX_VALUE) >> 2) as u8;
+            return Color{
+                r:correct(r * 26 + g * 4 + b * 2),
+                g:correct(g * 24 + b * 8),
+                b:correct(r * 6 + g * 4 + b * 22)
+            };
+        }
+
+        let expand = |value:u16| ((value << 3) | (value >> 2)) as u8;
+        return Color{
+            r:expand(r),
+            g:expand(g),
+            b:expand(b)
+        };
+    }
+}
+
+impl Color{
+    // The frame buffer format (0xRRGGBB)
+    pub fn to_rgb888(&self)->u32{
+        ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+    }
+
+    pub fn from_rgb888(value:u32)->Color{
+        Color{
+            r:(value >> 16) as u8,
+            g:(value >> 8) as u8,
+            b:value as u8
+        }
+    }
+}
+
 impl Default for Color{
     fn default()->Color{
         Color{
@@ -33,3 +78,18 @@ impl PartialEq for Color{
         self.r == color.r
     }
 }
+
+impl SaveState for Color{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u8(self.r);
+        writer.write_u8(self.g);
+        writer.write_u8(self.b);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.r = reader.read_u8()?;
+        self.g = reader.read_u8()?;
+        self.b = reader.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+// The CGB bootrom colorizes DMG cartridges of Nintendo by a checksum of the title,
+// some checksums are shared by several titles and the 4th letter of the title tells them apart.
+// The tables are the ones of the bootrom, other titles get the default palettes like on the hardware.
+
+pub const TITLE_LENGTH:usize = 16;
+const NINTENDO_OLD_LICENSEE_CODE:u8 = 0x01;
+const NEW_LICENSEE_CODE_MARKER:u8 = 0x33;
+const NINTENDO_NEW_LICENSEE_CODE:[u8;2] = *b"01";
+
+// Colors are 0xRRGGBB, darkest last
+pub struct CompatibilityPalettes{
+    pub bg:[u32;4],
+    pub obj0:[u32;4],
+    pub obj1:[u32;4]
+}
+
+const TITLE_CHECKSUMS_COUNT:usize = 94;
+// The checksums from here on are shared and need the 4th letter to match too
+const FIRST_CHECKSUM_WITH_DUPLICATE:usize = 65;
+
+const TITLE_CHECKSUMS:[u8;TITLE_CHECKSUMS_COUNT] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B,
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3, 0x46,
+    0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3
+];
+
+const FOURTH_LETTERS:&[u8;TITLE_CHECKSUMS_COUNT - FIRST_CHECKSUM_WITH_DUPLICATE] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// The palettes combination of every checksum
+const COMBINATION_PER_CHECKSUM:[u8;TITLE_CHECKSUMS_COUNT] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44,
+    21, 32, 31, 20, 5, 33, 13, 14, 5, 29, 5, 18, 9, 3, 2, 26,
+    25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34,
+    5, 42, 6, 5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0,
+    39,
+    36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50, 17, 46,
+    6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29
+];
+
+// RGB555 like in the bootrom, lightest first
+const PALETTES:[[u16;4];30] = [
+    [0x7FFF, 0x32BF, 0x00D0, 0x0000],
+    [0x639F, 0x4279, 0x15B0, 0x04CB],
+    [0x7FFF, 0x6E31, 0x454A, 0x0000],
+    [0x7FFF, 0x1BEF, 0x0200, 0x0000],
+    [0x7FFF, 0x421F, 0x1CF2, 0x0000],
+    [0x7FFF, 0x5294, 0x294A, 0x0000],
+    [0x7FFF, 0x03FF, 0x012F, 0x0000],
+    [0x7FFF, 0x03EF, 0x01D6, 0x0000],
+    [0x7FFF, 0x42B5, 0x3DC8, 0x0000],
+    [0x7E74, 0x03FF, 0x0180, 0x0000],
+    [0x67FF, 0x77AC, 0x1A13, 0x2D6B],
+    [0x7ED6, 0x4BFF, 0x2175, 0x0000],
+    [0x53FF, 0x4A5F, 0x7E52, 0x0000],
+    [0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0],
+    [0x03ED, 0x7FFF, 0x255F, 0x0000],
+    [0x036A, 0x021F, 0x03FF, 0x7FFF],
+    [0x7FFF, 0x01DF, 0x0112, 0x0000],
+    [0x231F, 0x035F, 0x00F2, 0x0009],
+    [0x7FFF, 0x03EA, 0x011F, 0x0000],
+    [0x299F, 0x001A, 0x000C, 0x0000],
+    [0x7FFF, 0x027F, 0x001F, 0x0000],
+    [0x7FFF, 0x03E0, 0x0206, 0x0120],
+    [0x7FFF, 0x7EEB, 0x001F, 0x7C00],
+    [0x7FFF, 0x3FFF, 0x7E00, 0x001F],
+    [0x7FFF, 0x03FF, 0x001F, 0x0000],
+    [0x03FF, 0x001F, 0x000C, 0x0000],
+    [0x7FFF, 0x033F, 0x0193, 0x0000],
+    [0x0000, 0x4200, 0x037F, 0x7FFF],
+    [0x7FFF, 0x7E8C, 0x7C00, 0x0000],
+    [0x7FFF, 0x1BEF, 0x6180, 0x0000],
+];
+
+// The bootrom picks the palettes by their offset in the palettes table (obj0, obj1, bg),
+// a few combinations start in the middle of a palette and take the colors of the next one
+static PALETTE_COMBINATIONS:[CompatibilityPalettes;51] = [
+    combination(4, 4, 29),
+    combination(18, 18, 18),
+    combination(20, 20, 20),
+    combination(24, 24, 24),
+    combination(9, 9, 9),
+    combination(0, 0, 0),
+    combination(27, 27, 27),
+    combination(5, 5, 5),
+    combination(12, 12, 12),
+    combination(26, 26, 26),
+    combination(16, 8, 8),
+    combination(4, 28, 28),
+    combination(4, 2, 2),
+    combination(3, 4, 4),
+    combination(4, 29, 29),
+    combination(28, 4, 28),
+    combination(2, 17, 2),
+    combination(16, 16, 8),
+    combination(4, 4, 7),
+    combination(4, 4, 18),
+    combination(4, 4, 20),
+    combination(19, 19, 9),
+    colors_combination(4 * 4 - 1, 4 * 4 - 1, 11 * 4),
+    combination(17, 17, 2),
+    combination(4, 4, 2),
+    combination(4, 4, 3),
+    combination(28, 28, 0),
+    combination(3, 3, 0),
+    combination(0, 0, 1),
+    combination(18, 22, 18),
+    combination(20, 22, 20),
+    combination(24, 22, 24),
+    combination(16, 22, 8),
+    combination(17, 4, 13),
+    colors_combination(28 * 4 - 1, 0, 14 * 4),
+    colors_combination(28 * 4 - 1, 4 * 4, 15 * 4),
+    combination(19, 22, 9),
+    combination(16, 28, 10),
+    combination(4, 23, 28),
+    combination(17, 22, 2),
+    combination(4, 0, 2),
+    combination(4, 28, 3),
+    combination(28, 3, 0),
+    combination(3, 28, 4),
+    combination(21, 28, 4),
+    combination(3, 28, 0),
+    combination(25, 3, 28),
+    combination(0, 28, 8),
+    combination(4, 3, 28),
+    combination(28, 3, 6),
+    combination(4, 28, 29),
+];
+
+const DEFAULT_COMBINATION:usize = 0;
+
+const fn combination(obj0:usize, obj1:usize, bg:usize)->CompatibilityPalettes{
+    colors_combination(obj0 * 4, obj1 * 4, bg * 4)
+}
+
+// The offsets are in colors
+const fn colors_combination(obj0:usize, obj1:usize, bg:usize)->CompatibilityPalettes{
+    CompatibilityPalettes{bg:get_palette(bg), obj0:get_palette(obj0), obj1:get_palette(obj1)}
+}
+
+const fn get_palette(offset:usize)->[u32;4]{
+    let mut palette = [0;4];
+    let mut i = 0;
+    while i < 4{
+        palette[i] = rgb555_to_rgb888(PALETTES[(offset + i) / 4][(offset + i) % 4]);
+        i += 1;
+    }
+    return palette;
+}
+
+pub fn get_compatibility_palettes(title:&[u8;TITLE_LENGTH], old_licensee_code:u8, new_licensee_code:[u8;2])->&'static CompatibilityPalettes{
+    if !is_nintendo_licensee(old_licensee_code, new_licensee_code){
+        return &PALETTE_COMBINATIONS[DEFAULT_COMBINATION];
+    }
+
+    let checksum = get_title_checksum(title);
+    for i in 0..TITLE_CHECKSUMS_COUNT{
+        if TITLE_CHECKSUMS[i] == checksum && (i < FIRST_CHECKSUM_WITH_DUPLICATE || FOURTH_LETTERS[i - FIRST_CHECKSUM_WITH_DUPLICATE] == title[3]){
+            return &PALETTE_COMBINATIONS[COMBINATION_PER_CHECKSUM[i] as usize];
+        }
+    }
+
+    return &PALETTE_COMBINATIONS[DEFAULT_COMBINATION];
+}
+
+pub fn is_nintendo_licensee(old_licensee_code:u8, new_licensee_code:[u8;2])->bool{
+    old_licensee_code == NINTENDO_OLD_LICENSEE_CODE ||
+        (old_licensee_code == NEW_LICENSEE_CODE_MARKER && new_licensee_code == NINTENDO_NEW_LICENSEE_CODE)
+}
+
+pub fn get_title_checksum(title:&[u8;TITLE_LENGTH])->u8{
+    title.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+// The CGB palette ram holds RGB555 colors
+pub fn rgb888_to_rgb555(color:u32)->u16{
+    let r = ((color >> 16) & 0xFF) as u16 >> 3;
+    let g = ((color >> 8) & 0xFF) as u16 >> 3;
+    let b = (color & 0xFF) as u16 >> 3;
+    return r | (g << 5) | (b << 10);
+}
+
+// Rounds like the palettes in the pandocs, rgb888_to_rgb555 turns it back
+const fn rgb555_to_rgb888(color:u16)->u32{
+    let r = (color & 0x1F) as u32;
+    let g = ((color >> 5) & 0x1F) as u32;
+    let b = ((color >> 10) & 0x1F) as u32;
+    return (expand_color_channel(r) << 16) | (expand_color_channel(g) << 8) | expand_color_channel(b);
+}
+
+const fn expand_color_channel(channel:u32)->u32{
+    (channel * 0xFF + 15) / 0x1F
+}
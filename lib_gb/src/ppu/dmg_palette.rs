@@ -0,0 +1,132 @@
+use std::{fmt, str::FromStr};
+use super::{color::Color, colors::*};
+
+const COLORS_PER_SET:usize = 4;
+const SETS_COUNT:usize = 3;
+
+// The colors the 4 DMG shades are drawn with (lightest first), the background and each object palette have their own colors
+#[derive(Clone, Copy, PartialEq)]
+pub struct DmgPalette{
+    pub bg:[Color;COLORS_PER_SET],
+    pub obj0:[Color;COLORS_PER_SET],
+    pub obj1:[Color;COLORS_PER_SET]
+}
+
+impl DmgPalette{
+    pub const fn new(colors:[Color;COLORS_PER_SET])->DmgPalette{
+        DmgPalette{
+            bg:colors,
+            obj0:colors,
+            obj1:colors
+        }
+    }
+
+    // Parses 4 colors for every palette or 12 colors (the background, OBJ0 and OBJ1 colors),
+    // the colors are hex RGB values (optionally starting with # or 0x) separated by whitespace or commas
+    pub fn from_colors_text(text:&str)->Result<DmgPalette, String>{
+        let colors = text.split(|c:char|c.is_whitespace() || c == ',')
+            .filter(|value|!value.is_empty())
+            .map(Self::parse_color)
+            .collect::<Result<Vec<Color>, String>>()?;
+
+        let mut sets = [[Color::default();COLORS_PER_SET];SETS_COUNT];
+        match colors.len(){
+            COLORS_PER_SET=>sets.iter_mut().for_each(|set|set.copy_from_slice(&colors)),
+            len if len == COLORS_PER_SET * SETS_COUNT=>{
+                for (set, set_colors) in sets.iter_mut().zip(colors.chunks_exact(COLORS_PER_SET)){
+                    set.copy_from_slice(set_colors);
+                }
+            }
+            len=>return Err(format!("a palette has {} or {} colors, got {}", COLORS_PER_SET, COLORS_PER_SET * SETS_COUNT, len))
+        }
+
+        return Ok(DmgPalette{bg:sets[0], obj0:sets[1], obj1:sets[2]});
+    }
+
+    fn parse_color(value:&str)->Result<Color, String>{
+        let hex = value.trim_start_matches('#').trim_start_matches("0x");
+        if hex.len() != 6{
+            return Err(format!("invalid color {}", value));
+        }
+        return u32::from_str_radix(hex, 16).map(Color::from_rgb888).map_err(|_|format!("invalid color {}", value));
+    }
+}
+
+impl Default for DmgPalette{
+    fn default()->DmgPalette{
+        DmgPalettePreset::Gray.get_palette()
+    }
+}
+
+// Parses a preset name or the colors of a palette
+impl FromStr for DmgPalette{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<DmgPalettePreset>(){
+            Ok(preset)=>Ok(preset.get_palette()),
+            Err(_)=>Self::from_colors_text(s)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DmgPalettePreset{
+    Gray,
+    Green,
+    Pocket,
+    HighContrast,
+    ColorBlind
+}
+
+impl DmgPalettePreset{
+    pub const ALL:[DmgPalettePreset;5] = [DmgPalettePreset::Gray, DmgPalettePreset::Green, DmgPalettePreset::Pocket,
+        DmgPalettePreset::HighContrast, DmgPalettePreset::ColorBlind];
+
+    pub fn get_palette(&self)->DmgPalette{
+        match self{
+            DmgPalettePreset::Gray=>DmgPalette::new([WHITE, LIGHT_GRAY, DARK_GRAY, BLACK]),
+            // the green tinted lcd of the original model
+            DmgPalettePreset::Green=>DmgPalette::new(Self::rgb888_colors([0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F])),
+            // the black and white lcd of the pocket model
+            DmgPalettePreset::Pocket=>DmgPalette::new(Self::rgb888_colors([0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F])),
+            DmgPalettePreset::HighContrast=>DmgPalette::new(Self::rgb888_colors([0xFFFFFF, 0xFFFF00, 0x0000FF, 0x000000])),
+            // the Okabe-Ito colors, the objects get their own hues so they stand out from the background
+            DmgPalettePreset::ColorBlind=>DmgPalette{
+                bg:Self::rgb888_colors([0xFFFFFF, 0xE69F00, 0x0072B2, 0x000000]),
+                obj0:Self::rgb888_colors([0xFFFFFF, 0x56B4E9, 0xD55E00, 0x000000]),
+                obj1:Self::rgb888_colors([0xFFFFFF, 0xF0E442, 0xCC79A7, 0x000000])
+            }
+        }
+    }
+
+    fn rgb888_colors(values:[u32;COLORS_PER_SET])->[Color;COLORS_PER_SET]{
+        let mut colors = [Color::default();COLORS_PER_SET];
+        for (color, value) in colors.iter_mut().zip(values.iter()){
+            *color = Color::from_rgb888(*value);
+        }
+        return colors;
+    }
+}
+
+impl fmt::Display for DmgPalettePreset{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self{
+            DmgPalettePreset::Gray=>"gray",
+            DmgPalettePreset::Green=>"green",
+            DmgPalettePreset::Pocket=>"pocket",
+            DmgPalettePreset::HighContrast=>"high-contrast",
+            DmgPalettePreset::ColorBlind=>"color-blind"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DmgPalettePreset{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        DmgPalettePreset::ALL.iter().find(|preset|preset.to_string() == name).copied().ok_or(format!("unknown palette {}", s))
+    }
+}
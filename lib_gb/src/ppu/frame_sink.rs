@@ -0,0 +1,82 @@
+use super::gb_ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const OPAQUE_ALPHA:u32 = 0xFF;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat{
+    // 0x00RRGGBB, the format the ppu draws in so the frames are passed without converting them
+    Rgb888,
+    // 0xAARRGGBB, the alpha is always opaque
+    Argb8888,
+    // 0xRRGGBBAA, the alpha is always opaque
+    Rgba8888,
+    // 5 bits red, 6 bits green and 5 bits blue
+    Rgb565,
+    // The 2 bits shades of the screen after the DMG palette registers and before they are colored,
+    // these are always 160x144 (the SGB border is not included).
+    // The CGB mode colors the screen from the color palettes so the shades are meaningless there
+    Shades
+}
+
+pub enum FramePixels<'a>{
+    Rgb888(&'a [u32]),
+    Argb8888(&'a [u32]),
+    Rgba8888(&'a [u32]),
+    Rgb565(&'a [u16]),
+    Shades(&'a [u8])
+}
+
+// Receives every frame in the pixel format it asks for
+pub trait FrameSink{
+    fn get_pixel_format(&self)->PixelFormat;
+    fn push_frame(&mut self, pixels:FramePixels, width:usize, height:usize);
+}
+
+// Converts the 0xRRGGBB frames of the ppu and the sgb for the frame sink
+pub struct FrameOutput<'a>{
+    frame_sink:Box<dyn FrameSink + 'a>,
+    u32_buffer:Vec<u32>,
+    u16_buffer:Vec<u16>
+}
+
+impl<'a> FrameOutput<'a>{
+    pub fn new(frame_sink:Box<dyn FrameSink + 'a>)->Self{
+        FrameOutput{
+            frame_sink,
+            u32_buffer:Vec::new(),
+            u16_buffer:Vec::new()
+        }
+    }
+
+    pub fn push_frame(&mut self, frame:&[u32], shades:&[u8], width:usize, height:usize){
+        match self.frame_sink.get_pixel_format(){
+            PixelFormat::Rgb888=>self.frame_sink.push_frame(FramePixels::Rgb888(frame), width, height),
+            PixelFormat::Argb8888=>{
+                Self::convert(frame, &mut self.u32_buffer, |pixel|(OPAQUE_ALPHA << 24) | pixel);
+                self.frame_sink.push_frame(FramePixels::Argb8888(&self.u32_buffer), width, height);
+            }
+            PixelFormat::Rgba8888=>{
+                Self::convert(frame, &mut self.u32_buffer, |pixel|(pixel << 8) | OPAQUE_ALPHA);
+                self.frame_sink.push_frame(FramePixels::Rgba8888(&self.u32_buffer), width, height);
+            }
+            PixelFormat::Rgb565=>{
+                Self::convert(frame, &mut self.u16_buffer, rgb888_to_rgb565);
+                self.frame_sink.push_frame(FramePixels::Rgb565(&self.u16_buffer), width, height);
+            }
+            PixelFormat::Shades=>self.frame_sink.push_frame(FramePixels::Shades(shades), SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    // The buffer is kept between frames so the conversion does not allocate
+    fn convert<T, F:Fn(u32)->T>(frame:&[u32], buffer:&mut Vec<T>, convert_pixel:F){
+        buffer.clear();
+        buffer.extend(frame.iter().map(|pixel|convert_pixel(*pixel)));
+    }
+}
+
+pub fn rgb888_to_rgb565(pixel:u32)->u16{
+    let r = (pixel >> 19) & 0b1_1111;
+    let g = (pixel >> 10) & 0b11_1111;
+    let b = (pixel >> 3) & 0b1_1111;
+    return ((r << 11) | (g << 5) | b) as u16;
+}
@@ -1,16 +1,22 @@
 use crate::mmu::vram::VRam;
 use super::ppu_state::PpuState;
 use super::color::Color;
-use super::colors::*;
+use super::cgb_palette_ram::CgbPaletteRam;
 use crate::utils::vec2::Vec2;
 use super::colors::WHITE;
+use super::dmg_palette::DmgPalette;
+use super::ppu_register_updater::{handle_bg_pallet_register, handle_obp_pallet_register};
 use super::normal_sprite::NormalSprite;
 use super::extended_sprite::ExtendedSprite;
 use super::sprite::Sprite;
 use super::sprite_attribute::SpriteAttribute;
+use super::bg_tile_attribute::BgTileAttribute;
+use super::pixel_fifo::*;
+use super::ppu_renderer::PpuRenderer;
 use crate::utils::{
     bit_masks::*
 };
+use crate::save_state::*;
 use std::cmp;
 
 pub const SCREEN_HEIGHT: usize = 144;
@@ -32,14 +38,21 @@ const NORMAL_SPRITE_HIEGHT:u8 = 8;
 const SPRITE_MAX_HEIGHT:u8 = 16;
 const BG_SPRITES_PER_LINE:u16 = 32;
 const SPRITE_SIZE_IN_MEMORY:u16 = 16;
+const DOTS_PER_CYCLE:u8 = 4;
+const FETCHER_STEP_DOTS:u8 = 2;
+// the first fetch of every line is done twice
+const FIRST_FETCH_DOTS:u8 = 6;
+const OBJECT_FETCH_DOTS:u8 = 6;
+const WX_OFFSET:u8 = 7;
 
-const BLANK_SCREEN_BUFFER:[u32; SCREEN_HEIGHT * SCREEN_WIDTH] = [GbPpu::color_as_uint(&WHITE);SCREEN_HEIGHT * SCREEN_WIDTH];
 
 pub struct GbPpu {
     pub vram: VRam,
     pub sprite_attribute_table:[u8;SPRITE_ATTRIBUTE_TABLE_SIZE],
 
     pub screen_buffer: [u32; SCREEN_HEIGHT*SCREEN_WIDTH],
+    // The DMG shades (after BGP and OBP) of the screen, the SGB colors the screen by them
+    pub shade_buffer: [u8; SCREEN_HEIGHT*SCREEN_WIDTH],
     pub screen_enable: bool,
     pub window_enable: bool,
     pub sprite_extended: bool,
@@ -51,9 +64,20 @@ pub struct GbPpu {
     pub background_tile_map_address: bool,
     pub background_scroll: Vec2<u8>,
     pub window_scroll: Vec2<u8>,
+    pub wx_register: u8,
+    // the colors of the DMG shades, BGP, OBP0 and OBP1 are mapped to them
+    pub dmg_palette: DmgPalette,
     pub bg_color_mapping: [Color; 4],
     pub obj_color_mapping0: [Option<Color>;4],
     pub obj_color_mapping1: [Option<Color>;4],
+    pub bg_color_ram: CgbPaletteRam,
+    pub obj_color_ram: CgbPaletteRam,
+    pub color_correction: bool,
+    // DMG cartridges on a CGB, the DMG palettes registers index into the CGB palettes
+    pub dmg_compatibility: bool,
+    pub bgp_register: u8,
+    pub obp0_register: u8,
+    pub obp1_register: u8,
     pub current_line_drawn: u8,
     pub state:PpuState,
 
@@ -66,6 +90,11 @@ pub struct GbPpu {
     pub oam_search_interrupt_request:bool,
     pub coincidence_interrupt_request:bool,
 
+    // Set when the ppu enters hblank on a visible line, cleared by the hdma
+    pub hblank_started:bool,
+
+    pub renderer:PpuRenderer,
+    pixel_fifo:PixelFifo,
     window_active:bool,
     window_line_counter:u8,
     line_rendered:bool,
@@ -77,6 +106,7 @@ pub struct GbPpu {
 
 impl Default for GbPpu {
     fn default() -> Self {
+        let dmg_palette = DmgPalette::default();
         GbPpu {
             vram:VRam::default(),
             sprite_attribute_table: [0;SPRITE_ATTRIBUTE_TABLE_SIZE],
@@ -85,18 +115,28 @@ impl Default for GbPpu {
             background_enabled: false,
             background_scroll: Vec2::<u8> { x: 0, y: 0 },
             window_scroll: Vec2::<u8> { x: 0, y: 0 },
+            wx_register: 0,
             background_tile_map_address: false,
             gbc_mode: false,
             screen_buffer: [0; SCREEN_HEIGHT*SCREEN_WIDTH],
+            shade_buffer: [0; SCREEN_HEIGHT*SCREEN_WIDTH],
             screen_enable: false,
             sprite_enable: false,
             sprite_extended: false,
             window_enable: false,
             window_tile_background_map_data_address: false,
             window_tile_map_address: false,
-            bg_color_mapping: [WHITE, LIGHT_GRAY, DARK_GRAY, BLACK],
-            obj_color_mapping0: [None, Some(LIGHT_GRAY), Some(DARK_GRAY), Some(BLACK)],
-            obj_color_mapping1: [None, Some(LIGHT_GRAY), Some(DARK_GRAY), Some(BLACK)],
+            bg_color_mapping: dmg_palette.bg,
+            obj_color_mapping0: [None, Some(dmg_palette.obj0[1]), Some(dmg_palette.obj0[2]), Some(dmg_palette.obj0[3])],
+            obj_color_mapping1: [None, Some(dmg_palette.obj1[1]), Some(dmg_palette.obj1[2]), Some(dmg_palette.obj1[3])],
+            dmg_palette,
+            bg_color_ram: CgbPaletteRam::default(),
+            obj_color_ram: CgbPaletteRam::default(),
+            color_correction: false,
+            dmg_compatibility: false,
+            bgp_register: 0,
+            obp0_register: 0,
+            obp1_register: 0,
             current_line_drawn:0,
             state:PpuState::OamSearch,
             line_rendered:false,
@@ -110,7 +150,10 @@ impl Default for GbPpu {
             v_blank_interrupt_request:false,
             h_blank_interrupt_request:false,
             oam_search_interrupt_request:false,
-            coincidence_interrupt_request:false
+            coincidence_interrupt_request:false,
+            hblank_started:false,
+            renderer:PpuRenderer::Scanline,
+            pixel_fifo:PixelFifo::default()
         }
     }
 }
@@ -124,6 +167,36 @@ impl GbPpu {
         return &self.screen_buffer;
     }
 
+    // The palette registers are mapped again so the new colors show up right away
+    pub fn set_dmg_palette(&mut self, dmg_palette:DmgPalette){
+        self.dmg_palette = dmg_palette;
+        self.update_dmg_color_mappings();
+    }
+
+    fn update_dmg_color_mappings(&mut self){
+        handle_bg_pallet_register(self.bgp_register, &self.dmg_palette.bg, &mut self.bg_color_mapping);
+        handle_obp_pallet_register(self.obp0_register, &self.dmg_palette.obj0, &mut self.obj_color_mapping0);
+        handle_obp_pallet_register(self.obp1_register, &self.dmg_palette.obj1, &mut self.obj_color_mapping1);
+    }
+
+    pub fn set_renderer(&mut self, renderer:PpuRenderer){
+        self.renderer = renderer;
+        // a line the scanline renderer already drew is not continued by the pixel fifo
+        self.pixel_fifo.line_done = true;
+    }
+
+    // Moves the ppu to the m cycle of the frame the bootrom leaves it at
+    pub fn set_frame_cycle(&mut self, frame_cycle:u32){
+        self.current_cycle = frame_cycle % CYCLES_PER_FRAME;
+        self.current_line_drawn = (self.current_cycle / DRAWING_CYCLE_CLOCKS as u32) as u8;
+        self.state = Self::get_ppu_state(self.current_cycle, self.current_line_drawn);
+        self.line_rendered = self.state as u8 != PpuState::OamSearch as u8;
+        self.v_blank_triggered = self.current_line_drawn >= SCREEN_HEIGHT as u8;
+
+        let mut if_register = 0;
+        self.update_stat_register(&mut if_register);
+    }
+
     fn update_ly(&mut self){
         
         let line = self.current_cycle/DRAWING_CYCLE_CLOCKS as u32;
@@ -228,9 +301,14 @@ impl GbPpu {
         if !self.screen_enable && self.last_screen_state {
             self.current_line_drawn = 0;
             self.current_cycle = 0;
-            self.screen_buffer = BLANK_SCREEN_BUFFER;
+            // the lcd is blank with the lightest color
+            let blank_color = if self.gbc_mode {WHITE} else {self.dmg_palette.bg[0]};
+            self.screen_buffer = [Self::color_as_uint(&blank_color); SCREEN_HEIGHT*SCREEN_WIDTH];
+            self.shade_buffer = [0; SCREEN_HEIGHT*SCREEN_WIDTH];
             self.state = PpuState::Hblank;
             self.window_active = false;
+            self.line_rendered = false;
+            self.pixel_fifo.line_done = true;
             self.last_screen_state = self.screen_enable;
             return;
         }
@@ -240,9 +318,18 @@ impl GbPpu {
         
         self.last_screen_state = self.screen_enable;
 
+        if self.renderer == PpuRenderer::PixelFifo{
+            self.cycle_pixel_fifo(if_register, cycles_passed);
+            return;
+        }
+
         self.current_cycle += cycles_passed as u32;
         self.update_ly();
+        let last_state = self.state;
         self.state = Self::get_ppu_state(self.current_cycle, self.current_line_drawn);
+        if self.state as u8 == PpuState::Hblank as u8 && last_state as u8 != PpuState::Hblank as u8{
+            self.hblank_started = true;
+        }
         
         self.update_ly_register(if_register);
         self.update_stat_register(if_register);
@@ -251,66 +338,355 @@ impl GbPpu {
             if !self.line_rendered {
                 self.line_rendered = true;
 
-                let mut frame_buffer_line = self.get_bg_frame_buffer();
-                self.draw_window_frame_buffer(&mut frame_buffer_line);
-                self.draw_objects_frame_buffer(&mut frame_buffer_line);
+                let mut bg_line = self.get_bg_frame_buffer();
+                self.draw_window_frame_buffer(&mut bg_line);
+
+                let mut frame_buffer_line = [Color::default();SCREEN_WIDTH];
+                let mut shade_line = [0;SCREEN_WIDTH];
+                for i in 0..SCREEN_WIDTH{
+                    frame_buffer_line[i] = self.get_bg_color(bg_line[i].color_index, bg_line[i].palette_number);
+                    shade_line[i] = Self::get_shade(self.bgp_register, bg_line[i].color_index);
+                }
+                self.draw_objects_frame_buffer(&mut frame_buffer_line, &mut shade_line, &bg_line);
 
                 let line_index = self.current_line_drawn as usize * SCREEN_WIDTH;
 
                 for i in line_index..line_index+SCREEN_WIDTH{
                     self.screen_buffer[i] = Self::color_as_uint(&frame_buffer_line[(i - line_index)]);
                 }
+                self.shade_buffer[line_index..line_index+SCREEN_WIDTH].copy_from_slice(&shade_line);
             }
         }
     }
 
-    fn read_vram(&self, address:u16)->u8{
-        self.vram.read_current_bank(address - 0x8000)
+    // Runs the ppu dot by dot, pixel transfer lasts until the last pixel of the line is pushed to the lcd
+    fn cycle_pixel_fifo(&mut self, if_register:&mut u8, cycles_passed:u32){
+        for _ in 0..cycles_passed{
+            self.current_cycle += 1;
+            self.update_ly();
+
+            // the line starts once the oam search cycles passed and the following cycles draw it
+            let visible_line = self.current_line_drawn < SCREEN_HEIGHT as u8;
+            let line_clocks = self.current_cycle % DRAWING_CYCLE_CLOCKS as u32;
+            if visible_line && self.line_rendered{
+                for _ in 0..DOTS_PER_CYCLE{
+                    if self.pixel_fifo.line_done{
+                        break;
+                    }
+                    self.cycle_fifo_dot();
+                }
+            }
+            if visible_line && !self.line_rendered && line_clocks >= OAM_CLOCKS as u32{
+                self.line_rendered = true;
+                self.start_fifo_line();
+            }
+
+            let last_state = self.state;
+            self.state = if !visible_line{
+                PpuState::Vblank
+            }
+            else if !self.line_rendered{
+                PpuState::OamSearch
+            }
+            else if !self.pixel_fifo.line_done{
+                PpuState::PixelTransfer
+            }
+            else{
+                PpuState::Hblank
+            };
+            if self.state as u8 == PpuState::Hblank as u8 && last_state as u8 != PpuState::Hblank as u8{
+                self.hblank_started = true;
+            }
+
+            self.update_ly_register(if_register);
+            self.update_stat_register(if_register);
+        }
     }
 
-    fn get_bg_frame_buffer(&self)-> [Color;SCREEN_WIDTH] {
-        if !self.background_enabled{
-            //color in BGP 0
-            let color = self.get_bg_color(0);
-            return [color;SCREEN_WIDTH]
+    // Selects the (up to 10) objects of the line and resets the fetcher
+    fn start_fifo_line(&mut self){
+        let line = self.current_line_drawn;
+        let height = if self.sprite_extended {SPRITE_MAX_HEIGHT} else {NORMAL_SPRITE_HIEGHT};
+        let fifo = &mut self.pixel_fifo;
+        fifo.line_objects.clear();
+        for i in (0..OAM_SIZE as usize).step_by(4){
+            if fifo.line_objects.len() >= OBJ_PER_LINE{
+                break;
+            }
+            let y = self.sprite_attribute_table[i];
+            let top = y as i16 - SPRITE_MAX_HEIGHT as i16;
+            if (line as i16) < top || line as i16 >= top + height as i16{
+                continue;
+            }
+            fifo.line_objects.push(LineObject{
+                oam_index:(i / 4) as u8,
+                y,
+                x:self.sprite_attribute_table[i + 1],
+                tile_number:self.sprite_attribute_table[i + 2],
+                attributes:self.sprite_attribute_table[i + 3],
+                // objects right of the screen are never fetched
+                fetched:self.sprite_attribute_table[i + 1] >= SCREEN_WIDTH as u8 + SPRITE_WIDTH
+            });
         }
+        // the objects are fetched by their x position (the sort is stable so the oam order breaks the ties)
+        fifo.line_objects.sort_by_key(|object|object.x);
 
-        let current_line = self.current_line_drawn;
+        fifo.bg_fifo.clear();
+        fifo.obj_fifo.clear();
+        fifo.reset_fetcher();
+        fifo.fetcher_x = 0;
+        fifo.fetching_window = false;
+        fifo.stall_dots = FIRST_FETCH_DOTS;
+        fifo.fetching_object = false;
+        fifo.discard_pixels = self.background_scroll.x & 0b111;
+        fifo.lx = 0;
+        fifo.line_done = false;
 
-        let address = if self.background_tile_map_address {
-            0x9C00
-        } else {
-            0x9800
-        };
-        let mut line_sprites:Vec<NormalSprite> = Vec::with_capacity(BG_SPRITES_PER_LINE as usize);
-        let index = ((current_line.wrapping_add(self.background_scroll.y)) / NORMAL_SPRITE_HIEGHT) as u16;
-        if self.window_tile_background_map_data_address {
-            for i in 0..BG_SPRITES_PER_LINE {
-                let chr: u8 = self.read_vram(address + (index*BG_SPRITES_PER_LINE) + i);
-                let sprite = self.get_normal_sprite(chr, 0x8000);
-                line_sprites.push(sprite);
+        if self.window_enable && self.current_line_drawn == self.window_scroll.y{
+            self.window_active = true;
+        }
+    }
+
+    fn cycle_fifo_dot(&mut self){
+        if self.pixel_fifo.stall_dots > 0{
+            self.pixel_fifo.stall_dots -= 1;
+            if self.pixel_fifo.stall_dots == 0 && self.pixel_fifo.fetching_object{
+                self.pixel_fifo.fetching_object = false;
+                self.fetch_object();
             }
-        } 
-        else {
-            for i in 0..BG_SPRITES_PER_LINE {
-                let mut chr: u8 = self.read_vram(address + (index*BG_SPRITES_PER_LINE) + i);
-                chr = chr.wrapping_add(0x80);
-                let sprite = self.get_normal_sprite(chr, 0x8800);
-                line_sprites.push(sprite);
+            return;
+        }
+
+        if self.should_start_window(){
+            let fifo = &mut self.pixel_fifo;
+            fifo.fetching_window = true;
+            fifo.bg_fifo.clear();
+            fifo.reset_fetcher();
+            fifo.fetcher_x = 0;
+            fifo.discard_pixels = WX_OFFSET.saturating_sub(self.wx_register);
+        }
+
+        // the object fetch waits for the background fetcher to finish the tile it is fetching
+        if self.sprite_enable && self.get_pending_object().is_some(){
+            self.cycle_fetcher();
+            let fifo = &mut self.pixel_fifo;
+            if fifo.fetcher_step == FetcherStep::Push && !fifo.bg_fifo.is_empty(){
+                // this dot is the first of the fetch
+                fifo.stall_dots = OBJECT_FETCH_DOTS - 1;
+                fifo.fetching_object = true;
             }
-        }   
+            return;
+        }
 
-        let mut drawn_line:[Color; 256] = [Color::default();256];
+        self.cycle_fetcher();
+        self.push_fifo_pixel();
+    }
 
-        let sprite_line = (current_line as u16 + self.background_scroll.y as u16) % 8;
-        for i in 0..line_sprites.len(){
-            for j in 0..SPRITE_WIDTH{
-                let pixel = line_sprites[i].pixels[((sprite_line as u8 * SPRITE_WIDTH) + j) as usize];
-                drawn_line[(i * SPRITE_WIDTH as usize) + j as usize] = self.get_bg_color(pixel);
+    fn should_start_window(&self)->bool{
+        if self.pixel_fifo.fetching_window || !self.window_active || !self.window_enable || (!self.background_enabled && !self.gbc_mode){
+            return false;
+        }
+        if self.wx_register < WX_OFFSET{
+            return self.pixel_fifo.lx == 0;
+        }
+        return self.pixel_fifo.lx as u16 + WX_OFFSET as u16 == self.wx_register as u16;
+    }
+
+    // The first object of the line not fetched yet that starts at the current pixel
+    fn get_pending_object(&self)->Option<usize>{
+        let fifo = &self.pixel_fifo;
+        let index = fifo.line_objects.iter().position(|object|!object.fetched)?;
+        if fifo.line_objects[index].x <= fifo.lx + SPRITE_WIDTH{
+            return Some(index);
+        }
+
+        return None;
+    }
+
+    fn cycle_fetcher(&mut self){
+        let step = self.pixel_fifo.fetcher_step;
+        // the next tile fetch starts in the same dot as the push
+        if step == FetcherStep::Push{
+            if self.pixel_fifo.bg_fifo.is_empty(){
+                self.push_fetched_tile();
+                self.pixel_fifo.fetcher_dots = 1;
+            }
+            return;
+        }
+
+        self.pixel_fifo.fetcher_dots += 1;
+        if self.pixel_fifo.fetcher_dots < FETCHER_STEP_DOTS{
+            return;
+        }
+        self.pixel_fifo.fetcher_dots = 0;
+
+        match step{
+            FetcherStep::Tile=>{
+                let (map_address, map_x, map_y) = if self.pixel_fifo.fetching_window{
+                    let address = if self.window_tile_map_address {0x9C00} else {0x9800};
+                    (address, self.pixel_fifo.fetcher_x, self.window_line_counter)
+                }
+                else{
+                    let address = if self.background_tile_map_address {0x9C00} else {0x9800};
+                    let x = (self.background_scroll.x / SPRITE_WIDTH).wrapping_add(self.pixel_fifo.fetcher_x);
+                    (address, x, self.current_line_drawn.wrapping_add(self.background_scroll.y))
+                };
+                let tile_address = map_address + (map_y / NORMAL_SPRITE_HIEGHT) as u16 * BG_SPRITES_PER_LINE + (map_x as u16 % BG_SPRITES_PER_LINE);
+                self.pixel_fifo.tile_number = self.read_vram(0, tile_address);
+                self.pixel_fifo.tile_attributes = if self.gbc_mode {self.read_vram(1, tile_address)} else {0};
+                self.pixel_fifo.fetcher_step = FetcherStep::DataLow;
+            }
+            FetcherStep::DataLow=>{
+                self.pixel_fifo.data_low = self.read_tile_data(0);
+                self.pixel_fifo.fetcher_step = FetcherStep::DataHigh;
+            }
+            FetcherStep::DataHigh=>{
+                self.pixel_fifo.data_high = self.read_tile_data(1);
+                self.pixel_fifo.fetcher_step = FetcherStep::Push;
             }
+            FetcherStep::Push=>{}
         }
+    }
 
-        let mut screen_line:[Color;SCREEN_WIDTH] = [Color::default();SCREEN_WIDTH];
+    // Reads a byte of the current row of the fetched tile, the row is calculated with the current SCY
+    fn read_tile_data(&self, byte_offset:u16)->u8{
+        let attribute = BgTileAttribute::new(self.pixel_fifo.tile_attributes);
+        let line = if self.pixel_fifo.fetching_window {self.window_line_counter} else {self.current_line_drawn.wrapping_add(self.background_scroll.y)};
+        let mut row = (line % NORMAL_SPRITE_HIEGHT) as u16;
+        if attribute.flip_y{
+            row = (NORMAL_SPRITE_HIEGHT as u16 - 1) - row;
+        }
+        let tile = self.pixel_fifo.tile_number;
+        let tile_address = if self.window_tile_background_map_data_address{
+            0x8000 + tile as u16 * SPRITE_SIZE_IN_MEMORY
+        }
+        else{
+            0x8800 + tile.wrapping_add(0x80) as u16 * SPRITE_SIZE_IN_MEMORY
+        };
+
+        return self.read_vram(attribute.vram_bank, tile_address + row * 2 + byte_offset);
+    }
+
+    fn push_fetched_tile(&mut self){
+        let fifo = &mut self.pixel_fifo;
+        let attribute = BgTileAttribute::new(fifo.tile_attributes);
+        for i in 0..SPRITE_WIDTH{
+            let bit = if attribute.flip_x {i} else {SPRITE_WIDTH - 1 - i};
+            fifo.bg_fifo.push_back(BgPixel{
+                color_index:((fifo.data_low >> bit) & 1) | (((fifo.data_high >> bit) & 1) << 1),
+                palette_number:attribute.palette_number,
+                priority:attribute.bg_priority
+            });
+        }
+        fifo.fetcher_x = fifo.fetcher_x.wrapping_add(1);
+        fifo.reset_fetcher();
+    }
+
+    // Mixes the object into the object fifo, on DMG the object already there stays on top
+    // and on CGB the one with the lower oam index
+    fn fetch_object(&mut self){
+        let index = match self.get_pending_object(){
+            Some(index)=>index,
+            None=>return
+        };
+        let object = self.pixel_fifo.line_objects[index];
+        self.pixel_fifo.line_objects[index].fetched = true;
+
+        let attribute = SpriteAttribute::new(object.y, object.x, object.tile_number, object.attributes);
+        let height = if self.sprite_extended {SPRITE_MAX_HEIGHT} else {NORMAL_SPRITE_HIEGHT};
+        let tile_number = if self.sprite_extended {object.tile_number & !1} else {object.tile_number};
+        // the objects of the line are selected with the height at the oam scan, LCDC.2 might have changed since
+        // so only the low bits of the row are used (like the hardware does)
+        let mut row = (self.current_line_drawn as i16 - (object.y as i16 - SPRITE_MAX_HEIGHT as i16)) as u16 & (height as u16 - 1);
+        if attribute.flip_y{
+            row = (height as u16 - 1) - row;
+        }
+        let bank = if self.gbc_mode {attribute.vram_bank} else {0};
+        let address = 0x8000 + tile_number as u16 * SPRITE_SIZE_IN_MEMORY + row * 2;
+        let low = self.read_vram(bank, address);
+        let high = self.read_vram(bank, address + 1);
+
+        let fifo = &mut self.pixel_fifo;
+        while fifo.obj_fifo.len() < FIFO_SIZE{
+            fifo.obj_fifo.push_back(ObjPixel{color_index:0, attributes:0, oam_index:u8::MAX});
+        }
+        // objects partially left of the screen lose their first pixels
+        let hidden_pixels = (fifo.lx + SPRITE_WIDTH).saturating_sub(object.x);
+        for i in hidden_pixels..SPRITE_WIDTH{
+            let bit = if attribute.flip_x {i} else {SPRITE_WIDTH - 1 - i};
+            let pixel = ObjPixel{
+                color_index:((low >> bit) & 1) | (((high >> bit) & 1) << 1),
+                attributes:object.attributes,
+                oam_index:object.oam_index
+            };
+            let slot = &mut fifo.obj_fifo[(i - hidden_pixels) as usize];
+            let replace = slot.color_index == 0 || (self.gbc_mode && pixel.color_index != 0 && pixel.oam_index < slot.oam_index);
+            if replace{
+                *slot = pixel;
+            }
+        }
+    }
+
+    // Shifts a pixel out of the fifos and draws it with the current palettes
+    fn push_fifo_pixel(&mut self){
+        let bg_pixel = match self.pixel_fifo.bg_fifo.pop_front(){
+            Some(pixel)=>pixel,
+            None=>return
+        };
+        // the discarded pixels are before the objects of the line
+        if self.pixel_fifo.discard_pixels > 0{
+            self.pixel_fifo.discard_pixels -= 1;
+            return;
+        }
+        let obj_pixel = self.pixel_fifo.obj_fifo.pop_front();
+
+        let bg_pixel = if !self.background_enabled && !self.gbc_mode {BgPixel::default()} else {bg_pixel};
+        let mut color = self.get_bg_color(bg_pixel.color_index, bg_pixel.palette_number);
+        let mut shade = Self::get_shade(self.bgp_register, bg_pixel.color_index);
+        if let Some(obj_pixel) = obj_pixel{
+            if self.sprite_enable && obj_pixel.color_index != 0{
+                let attribute = SpriteAttribute::new(0, 0, 0, obj_pixel.attributes);
+                if let Some(obj_color) = self.get_obj_color(obj_pixel.color_index, &attribute){
+                    if !self.is_bg_over_obj(&bg_pixel, &attribute){
+                        color = obj_color;
+                        let obp_register = if attribute.palette_number {self.obp1_register} else {self.obp0_register};
+                        shade = Self::get_shade(obp_register, obj_pixel.color_index);
+                    }
+                }
+            }
+        }
+
+        let index = self.current_line_drawn as usize * SCREEN_WIDTH + self.pixel_fifo.lx as usize;
+        self.screen_buffer[index] = Self::color_as_uint(&color);
+        self.shade_buffer[index] = shade;
+        self.pixel_fifo.lx += 1;
+        if self.pixel_fifo.lx as usize == SCREEN_WIDTH{
+            self.pixel_fifo.line_done = true;
+            if self.pixel_fifo.fetching_window{
+                self.window_line_counter += 1;
+            }
+        }
+    }
+
+    fn read_vram(&self, bank:u8, address:u16)->u8{
+        self.vram.read_bank(bank, address - 0x8000)
+    }
+
+    fn get_bg_frame_buffer(&self)-> [BgPixel;SCREEN_WIDTH] {
+        //on CGB the background is always drawn and LCDC bit 0 only controls its priority
+        if !self.background_enabled && !self.gbc_mode{
+            //color in BGP 0
+            return [BgPixel::default();SCREEN_WIDTH];
+        }
+
+        let address = if self.background_tile_map_address {
+            0x9C00
+        } else {
+            0x9800
+        };
+        let drawn_line = self.get_map_line(address, self.current_line_drawn.wrapping_add(self.background_scroll.y));
+
+        let mut screen_line = [BgPixel::default();SCREEN_WIDTH];
         for i in 0..SCREEN_WIDTH{
             let index:usize = (i as u8).wrapping_add(self.background_scroll.x) as usize;
             screen_line[i] = drawn_line[index]
@@ -319,14 +695,52 @@ impl GbPpu {
         return screen_line;
     }
 
-    fn get_normal_sprite(&self, index:u8, data_address:u16)->NormalSprite{
+    // Draws a line of 256 pixels from a tile map, on CGB the tiles attributes are read from vram bank 1
+    fn get_map_line(&self, map_address:u16, map_line:u8)->[BgPixel;256]{
+        let index = (map_line / NORMAL_SPRITE_HIEGHT) as u16;
+        let sprite_line = map_line % NORMAL_SPRITE_HIEGHT;
+
+        let mut drawn_line = [BgPixel::default();256];
+        for i in 0..BG_SPRITES_PER_LINE {
+            let tile_address = map_address + (index*BG_SPRITES_PER_LINE) + i;
+            let mut chr: u8 = self.read_vram(0, tile_address);
+            let attribute = BgTileAttribute::new(if self.gbc_mode {self.read_vram(1, tile_address)} else {0});
+
+            let data_address = if self.window_tile_background_map_data_address {
+                0x8000
+            }
+            else{
+                chr = chr.wrapping_add(0x80);
+                0x8800
+            };
+            let mut sprite = self.get_normal_sprite(chr, data_address, attribute.vram_bank);
+            if attribute.flip_x{
+                sprite.flip_x();
+            }
+            if attribute.flip_y{
+                sprite.flip_y();
+            }
+
+            for j in 0..SPRITE_WIDTH{
+                drawn_line[(i as usize * SPRITE_WIDTH as usize) + j as usize] = BgPixel{
+                    color_index: sprite.pixels[((sprite_line * SPRITE_WIDTH) + j) as usize],
+                    palette_number: attribute.palette_number,
+                    priority: attribute.bg_priority
+                };
+            }
+        }
+
+        return drawn_line;
+    }
+
+    fn get_normal_sprite(&self, index:u8, data_address:u16, bank:u8)->NormalSprite{
         let mut sprite = NormalSprite::new();
 
         let mut line_number = 0;
         let start:u16 = index as u16 * SPRITE_SIZE_IN_MEMORY;
         let end:u16 = start + SPRITE_SIZE_IN_MEMORY;
         for j in (start .. end).step_by(2) {
-            self.get_line(&mut sprite, data_address + j, line_number);
+            self.get_line(&mut sprite, bank, data_address + j, line_number);
             line_number += 1;
         }
 
@@ -334,8 +748,8 @@ impl GbPpu {
     }
 
     
-    fn draw_window_frame_buffer(&mut self, line:&mut [Color;SCREEN_WIDTH]) {
-        if !self.window_enable || !self.background_enabled || self.current_line_drawn < self.window_scroll.y{ 
+    fn draw_window_frame_buffer(&mut self, line:&mut [BgPixel;SCREEN_WIDTH]) {
+        if !self.window_enable || (!self.background_enabled && !self.gbc_mode) || self.current_line_drawn < self.window_scroll.y{ 
             return;
         }
 
@@ -356,42 +770,16 @@ impl GbPpu {
         } else {
             0x9800
         };
-        let mut line_sprites:Vec<NormalSprite> = Vec::with_capacity(BG_SPRITES_PER_LINE as usize);
-        let index = ((self.window_line_counter) / 8) as u16;
-        if self.window_tile_background_map_data_address {
-            for i in 0..BG_SPRITES_PER_LINE {
-                let chr: u8 = self.read_vram(address + (index*BG_SPRITES_PER_LINE) + i);
-                let sprite = self.get_normal_sprite(chr, 0x8000);
-                line_sprites.push(sprite);
-            }
-        } 
-        else {
-            for i in 0..BG_SPRITES_PER_LINE {
-                let mut chr: u8 = self.read_vram(address + (index*BG_SPRITES_PER_LINE) + i);
-                chr = chr.wrapping_add(0x80);
-                let sprite = self.get_normal_sprite(chr, 0x8800);
-                line_sprites.push(sprite);
-            }
-        }   
-
-        let mut drawn_line:[Color; 256] = [Color::default();256];
-
-        let sprite_line = ( self.window_line_counter) % NORMAL_SPRITE_HIEGHT;
-        for i in 0..line_sprites.len(){
-            for j in 0..SPRITE_WIDTH{
-                let pixel = line_sprites[i].pixels[((sprite_line * SPRITE_WIDTH) + j) as usize];
-                drawn_line[(i * SPRITE_WIDTH as usize) + j as usize] = self.get_bg_color(pixel);
-            }
-        }
+        let drawn_line = self.get_map_line(address, self.window_line_counter);
 
         for i in self.window_scroll.x as usize..SCREEN_WIDTH{
-            line[(i as usize)] = drawn_line[i - self.window_scroll.x as usize];
+            line[i] = drawn_line[i - self.window_scroll.x as usize];
         }
 
         self.window_line_counter += 1;
     }
 
-    fn draw_objects_frame_buffer(&self, line:&mut [Color;SCREEN_WIDTH]){
+    fn draw_objects_frame_buffer(&self, line:&mut [Color;SCREEN_WIDTH], shade_line:&mut [u8;SCREEN_WIDTH], bg_line:&[BgPixel;SCREEN_WIDTH]){
         if !self.sprite_enable{
             return;
         }
@@ -431,10 +819,14 @@ impl GbPpu {
         //draw onto the last ones.
         obj_attributes.reverse();
         //ordering this from the less priority to the higher where the smaller x the priority higher.
-        obj_attributes.sort_by(|a, b| b.x.cmp(&a.x));
+        //on CGB the priority is only by the oam order
+        if !self.gbc_mode{
+            obj_attributes.sort_by(|a, b| b.x.cmp(&a.x));
+        }
 
         for obj_attribute in &obj_attributes{
-            let mut sprite = self.get_sprite(obj_attribute.tile_number, 0x8000, self.sprite_extended);
+            let bank = if self.gbc_mode {obj_attribute.vram_bank} else {0};
+            let mut sprite = self.get_sprite(obj_attribute.tile_number, 0x8000, self.sprite_extended, bank);
 
             if obj_attribute.flip_y {
                 sprite.flip_y();
@@ -451,23 +843,65 @@ impl GbPpu {
 
             for x in start_x..end_x{
                 let pixel = sprite.get_pixel(sprite_line * SPRITE_WIDTH + (x - start_x));
-                let color = self.get_obj_color(pixel, obj_attribute.palette_number);
+                let color = self.get_obj_color(pixel, obj_attribute);
                 
                 if let Some(c) = color{
-                    if !(obj_attribute.is_bg_priority && self.get_bg_color(0) != line[x as usize]){
-                        line[x as usize] = c
+                    if !self.is_bg_over_obj(&bg_line[x as usize], obj_attribute){
+                        line[x as usize] = c;
+                        let obp_register = if obj_attribute.palette_number {self.obp1_register} else {self.obp0_register};
+                        shade_line[x as usize] = Self::get_shade(obp_register, pixel);
                     }
                 }
             }
         }
     }
 
-    fn get_bg_color(&self, color: u8) -> Color {
+    // Background color 0 is always behind the objects, on CGB LCDC bit 0 disables the background priority
+    fn is_bg_over_obj(&self, bg_pixel:&BgPixel, obj_attribute:&SpriteAttribute)->bool{
+        if bg_pixel.color_index == 0{
+            return false;
+        }
+        if self.gbc_mode{
+            return self.background_enabled && (bg_pixel.priority || obj_attribute.is_bg_priority);
+        }
+
+        return obj_attribute.is_bg_priority;
+    }
+
+    fn get_shade(palette_register:u8, color:u8)->u8{
+        (palette_register >> (color * 2)) & 0b11
+    }
+
+    fn get_bg_color(&self, color: u8, palette_number:u8) -> Color {
+        if self.gbc_mode{
+            return Color::from_rgb555(self.bg_color_ram.get_color(palette_number, color), self.color_correction);
+        }
+        if self.dmg_compatibility{
+            let shade = Self::get_shade(self.bgp_register, color);
+            return Color::from_rgb555(self.bg_color_ram.get_color(0, shade), self.color_correction);
+        }
+
         return self.bg_color_mapping[color as usize].clone();
     }
 
-    fn get_obj_color(&self, color:u8, pallet_bit_set:bool)->Option<Color>{
-        return if pallet_bit_set{
+    fn get_obj_color(&self, color:u8, obj_attribute:&SpriteAttribute)->Option<Color>{
+        if self.gbc_mode{
+            //color 0 is transparent for objects
+            if color == 0{
+                return None;
+            }
+            return Some(Color::from_rgb555(self.obj_color_ram.get_color(obj_attribute.cgb_palette_number, color), self.color_correction));
+        }
+        if self.dmg_compatibility{
+            if color == 0{
+                return None;
+            }
+            let register = if obj_attribute.palette_number {self.obp1_register} else {self.obp0_register};
+            let shade = Self::get_shade(register, color);
+            return Some(Color::from_rgb555(self.obj_color_ram.get_color(obj_attribute.palette_number as u8, shade), self.color_correction));
+        }
+
+        return if obj_attribute.palette_number{
             self.obj_color_mapping1[color as usize].clone()
         }
         else{
@@ -475,7 +909,7 @@ impl GbPpu {
         };
     }
     
-    fn get_sprite(&self, mut index:u8, data_address:u16, extended:bool)->Box<dyn Sprite>{
+    fn get_sprite(&self, mut index:u8, data_address:u16, extended:bool, bank:u8)->Box<dyn Sprite>{
         let mut sprite:Box<dyn Sprite>;
         if extended{
             //ignore bit 0
@@ -491,7 +925,7 @@ impl GbPpu {
         let end:u16 = start + ((sprite.size() as u16) *2);
         let raw = Box::into_raw(sprite);
         for j in (start .. end).step_by(2) {
-            self.get_line( raw, data_address + j, line_number);
+            self.get_line( raw, bank, data_address + j, line_number);
             line_number += 1;
         }
         unsafe{sprite = Box::from_raw(raw);}
@@ -499,9 +933,9 @@ impl GbPpu {
         return sprite;
     }
 
-    fn get_line(&self, sprite:*mut dyn Sprite, address:u16, line_number:u8){
-        let byte = self.read_vram(address);
-        let next = self.read_vram(address + 1);
+    fn get_line(&self, sprite:*mut dyn Sprite, bank:u8, address:u16, line_number:u8){
+        let byte = self.read_vram(bank, address);
+        let next = self.read_vram(bank, address + 1);
         for k in (0..SPRITE_WIDTH).rev() {
             let mask = 1 << k;
             let mut value = (byte & mask) >> k;
@@ -511,3 +945,122 @@ impl GbPpu {
         }
     }
 }
+
+impl SaveState for GbPpu{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.vram.save_state(writer);
+        writer.write_bytes(&self.sprite_attribute_table);
+        for pixel in self.screen_buffer.iter(){
+            writer.write_u32(*pixel);
+        }
+        writer.write_bytes(&self.shade_buffer);
+        writer.write_bool(self.screen_enable);
+        writer.write_bool(self.window_enable);
+        writer.write_bool(self.sprite_extended);
+        writer.write_bool(self.background_enabled);
+        writer.write_bool(self.gbc_mode);
+        writer.write_bool(self.sprite_enable);
+        writer.write_bool(self.window_tile_map_address);
+        writer.write_bool(self.window_tile_background_map_data_address);
+        writer.write_bool(self.background_tile_map_address);
+        writer.write_u8(self.background_scroll.x);
+        writer.write_u8(self.background_scroll.y);
+        writer.write_u8(self.window_scroll.x);
+        writer.write_u8(self.window_scroll.y);
+        writer.write_u8(self.wx_register);
+        for color in self.bg_color_mapping.iter(){
+            color.save_state(writer);
+        }
+        for color in self.obj_color_mapping0.iter().chain(self.obj_color_mapping1.iter()){
+            writer.write_bool(color.is_some());
+            color.unwrap_or_default().save_state(writer);
+        }
+        self.bg_color_ram.save_state(writer);
+        self.obj_color_ram.save_state(writer);
+        writer.write_bool(self.dmg_compatibility);
+        writer.write_u8(self.bgp_register);
+        writer.write_u8(self.obp0_register);
+        writer.write_u8(self.obp1_register);
+        writer.write_u8(self.current_line_drawn);
+        writer.write_u8(self.state as u8);
+        writer.write_u8(self.stat_register);
+        writer.write_u8(self.lyc_register);
+        writer.write_bool(self.v_blank_interrupt_request);
+        writer.write_bool(self.h_blank_interrupt_request);
+        writer.write_bool(self.oam_search_interrupt_request);
+        writer.write_bool(self.coincidence_interrupt_request);
+        writer.write_bool(self.window_active);
+        writer.write_u8(self.window_line_counter);
+        writer.write_bool(self.line_rendered);
+        writer.write_u32(self.current_cycle);
+        writer.write_bool(self.last_screen_state);
+        writer.write_bool(self.v_blank_triggered);
+        writer.write_bool(self.stat_triggered);
+        writer.write_bool(self.renderer == PpuRenderer::PixelFifo);
+        self.pixel_fifo.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.vram.load_state(reader)?;
+        reader.read_bytes_into(&mut self.sprite_attribute_table, "oam")?;
+        for pixel in self.screen_buffer.iter_mut(){
+            *pixel = reader.read_u32()?;
+        }
+        reader.read_bytes_into(&mut self.shade_buffer, "shade buffer")?;
+        if self.shade_buffer.iter().any(|shade| *shade > 0b11){
+            return Err(SaveStateError::InvalidValue("shade buffer"));
+        }
+        self.screen_enable = reader.read_bool()?;
+        self.window_enable = reader.read_bool()?;
+        self.sprite_extended = reader.read_bool()?;
+        self.background_enabled = reader.read_bool()?;
+        self.gbc_mode = reader.read_bool()?;
+        self.sprite_enable = reader.read_bool()?;
+        self.window_tile_map_address = reader.read_bool()?;
+        self.window_tile_background_map_data_address = reader.read_bool()?;
+        self.background_tile_map_address = reader.read_bool()?;
+        self.background_scroll.x = reader.read_u8()?;
+        self.background_scroll.y = reader.read_u8()?;
+        self.window_scroll.x = reader.read_u8()?;
+        self.window_scroll.y = reader.read_u8()?;
+        self.wx_register = reader.read_u8()?;
+        for color in self.bg_color_mapping.iter_mut(){
+            color.load_state(reader)?;
+        }
+        for color in self.obj_color_mapping0.iter_mut().chain(self.obj_color_mapping1.iter_mut()){
+            let is_some = reader.read_bool()?;
+            let mut value = Color::default();
+            value.load_state(reader)?;
+            *color = if is_some {Some(value)} else {None};
+        }
+        self.bg_color_ram.load_state(reader)?;
+        self.obj_color_ram.load_state(reader)?;
+        self.dmg_compatibility = reader.read_bool()?;
+        self.bgp_register = reader.read_u8()?;
+        self.obp0_register = reader.read_u8()?;
+        self.obp1_register = reader.read_u8()?;
+        // the state could be saved with other dmg colors
+        self.update_dmg_color_mappings();
+        self.current_line_drawn = reader.read_u8()?;
+        self.state = PpuState::from_u8(reader.read_u8()?);
+        self.stat_register = reader.read_u8()?;
+        self.lyc_register = reader.read_u8()?;
+        self.v_blank_interrupt_request = reader.read_bool()?;
+        self.h_blank_interrupt_request = reader.read_bool()?;
+        self.oam_search_interrupt_request = reader.read_bool()?;
+        self.coincidence_interrupt_request = reader.read_bool()?;
+        self.window_active = reader.read_bool()?;
+        self.window_line_counter = reader.read_u8()?;
+        self.line_rendered = reader.read_bool()?;
+        self.current_cycle = reader.read_u32()?;
+        self.last_screen_state = reader.read_bool()?;
+        self.v_blank_triggered = reader.read_bool()?;
+        self.stat_triggered = reader.read_bool()?;
+        self.renderer = if reader.read_bool()? {PpuRenderer::PixelFifo} else {PpuRenderer::Scanline};
+        self.pixel_fifo.load_state(reader)?;
+        if self.current_line_drawn > LY_MAX_VALUE || self.current_cycle >= CYCLES_PER_FRAME{
+            return Err(SaveStateError::InvalidValue("ppu position"));
+        }
+        Ok(())
+    }
+}
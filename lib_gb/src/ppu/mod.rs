@@ -2,8 +2,16 @@ pub mod gb_ppu;
 pub mod ppu_state;
 pub mod color;
 pub mod colors;
+pub mod dmg_palette;
+pub mod frame_sink;
+pub mod png_writer;
+pub mod cgb_palette_ram;
+pub mod compatibility_palettes;
 pub mod ppu_register_updater;
+pub mod ppu_renderer;
+mod pixel_fifo;
 mod normal_sprite;
 mod sprite_attribute;
+mod bg_tile_attribute;
 mod extended_sprite;
 mod sprite;
\ No newline at end of file
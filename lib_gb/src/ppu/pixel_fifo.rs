@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+use crate::save_state::*;
+
+pub const FIFO_SIZE:usize = 8;
+const MAX_LINE_OBJECTS:usize = 10;
+
+// The background color index is needed for the objects priority
+#[derive(Clone, Copy, Default)]
+pub struct BgPixel{
+    pub color_index:u8,
+    pub palette_number:u8,
+    pub priority:bool
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ObjPixel{
+    pub color_index:u8,
+    pub attributes:u8,
+    pub oam_index:u8
+}
+
+// An object selected by the oam scan of the line
+#[derive(Clone, Copy, Default)]
+pub struct LineObject{
+    pub oam_index:u8,
+    pub y:u8,
+    pub x:u8,
+    pub tile_number:u8,
+    pub attributes:u8,
+    pub fetched:bool
+}
+
+// Every step of the background fetcher takes 2 dots, the push step waits until the background fifo is empty
+#[derive(Clone, Copy, PartialEq)]
+pub enum FetcherStep{
+    Tile = 0,
+    DataLow = 1,
+    DataHigh = 2,
+    Push = 3
+}
+
+// The state of the pixel fifo renderer during pixel transfer
+pub struct PixelFifo{
+    pub bg_fifo:VecDeque<BgPixel>,
+    pub obj_fifo:VecDeque<ObjPixel>,
+    pub fetcher_step:FetcherStep,
+    pub fetcher_dots:u8,
+    // the tile of the line the fetcher is on
+    pub fetcher_x:u8,
+    pub tile_number:u8,
+    pub tile_attributes:u8,
+    pub data_low:u8,
+    pub data_high:u8,
+    pub fetching_window:bool,
+    // the dots left of the first fetch of the line (which is thrown away) or of an object fetch
+    pub stall_dots:u8,
+    pub fetching_object:bool,
+    // pixels shifted out without being drawn (the SCX fine scroll or the window when WX < 7)
+    pub discard_pixels:u8,
+    // the next pixel of the line to draw
+    pub lx:u8,
+    pub line_objects:Vec<LineObject>,
+    pub line_done:bool
+}
+
+impl Default for PixelFifo{
+    fn default() -> Self {
+        PixelFifo{
+            bg_fifo:VecDeque::with_capacity(FIFO_SIZE * 2),
+            obj_fifo:VecDeque::with_capacity(FIFO_SIZE),
+            fetcher_step:FetcherStep::Tile,
+            fetcher_dots:0,
+            fetcher_x:0,
+            tile_number:0,
+            tile_attributes:0,
+            data_low:0,
+            data_high:0,
+            fetching_window:false,
+            stall_dots:0,
+            fetching_object:false,
+            discard_pixels:0,
+            lx:0,
+            line_objects:Vec::with_capacity(MAX_LINE_OBJECTS),
+            line_done:true
+        }
+    }
+}
+
+impl PixelFifo{
+    pub fn reset_fetcher(&mut self){
+        self.fetcher_step = FetcherStep::Tile;
+        self.fetcher_dots = 0;
+    }
+}
+
+impl SaveState for PixelFifo{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u8(self.bg_fifo.len() as u8);
+        for pixel in self.bg_fifo.iter(){
+            writer.write_u8(pixel.color_index);
+            writer.write_u8(pixel.palette_number);
+            writer.write_bool(pixel.priority);
+        }
+        writer.write_u8(self.obj_fifo.len() as u8);
+        for pixel in self.obj_fifo.iter(){
+            writer.write_u8(pixel.color_index);
+            writer.write_u8(pixel.attributes);
+            writer.write_u8(pixel.oam_index);
+        }
+        writer.write_u8(self.fetcher_step as u8);
+        writer.write_u8(self.fetcher_dots);
+        writer.write_u8(self.fetcher_x);
+        writer.write_u8(self.tile_number);
+        writer.write_u8(self.tile_attributes);
+        writer.write_u8(self.data_low);
+        writer.write_u8(self.data_high);
+        writer.write_bool(self.fetching_window);
+        writer.write_u8(self.stall_dots);
+        writer.write_bool(self.fetching_object);
+        writer.write_u8(self.discard_pixels);
+        writer.write_u8(self.lx);
+        writer.write_u8(self.line_objects.len() as u8);
+        for object in self.line_objects.iter(){
+            writer.write_u8(object.oam_index);
+            writer.write_u8(object.y);
+            writer.write_u8(object.x);
+            writer.write_u8(object.tile_number);
+            writer.write_u8(object.attributes);
+            writer.write_bool(object.fetched);
+        }
+        writer.write_bool(self.line_done);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        let bg_fifo_length = reader.read_u8()? as usize;
+        if bg_fifo_length > FIFO_SIZE * 2{
+            return Err(SaveStateError::InvalidValue("background fifo length"));
+        }
+        self.bg_fifo.clear();
+        for _ in 0..bg_fifo_length{
+            let pixel = BgPixel{color_index:reader.read_u8()?, palette_number:reader.read_u8()?, priority:reader.read_bool()?};
+            if pixel.color_index > 0b11 || pixel.palette_number > 0b111{
+                return Err(SaveStateError::InvalidValue("background fifo pixel"));
+            }
+            self.bg_fifo.push_back(pixel);
+        }
+        let obj_fifo_length = reader.read_u8()? as usize;
+        if obj_fifo_length > FIFO_SIZE{
+            return Err(SaveStateError::InvalidValue("object fifo length"));
+        }
+        self.obj_fifo.clear();
+        for _ in 0..obj_fifo_length{
+            let pixel = ObjPixel{color_index:reader.read_u8()?, attributes:reader.read_u8()?, oam_index:reader.read_u8()?};
+            if pixel.color_index > 0b11{
+                return Err(SaveStateError::InvalidValue("object fifo pixel"));
+            }
+            self.obj_fifo.push_back(pixel);
+        }
+        self.fetcher_step = match reader.read_u8()?{
+            0=>FetcherStep::Tile,
+            1=>FetcherStep::DataLow,
+            2=>FetcherStep::DataHigh,
+            3=>FetcherStep::Push,
+            _=>return Err(SaveStateError::InvalidValue("fetcher step"))
+        };
+        self.fetcher_dots = reader.read_u8()?;
+        self.fetcher_x = reader.read_u8()?;
+        self.tile_number = reader.read_u8()?;
+        self.tile_attributes = reader.read_u8()?;
+        self.data_low = reader.read_u8()?;
+        self.data_high = reader.read_u8()?;
+        self.fetching_window = reader.read_bool()?;
+        self.stall_dots = reader.read_u8()?;
+        self.fetching_object = reader.read_bool()?;
+        self.discard_pixels = reader.read_u8()?;
+        self.lx = reader.read_u8()?;
+        let objects_count = reader.read_u8()? as usize;
+        if objects_count > MAX_LINE_OBJECTS{
+            return Err(SaveStateError::InvalidValue("line objects count"));
+        }
+        self.line_objects.clear();
+        for _ in 0..objects_count{
+            self.line_objects.push(LineObject{
+                oam_index:reader.read_u8()?,
+                y:reader.read_u8()?,
+                x:reader.read_u8()?,
+                tile_number:reader.read_u8()?,
+                attributes:reader.read_u8()?,
+                fetched:reader.read_bool()?
+            });
+        }
+        self.line_done = reader.read_bool()?;
+        if self.lx as usize > super::gb_ppu::SCREEN_WIDTH{
+            return Err(SaveStateError::InvalidValue("fifo pixel position"));
+        }
+        Ok(())
+    }
+}
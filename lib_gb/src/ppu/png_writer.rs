@@ -0,0 +1,103 @@
+use std::io::{Result, Write};
+
+const PNG_SIGNATURE:[u8;8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const BIT_DEPTH:u8 = 8;
+const RGB_COLOR_TYPE:u8 = 2;
+const BYTES_PER_PIXEL:usize = 3;
+const NO_FILTER:u8 = 0;
+// deflate with a 32KB window and no preset dictionary, the check bits make the header a multiple of 31
+const ZLIB_HEADER:[u8;2] = [0x78, 0x01];
+const MAX_STORED_BLOCK_SIZE:usize = 0xFFFF;
+const ADLER_MODULO:u32 = 65521;
+const CRC_POLYNOMIAL:u32 = 0xEDB8_8320;
+
+// Writes 0xRRGGBB pixels as a png image, every pixel is scaled to a scale x scale square.
+// The image data is stored uncompressed so no compression library is needed.
+pub fn write_png<W:Write>(writer:&mut W, pixels:&[u32], width:usize, height:usize, scale:usize)->Result<()>{
+    let scaled_width = width * scale;
+    let scaled_height = height * scale;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(scaled_width as u32).to_be_bytes());
+    header.extend_from_slice(&(scaled_height as u32).to_be_bytes());
+    header.extend_from_slice(&[BIT_DEPTH, RGB_COLOR_TYPE, 0, 0, 0]);
+
+    let mut image_data = Vec::with_capacity((scaled_width * BYTES_PER_PIXEL + 1) * scaled_height);
+    for row in pixels.chunks_exact(width).take(height){
+        let line_start = image_data.len();
+        image_data.push(NO_FILTER);
+        for pixel in row{
+            for _ in 0..scale{
+                image_data.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8]);
+            }
+        }
+        // the next rows of the scaled pixels are the same line
+        for _ in 1..scale{
+            image_data.extend_from_within(line_start..line_start + scaled_width * BYTES_PER_PIXEL + 1);
+        }
+    }
+
+    writer.write_all(&PNG_SIGNATURE)?;
+    write_chunk(writer, b"IHDR", &header)?;
+    write_chunk(writer, b"IDAT", &zlib_store(&image_data))?;
+    write_chunk(writer, b"IEND", &[])?;
+
+    Ok(())
+}
+
+fn write_chunk<W:Write>(writer:&mut W, chunk_type:&[u8;4], data:&[u8])->Result<()>{
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(chunk_type)?;
+    writer.write_all(data)?;
+    let crc = crc32(&[&chunk_type[..], data]);
+    writer.write_all(&crc.to_be_bytes())
+}
+
+// A zlib stream of deflate stored (uncompressed) blocks
+fn zlib_store(data:&[u8])->Vec<u8>{
+    let blocks_count = std::cmp::max(1, (data.len() + MAX_STORED_BLOCK_SIZE - 1) / MAX_STORED_BLOCK_SIZE);
+    let mut output = Vec::with_capacity(ZLIB_HEADER.len() + data.len() + blocks_count * 5 + 4);
+    output.extend_from_slice(&ZLIB_HEADER);
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none(){
+        output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next(){
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+        output.push(is_final as u8);
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+
+    return output;
+}
+
+fn adler32(data:&[u8])->u32{
+    let mut a:u32 = 1;
+    let mut b:u32 = 0;
+    for byte in data{
+        a = (a + *byte as u32) % ADLER_MODULO;
+        b = (b + a) % ADLER_MODULO;
+    }
+    return (b << 16) | a;
+}
+
+fn crc32(parts:&[&[u8]])->u32{
+    let mut table = [0u32;256];
+    for (i, entry) in table.iter_mut().enumerate(){
+        let mut value = i as u32;
+        for _ in 0..8{
+            value = if value & 1 != 0 {CRC_POLYNOMIAL ^ (value >> 1)} else {value >> 1};
+        }
+        *entry = value;
+    }
+
+    let mut crc = 0xFFFF_FFFF;
+    for byte in parts.iter().flat_map(|part|part.iter()){
+        crc = table[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    return !crc;
+}
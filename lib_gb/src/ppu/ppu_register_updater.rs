@@ -1,5 +1,5 @@
 use crate::utils::bit_masks::*;
-use super::{ gb_ppu::GbPpu, color::*,  colors::*};
+use super::{ gb_ppu::GbPpu, color::*, ppu_state::PpuState};
 
 const WX_OFFSET:u8 = 7;
 
@@ -36,28 +36,33 @@ pub fn set_scy(ppu:&mut GbPpu, value:u8){
     ppu.background_scroll.y = value;
 }
 
-pub fn handle_bg_pallet_register(register:u8, pallet:&mut [Color;4] ){
-    pallet[0] = get_matching_color(register&0b00000011);
-    pallet[1] = get_matching_color((register&0b00001100)>>2);
-    pallet[2] = get_matching_color((register&0b00110000)>>4);
-    pallet[3] = get_matching_color((register&0b11000000)>>6);
+pub fn set_bgp(ppu:&mut GbPpu, value:u8){
+    ppu.bgp_register = value;
+    handle_bg_pallet_register(value, &ppu.dmg_palette.bg, &mut ppu.bg_color_mapping);
 }
 
-pub fn handle_obp_pallet_register(register:u8, pallet:&mut [Option<Color>;4] ){
-    pallet[0] = None;
-    pallet[1] = Some(get_matching_color((register&0b00001100)>>2));
-    pallet[2] = Some(get_matching_color((register&0b00110000)>>4));
-    pallet[3] = Some(get_matching_color((register&0b11000000)>>6));
+pub fn set_obp0(ppu:&mut GbPpu, value:u8){
+    ppu.obp0_register = value;
+    handle_obp_pallet_register(value, &ppu.dmg_palette.obj0, &mut ppu.obj_color_mapping0);
+}
+
+pub fn set_obp1(ppu:&mut GbPpu, value:u8){
+    ppu.obp1_register = value;
+    handle_obp_pallet_register(value, &ppu.dmg_palette.obj1, &mut ppu.obj_color_mapping1);
+}
+
+pub fn handle_bg_pallet_register(register:u8, colors:&[Color;4], pallet:&mut [Color;4] ){
+    pallet[0] = colors[(register&0b00000011) as usize];
+    pallet[1] = colors[((register&0b00001100)>>2) as usize];
+    pallet[2] = colors[((register&0b00110000)>>4) as usize];
+    pallet[3] = colors[((register&0b11000000)>>6) as usize];
 }
 
-fn get_matching_color(number:u8)->Color{
-    return match number{
-        0b00=>WHITE,
-        0b01=>LIGHT_GRAY,
-        0b10=>DARK_GRAY,
-        0b11=>BLACK,
-        _=>std::panic!("no macthing color for color number: {}", number)
-    };
+pub fn handle_obp_pallet_register(register:u8, colors:&[Color;4], pallet:&mut [Option<Color>;4] ){
+    pallet[0] = None;
+    pallet[1] = Some(colors[((register&0b00001100)>>2) as usize]);
+    pallet[2] = Some(colors[((register&0b00110000)>>4) as usize]);
+    pallet[3] = Some(colors[((register&0b11000000)>>6) as usize]);
 }
 
 pub fn handle_wy_register(register:u8, ppu:&mut GbPpu){
@@ -65,6 +70,7 @@ pub fn handle_wy_register(register:u8, ppu:&mut GbPpu){
 }
 
 pub fn handle_wx_register(register:u8, ppu:&mut GbPpu){
+    ppu.wx_register = register;
     if register < WX_OFFSET{
         ppu.window_scroll.x = 0;
     }
@@ -84,3 +90,34 @@ pub fn get_stat(ppu:&GbPpu)->u8{
 pub fn set_lyc(ppu:&mut GbPpu, value:u8){
     ppu.lyc_register = value;
 }
+
+// The palette data registers are inaccessible while the ppu reads the palettes (pixel transfer)
+fn is_palette_ram_blocked(ppu:&GbPpu)->bool{
+    ppu.state as u8 == PpuState::PixelTransfer as u8
+}
+
+pub fn get_bcpd(ppu:&GbPpu)->u8{
+    if is_palette_ram_blocked(ppu){
+        return 0xFF;
+    }
+
+    return ppu.bg_color_ram.read_data_register();
+}
+
+pub fn set_bcpd(ppu:&mut GbPpu, value:u8){
+    let blocked = is_palette_ram_blocked(ppu);
+    ppu.bg_color_ram.write_data_register(value, blocked);
+}
+
+pub fn get_ocpd(ppu:&GbPpu)->u8{
+    if is_palette_ram_blocked(ppu){
+        return 0xFF;
+    }
+
+    return ppu.obj_color_ram.read_data_register();
+}
+
+pub fn set_ocpd(ppu:&mut GbPpu, value:u8){
+    let blocked = is_palette_ram_blocked(ppu);
+    ppu.obj_color_ram.write_data_register(value, blocked);
+}
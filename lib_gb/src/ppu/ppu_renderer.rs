@@ -0,0 +1,7 @@
+// The scanline renderer draws a whole line at the start of pixel transfer which is fast but ignores mid line register writes,
+// the pixel fifo renderer draws a pixel every dot and pixel transfer is stretched by the SCX fine scroll, the window and the objects
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PpuRenderer{
+    Scanline,
+    PixelFifo
+}
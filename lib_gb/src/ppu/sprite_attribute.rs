@@ -7,7 +7,9 @@ pub struct SpriteAttribute{
     pub is_bg_priority:bool,
     pub flip_y:bool,
     pub flip_x:bool,
-    pub palette_number:bool
+    pub palette_number:bool,
+    pub cgb_palette_number:u8,
+    pub vram_bank:u8
 }
 
 impl SpriteAttribute{
@@ -19,7 +21,9 @@ impl SpriteAttribute{
             is_bg_priority: attributes & BIT_7_MASK != 0,
             flip_y: attributes & BIT_6_MASK != 0,
             flip_x: attributes & BIT_5_MASK != 0,
-            palette_number: attributes & BIT_4_MASK != 0
+            palette_number: attributes & BIT_4_MASK != 0,
+            cgb_palette_number: attributes & 0b111,
+            vram_bank: (attributes & BIT_3_MASK) >> 3
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+pub mod save_state_error;
+pub mod state_writer;
+pub mod state_reader;
+pub mod serializable;
+
+pub use save_state_error::SaveStateError;
+pub use state_writer::StateWriter;
+pub use state_reader::StateReader;
+pub use serializable::SaveState;
@@ -0,0 +1,25 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum SaveStateError{
+    InvalidHeader,
+    UnsupportedVersion{found:u16, supported:u16},
+    CartridgeMismatch,
+    UnexpectedEnd,
+    InvalidValue(&'static str)
+}
+
+impl fmt::Display for SaveStateError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self{
+            SaveStateError::InvalidHeader=>write!(f, "the data is not a MagenBoy save state"),
+            SaveStateError::UnsupportedVersion{found, supported}=>
+                write!(f, "save state version {} is not supported by this build (supported version is {})", found, supported),
+            SaveStateError::CartridgeMismatch=>write!(f, "the save state was created with a different cartridge"),
+            SaveStateError::UnexpectedEnd=>write!(f, "the save state is truncated"),
+            SaveStateError::InvalidValue(name)=>write!(f, "the save state contains an invalid value for: {}", name)
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError{}
@@ -0,0 +1,8 @@
+use super::{save_state_error::SaveStateError, state_reader::StateReader, state_writer::StateWriter};
+
+// Every component of the machine writes and reads its own state in a fixed order,
+// so a change in the layout of any component requires bumping SAVE_STATE_VERSION in the gameboy module.
+pub trait SaveState{
+    fn save_state(&self, writer:&mut StateWriter);
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>;
+}
@@ -0,0 +1,66 @@
+use super::save_state_error::SaveStateError;
+
+pub struct StateReader<'a>{
+    data:&'a [u8],
+    position:usize
+}
+
+impl<'a> StateReader<'a>{
+    pub fn new(data:&'a [u8])->Self{
+        StateReader{data, position:0}
+    }
+
+    pub fn read_u8(&mut self)->Result<u8, SaveStateError>{
+        let value = *self.data.get(self.position).ok_or(SaveStateError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    pub fn read_bool(&mut self)->Result<bool, SaveStateError>{
+        match self.read_u8()?{
+            0=>Ok(false),
+            1=>Ok(true),
+            _=>Err(SaveStateError::InvalidValue("bool"))
+        }
+    }
+
+    pub fn read_u16(&mut self)->Result<u16, SaveStateError>{
+        let mut bytes = [0;2];
+        bytes.copy_from_slice(self.read_slice(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn read_u32(&mut self)->Result<u32, SaveStateError>{
+        let mut bytes = [0;4];
+        bytes.copy_from_slice(self.read_slice(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self)->Result<u64, SaveStateError>{
+        let mut bytes = [0;8];
+        bytes.copy_from_slice(self.read_slice(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // Reads a buffer written with StateWriter::write_bytes into a buffer of the same size
+    pub fn read_bytes_into(&mut self, buffer:&mut [u8], name:&'static str)->Result<(), SaveStateError>{
+        let length = self.read_u32()? as usize;
+        if length != buffer.len(){
+            return Err(SaveStateError::InvalidValue(name));
+        }
+
+        buffer.copy_from_slice(self.read_slice(length)?);
+        Ok(())
+    }
+
+    pub fn is_finished(&self)->bool{
+        self.position == self.data.len()
+    }
+
+    fn read_slice(&mut self, length:usize)->Result<&'a [u8], SaveStateError>{
+        let end = self.position.checked_add(length).ok_or(SaveStateError::UnexpectedEnd)?;
+        let slice = self.data.get(self.position..end).ok_or(SaveStateError::UnexpectedEnd)?;
+        self.position = end;
+        Ok(slice)
+    }
+}
@@ -0,0 +1,37 @@
+#[derive(Default)]
+pub struct StateWriter{
+    buffer:Vec<u8>
+}
+
+// All the values are written in little endian
+impl StateWriter{
+    pub fn write_u8(&mut self, value:u8){
+        self.buffer.push(value);
+    }
+
+    pub fn write_bool(&mut self, value:bool){
+        self.buffer.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value:u16){
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value:u32){
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value:u64){
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Writes the length of the buffer before it in order to verify it when loaded
+    pub fn write_bytes(&mut self, bytes:&[u8]){
+        self.write_u32(bytes.len() as u32);
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn into_buffer(self)->Vec<u8>{
+        self.buffer
+    }
+}
@@ -0,0 +1,31 @@
+use super::serial_device::SerialDevice;
+
+const DISCONNECTED_VALUE:u8 = 0xFF;
+
+// Captures every byte sent by the gameboy, usefull for test roms (like Blargg's) that print their results to the serial port
+pub struct CaptureSerialDevice{
+    output:String
+}
+
+impl Default for CaptureSerialDevice{
+    fn default()->Self{
+        CaptureSerialDevice{output:String::new()}
+    }
+}
+
+impl CaptureSerialDevice{
+    pub fn get_output(&self)->&str{
+        &self.output
+    }
+}
+
+impl SerialDevice for CaptureSerialDevice{
+    fn start_transfer(&mut self, data:u8)->Option<u8> {
+        self.output.push(data as char);
+        Some(DISCONNECTED_VALUE)
+    }
+
+    fn poll_external_clock(&mut self, _data:u8)->Option<u8> {
+        None
+    }
+}
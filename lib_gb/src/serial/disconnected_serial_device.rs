@@ -0,0 +1,16 @@
+use super::serial_device::SerialDevice;
+
+const DISCONNECTED_VALUE:u8 = 0xFF;
+
+// Nothing is connected to the port so the input line is always high and nobody drives the external clock
+pub struct DisconnectedSerialDevice;
+
+impl SerialDevice for DisconnectedSerialDevice{
+    fn start_transfer(&mut self, _data:u8)->Option<u8> {
+        Some(DISCONNECTED_VALUE)
+    }
+
+    fn poll_external_clock(&mut self, _data:u8)->Option<u8> {
+        None
+    }
+}
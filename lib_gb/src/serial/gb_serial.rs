@@ -0,0 +1,150 @@
+use crate::{utils::bit_masks::*, save_state::*};
+use super::serial_device::SerialDevice;
+
+// The internal clock runs at 8192Hz (512 t_cycles per bit) and at 262144Hz (16 t_cycles per bit) with the CGB fast clock
+const NORMAL_CLOCK_T_CYCLES_PER_BIT:u32 = 512;
+const FAST_CLOCK_T_CYCLES_PER_BIT:u32 = 16;
+const BITS_PER_TRANSFER:u8 = 8;
+
+pub struct GbSerial<SD:SerialDevice>{
+    pub device:SD,
+    pub sb_register:u8,
+    pub transfer_enabled:bool,
+    pub internal_clock:bool,
+    pub fast_clock:bool,
+    pub cgb_mode:bool,
+
+    bits_left:u8,
+    incoming_byte:u8,
+    // the bits are shifted only once the device knows the byte of the other side
+    waiting_for_device:bool,
+    cycles_counter:u32
+}
+
+impl<SD:SerialDevice> GbSerial<SD>{
+    pub fn new(device:SD)->Self{
+        GbSerial{
+            device,
+            sb_register:0,
+            transfer_enabled:false,
+            internal_clock:false,
+            fast_clock:false,
+            cgb_mode:false,
+            bits_left:0,
+            incoming_byte:0,
+            waiting_for_device:false,
+            cycles_counter:0
+        }
+    }
+
+    pub fn start_transfer(&mut self){
+        self.cycles_counter = 0;
+        if self.internal_clock{
+            self.bits_left = BITS_PER_TRANSFER;
+            let byte = self.device.start_transfer(self.sb_register);
+            self.receive_incoming_byte(byte);
+        }
+    }
+
+    pub fn cycle(&mut self, if_register:&mut u8, m_cycles:u8){
+        // the clock is counted only during a transfer, start_transfer resets it
+        if !self.transfer_enabled{
+            return;
+        }
+        self.cycles_counter += m_cycles as u32 * 4;
+        let t_cycles_per_bit = self.get_t_cycles_per_bit();
+
+        if self.internal_clock{
+            // the device is polled at the bit rate so a slow other side does not block the emulation
+            if self.transfer_enabled && self.waiting_for_device{
+                if self.cycles_counter >= t_cycles_per_bit{
+                    self.cycles_counter = 0;
+                    let byte = self.device.poll_transfer();
+                    self.receive_incoming_byte(byte);
+                }
+                return;
+            }
+            while self.transfer_enabled && self.cycles_counter >= t_cycles_per_bit{
+                self.cycles_counter -= t_cycles_per_bit;
+                self.shift_bit(if_register);
+            }
+        }
+        // The device is polled only while a transfer is active, the other side should not be able to clock a gameboy that is not listening
+        else if self.cycles_counter >= t_cycles_per_bit{
+            self.cycles_counter %= t_cycles_per_bit;
+            if self.transfer_enabled{
+                if let Some(byte) = self.device.poll_external_clock(self.sb_register){
+                    self.sb_register = byte;
+                    self.finish_transfer(if_register);
+                }
+            }
+        }
+    }
+
+    fn receive_incoming_byte(&mut self, byte:Option<u8>){
+        self.waiting_for_device = byte.is_none();
+        if let Some(byte) = byte{
+            self.incoming_byte = byte;
+        }
+    }
+
+    fn shift_bit(&mut self, if_register:&mut u8){
+        self.bits_left -= 1;
+        let incoming_bit = (self.incoming_byte >> self.bits_left) & 1;
+        self.sb_register = (self.sb_register << 1) | incoming_bit;
+
+        if self.bits_left == 0{
+            self.finish_transfer(if_register);
+        }
+    }
+
+    fn finish_transfer(&mut self, if_register:&mut u8){
+        self.transfer_enabled = false;
+        self.cycles_counter = 0;
+        *if_register |= BIT_3_MASK;
+    }
+
+    fn get_t_cycles_per_bit(&self)->u32{
+        if self.cgb_mode && self.fast_clock{
+            return FAST_CLOCK_T_CYCLES_PER_BIT;
+        }
+
+        return NORMAL_CLOCK_T_CYCLES_PER_BIT;
+    }
+}
+
+impl<SD:SerialDevice> SaveState for GbSerial<SD>{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u8(self.sb_register);
+        writer.write_bool(self.transfer_enabled);
+        writer.write_bool(self.internal_clock);
+        writer.write_bool(self.fast_clock);
+        writer.write_bool(self.cgb_mode);
+        writer.write_u8(self.bits_left);
+        writer.write_u8(self.incoming_byte);
+        writer.write_bool(self.waiting_for_device);
+        writer.write_u32(self.cycles_counter);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.sb_register = reader.read_u8()?;
+        self.transfer_enabled = reader.read_bool()?;
+        self.internal_clock = reader.read_bool()?;
+        self.fast_clock = reader.read_bool()?;
+        self.cgb_mode = reader.read_bool()?;
+        self.bits_left = reader.read_u8()?;
+        self.incoming_byte = reader.read_u8()?;
+        self.waiting_for_device = reader.read_bool()?;
+        self.cycles_counter = reader.read_u32()?;
+        // an active internal clock transfer always has bits left to shift
+        if self.bits_left > BITS_PER_TRANSFER || (self.transfer_enabled && self.internal_clock && self.bits_left == 0){
+            return Err(SaveStateError::InvalidValue("serial bits left"));
+        }
+        // the device does not know about the transfer of the state so it is started again
+        if self.transfer_enabled && self.internal_clock && self.waiting_for_device{
+            let byte = self.device.start_transfer(self.sb_register);
+            self.receive_incoming_byte(byte);
+        }
+        Ok(())
+    }
+}
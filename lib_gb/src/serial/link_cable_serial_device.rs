@@ -0,0 +1,145 @@
+use std::{io::{ErrorKind, Read, Write}, time::Duration};
+use super::serial_device::SerialDevice;
+
+const TRANSFER_REQUEST:u8 = 0x01;
+const TRANSFER_REPLY:u8 = 0x02;
+// type, sequence number, data
+const MESSAGE_SIZE:usize = 3;
+const DISCONNECTED_VALUE:u8 = 0xFF;
+const WAIT_INTERVAL:Duration = Duration::from_micros(100);
+
+// Connects two gameboys over a byte stream using a master/slave protocol:
+// the side using the internal clock (the master) sends a request with its SB value and completes the transfer once
+// the other side (the slave) replies with its own SB value, the slave replies only while it waits for an external clock.
+// This way both sides see the same bytes no matter how far apart the emulators are, the master waits for the reply
+// as long as the stream is connected (while the emulation keeps running) and reads 0xFF only once it is disconnected.
+// Every request carries a sequence number that the reply repeats, replies to older requests are dropped.
+// The stream must be non blocking (see set_nonblocking on TcpStream and UnixStream).
+pub struct LinkCableSerialDevice<S:Read + Write>{
+    stream:S,
+    read_buffer:Vec<u8>,
+    connected:bool,
+    sequence:u8,
+    waiting_for_reply:bool,
+    reply:Option<u8>,
+    // the sequence number and the data of the last request of the other side
+    pending_request:Option<(u8, u8)>
+}
+
+impl<S:Read + Write> LinkCableSerialDevice<S>{
+    pub fn new(stream:S)->Self{
+        LinkCableSerialDevice{
+            stream,
+            read_buffer:Vec::new(),
+            connected:true,
+            sequence:0,
+            waiting_for_reply:false,
+            reply:None,
+            pending_request:None
+        }
+    }
+
+    pub fn is_connected(&self)->bool{
+        self.connected
+    }
+
+    fn send_message(&mut self, message_type:u8, sequence:u8, data:u8){
+        let message = [message_type, sequence, data];
+        let mut written = 0;
+        while self.connected && written < MESSAGE_SIZE{
+            match self.stream.write(&message[written..]){
+                Ok(0)=>self.disconnect(),
+                Ok(size)=>written += size,
+                Err(err) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::Interrupted=>std::thread::sleep(WAIT_INTERVAL),
+                Err(err)=>{
+                    log::error!("link cable write failed: {}", err);
+                    self.disconnect();
+                }
+            }
+        }
+        if self.connected{
+            let _ = self.stream.flush();
+        }
+    }
+
+    // reads whatever is available without blocking
+    fn receive_available(&mut self){
+        let mut buffer = [0;64];
+        while self.connected{
+            match self.stream.read(&mut buffer){
+                Ok(0)=>self.disconnect(),
+                Ok(size)=>self.read_buffer.extend_from_slice(&buffer[..size]),
+                Err(err) if err.kind() == ErrorKind::Interrupted=>{},
+                Err(err) if err.kind() == ErrorKind::WouldBlock=>return,
+                Err(err)=>{
+                    log::error!("link cable read failed: {}", err);
+                    self.disconnect();
+                }
+            }
+        }
+    }
+
+    fn process_messages(&mut self){
+        self.receive_available();
+        let messages_size = self.read_buffer.len() - self.read_buffer.len() % MESSAGE_SIZE;
+        let messages:Vec<u8> = self.read_buffer.drain(..messages_size).collect();
+        for message in messages.chunks_exact(MESSAGE_SIZE){
+            let (message_type, sequence, data) = (message[0], message[1], message[2]);
+            match message_type{
+                // a newer request replaces the one waiting for a reply
+                TRANSFER_REQUEST=>self.pending_request = Some((sequence, data)),
+                // replies to requests that were started again (after loading a state) are stale
+                TRANSFER_REPLY=>if self.waiting_for_reply && sequence == self.sequence{
+                    self.reply = Some(data);
+                },
+                _=>log::warn!("unknown link cable message: {:#X}", message_type)
+            }
+        }
+    }
+
+    fn disconnect(&mut self){
+        if self.connected{
+            log::warn!("link cable disconnected");
+        }
+        self.connected = false;
+    }
+}
+
+impl<S:Read + Write> SerialDevice for LinkCableSerialDevice<S>{
+    fn start_transfer(&mut self, data:u8)->Option<u8> {
+        self.reply = None;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.send_message(TRANSFER_REQUEST, self.sequence, data);
+        self.waiting_for_reply = self.connected;
+        if !self.connected{
+            return Some(DISCONNECTED_VALUE);
+        }
+
+        return None;
+    }
+
+    fn poll_transfer(&mut self)->Option<u8> {
+        if !self.waiting_for_reply{
+            return None;
+        }
+        self.process_messages();
+        if let Some(reply) = self.reply.take(){
+            self.waiting_for_reply = false;
+            return Some(reply);
+        }
+        // the slave might reply at any time so the transfer is abandoned only when it can no longer reply
+        if !self.connected{
+            self.waiting_for_reply = false;
+            return Some(DISCONNECTED_VALUE);
+        }
+
+        return None;
+    }
+
+    fn poll_external_clock(&mut self, data:u8)->Option<u8> {
+        self.process_messages();
+        let (sequence, request) = self.pending_request.take()?;
+        self.send_message(TRANSFER_REPLY, sequence, data);
+        return Some(request);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod gb_serial;
+pub mod serial_device;
+pub mod serial_register_updater;
+pub mod disconnected_serial_device;
+pub mod capture_serial_device;
+pub mod link_cable_serial_device;
+pub mod printer_serial_device;
@@ -0,0 +1,302 @@
+use crate::utils::bit_masks::BIT_7_MASK;
+use super::serial_device::SerialDevice;
+
+pub const PRINTER_WIDTH:usize = 160;
+const MAGIC_BYTES:[u8;2] = [0x88, 0x33];
+const ALIVE_VALUE:u8 = 0x81;
+const TILES_PER_ROW:usize = PRINTER_WIDTH / 8;
+const TILE_SIZE:usize = 16;
+const TILE_ROW_SIZE:usize = TILES_PER_ROW * TILE_SIZE;
+const MAX_DATA_SIZE:usize = TILE_ROW_SIZE * 2;
+// 9 data packets, the size of the gameboy screen
+const BUFFER_SIZE:usize = MAX_DATA_SIZE * 9;
+const PRINT_DATA_SIZE:usize = 4;
+// each margin unit feeds a tile row of paper
+const MARGIN_UNIT_HEIGHT:usize = 8;
+// the number of status requests the printer stays busy for after printing
+const PRINT_BUSY_STATUS_POLLS:u8 = 4;
+const PAPER_COLORS:[u32;4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+
+const INIT_COMMAND:u8 = 0x01;
+const PRINT_COMMAND:u8 = 0x02;
+const DATA_COMMAND:u8 = 0x04;
+const STATUS_COMMAND:u8 = 0x0F;
+
+const CHECKSUM_ERROR_STATUS:u8 = 1;
+const BUSY_STATUS:u8 = 1 << 1;
+const IMAGE_FULL_STATUS:u8 = 1 << 2;
+const UNPROCESSED_DATA_STATUS:u8 = 1 << 3;
+const PACKET_ERROR_STATUS:u8 = 1 << 4;
+
+// A strip of printer paper, fed until a print ends with a bottom margin
+pub struct PrintedPage{
+    pub width:usize,
+    pub height:usize,
+    pub pixels:Vec<u32>
+}
+
+pub trait PagePrinter{
+    fn print_page(&mut self, page:&PrintedPage);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PacketState{
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status
+}
+
+// The Game Boy Printer, every packet is: the magic bytes, command, compression flag, data length (LE), data and
+// checksum (LE sum of the command to the data), followed by 2 bytes where the printer replies 0x81 and its status.
+// The data is the 2bpp tiles of the image, 20 tiles per row and optionally RLE compressed.
+pub struct PrinterSerialDevice<PP:PagePrinter>{
+    page_printer:PP,
+    state:PacketState,
+    command:u8,
+    compressed:bool,
+    length:u16,
+    data:Vec<u8>,
+    checksum:u16,
+    received_checksum:u16,
+    image_buffer:Vec<u8>,
+    status:u8,
+    busy_status_polls:u8,
+    page:Vec<u8>
+}
+
+impl<PP:PagePrinter> PrinterSerialDevice<PP>{
+    pub fn new(page_printer:PP)->Self{
+        PrinterSerialDevice{
+            page_printer,
+            state:PacketState::Magic(0),
+            command:0,
+            compressed:false,
+            length:0,
+            data:Vec::new(),
+            checksum:0,
+            received_checksum:0,
+            image_buffer:Vec::with_capacity(BUFFER_SIZE),
+            status:0,
+            busy_status_polls:0,
+            page:Vec::new()
+        }
+    }
+
+    pub fn get_page_printer(&self)->&PP{
+        &self.page_printer
+    }
+
+    fn receive_byte(&mut self, data:u8)->u8{
+        let mut reply = 0;
+        self.state = match self.state{
+            PacketState::Magic(index)=>{
+                if data == MAGIC_BYTES[index]{
+                    if index + 1 == MAGIC_BYTES.len() {PacketState::Command} else {PacketState::Magic(index + 1)}
+                }
+                else if data == MAGIC_BYTES[0]{
+                    PacketState::Magic(1)
+                }
+                else{
+                    PacketState::Magic(0)
+                }
+            }
+            PacketState::Command=>{
+                self.command = data;
+                self.checksum = data as u16;
+                PacketState::Compression
+            }
+            PacketState::Compression=>{
+                self.compressed = data & 1 != 0;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                PacketState::LengthLow
+            }
+            PacketState::LengthLow=>{
+                self.length = data as u16;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                PacketState::LengthHigh
+            }
+            PacketState::LengthHigh=>{
+                self.length |= (data as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                self.data.clear();
+                if self.length == 0 {PacketState::ChecksumLow} else {PacketState::Data}
+            }
+            PacketState::Data=>{
+                self.data.push(data);
+                self.checksum = self.checksum.wrapping_add(data as u16);
+                if self.data.len() == self.length as usize {PacketState::ChecksumLow} else {PacketState::Data}
+            }
+            PacketState::ChecksumLow=>{
+                self.received_checksum = data as u16;
+                PacketState::ChecksumHigh
+            }
+            PacketState::ChecksumHigh=>{
+                self.received_checksum |= (data as u16) << 8;
+                self.handle_packet();
+                PacketState::Alive
+            }
+            PacketState::Alive=>{
+                reply = ALIVE_VALUE;
+                PacketState::Status
+            }
+            PacketState::Status=>{
+                reply = self.get_status();
+                if self.busy_status_polls > 0{
+                    self.busy_status_polls -= 1;
+                }
+                PacketState::Magic(0)
+            }
+        };
+
+        return reply;
+    }
+
+    fn get_status(&self)->u8{
+        let busy = if self.busy_status_polls > 0 {BUSY_STATUS} else {0};
+        return self.status | busy;
+    }
+
+    fn handle_packet(&mut self){
+        if self.received_checksum != self.checksum{
+            log::warn!("printer packet checksum mismatch, expected {:#X} got {:#X}", self.checksum, self.received_checksum);
+            self.status |= CHECKSUM_ERROR_STATUS;
+            return;
+        }
+        self.status &= !(CHECKSUM_ERROR_STATUS | PACKET_ERROR_STATUS);
+
+        match self.command{
+            INIT_COMMAND=>{
+                self.image_buffer.clear();
+                self.status = 0;
+            }
+            DATA_COMMAND=>self.handle_data(),
+            PRINT_COMMAND=>self.handle_print(),
+            STATUS_COMMAND=>{}
+            _=>{
+                log::warn!("unknown printer command: {:#X}", self.command);
+                self.status |= PACKET_ERROR_STATUS;
+            }
+        }
+    }
+
+    // An empty data packet ends the image
+    fn handle_data(&mut self){
+        if self.data.is_empty(){
+            self.status |= IMAGE_FULL_STATUS;
+            return;
+        }
+
+        let data = if self.compressed {Self::decompress(&self.data)} else {std::mem::take(&mut self.data)};
+        if data.len() > MAX_DATA_SIZE || self.image_buffer.len() + data.len() > BUFFER_SIZE{
+            self.status |= PACKET_ERROR_STATUS;
+            return;
+        }
+        self.image_buffer.extend_from_slice(&data);
+        self.status |= UNPROCESSED_DATA_STATUS;
+    }
+
+    // A control byte with bit 7 set repeats the next byte (control & 0x7F) + 2 times,
+    // otherwise the next (control + 1) bytes are copied
+    fn decompress(data:&[u8])->Vec<u8>{
+        let mut output = Vec::with_capacity(MAX_DATA_SIZE);
+        let mut index = 0;
+        while index < data.len(){
+            let control = data[index];
+            index += 1;
+            if control & BIT_7_MASK != 0{
+                let count = (control & !BIT_7_MASK) as usize + 2;
+                if let Some(value) = data.get(index){
+                    output.extend(std::iter::repeat(*value).take(count));
+                }
+                index += 1;
+            }
+            else{
+                let end = std::cmp::min(index + control as usize + 1, data.len());
+                output.extend_from_slice(&data[index..end]);
+                index = end;
+            }
+        }
+
+        return output;
+    }
+
+    // The data is: the number of copies (0 only feeds the paper), the margins (upper nibble before and lower nibble after),
+    // the palette (like BGP) and the exposure
+    fn handle_print(&mut self){
+        if self.data.len() != PRINT_DATA_SIZE{
+            self.status |= PACKET_ERROR_STATUS;
+            return;
+        }
+        let copies = self.data[0];
+        let margin_before = (self.data[1] >> 4) as usize;
+        let margin_after = (self.data[1] & 0xF) as usize;
+        let palette = self.data[2];
+
+        if copies != 0{
+            self.feed_paper(margin_before);
+            let image = self.render_image(palette);
+            for _ in 0..copies{
+                self.page.extend_from_slice(&image);
+            }
+        }
+        self.image_buffer.clear();
+        self.status &= !(UNPROCESSED_DATA_STATUS | IMAGE_FULL_STATUS);
+        self.busy_status_polls = PRINT_BUSY_STATUS_POLLS;
+
+        if (copies == 0 || margin_after != 0) && !self.page.is_empty(){
+            self.feed_paper(margin_after);
+            self.finish_page();
+        }
+    }
+
+    fn feed_paper(&mut self, margin:usize){
+        let blank_shades = margin * MARGIN_UNIT_HEIGHT * PRINTER_WIDTH;
+        self.page.extend(std::iter::repeat(0).take(blank_shades));
+    }
+
+    // Returns the shades of the buffered tiles
+    fn render_image(&self, palette:u8)->Vec<u8>{
+        let rows = self.image_buffer.len() / TILE_ROW_SIZE;
+        let mut shades = vec![0;rows * 8 * PRINTER_WIDTH];
+        for (tile_index, tile) in self.image_buffer.chunks_exact(TILE_SIZE).take(rows * TILES_PER_ROW).enumerate(){
+            let tile_x = tile_index % TILES_PER_ROW;
+            let tile_y = tile_index / TILES_PER_ROW;
+            for y in 0..8{
+                for x in 0..8{
+                    let color = ((tile[y * 2] >> (7 - x)) & 1) | (((tile[y * 2 + 1] >> (7 - x)) & 1) << 1);
+                    shades[(tile_y * 8 + y) * PRINTER_WIDTH + tile_x * 8 + x] = (palette >> (color * 2)) & 0b11;
+                }
+            }
+        }
+
+        return shades;
+    }
+
+    fn finish_page(&mut self){
+        let page = PrintedPage{
+            width:PRINTER_WIDTH,
+            height:self.page.len() / PRINTER_WIDTH,
+            pixels:self.page.iter().map(|shade|PAPER_COLORS[*shade as usize]).collect()
+        };
+        self.page_printer.print_page(&page);
+        self.page.clear();
+    }
+}
+
+impl<PP:PagePrinter> SerialDevice for PrinterSerialDevice<PP>{
+    fn start_transfer(&mut self, data:u8)->Option<u8> {
+        Some(self.receive_byte(data))
+    }
+
+    // The printer never clocks the transfers
+    fn poll_external_clock(&mut self, _data:u8)->Option<u8> {
+        None
+    }
+}
@@ -0,0 +1,28 @@
+pub trait SerialDevice{
+    // Called when this gameboy starts a transfer using its internal clock, returns the byte the other side shifts in during the transfer
+    // or None when it is not known yet, the transfer then waits for poll_transfer
+    fn start_transfer(&mut self, data:u8)->Option<u8>;
+
+    // Polled while an internal clock transfer waits for the byte of the other side, must not block
+    fn poll_transfer(&mut self)->Option<u8>{
+        None
+    }
+
+    // Polled periodically while this gameboy waits for an external clock transfer with `data` as the current SB value,
+    // returns the byte shifted in once the other side has clocked a full transfer
+    fn poll_external_clock(&mut self, data:u8)->Option<u8>;
+}
+
+impl<SD:SerialDevice + ?Sized> SerialDevice for Box<SD>{
+    fn start_transfer(&mut self, data:u8)->Option<u8> {
+        (**self).start_transfer(data)
+    }
+
+    fn poll_transfer(&mut self)->Option<u8> {
+        (**self).poll_transfer()
+    }
+
+    fn poll_external_clock(&mut self, data:u8)->Option<u8> {
+        (**self).poll_external_clock(data)
+    }
+}
\ No newline at end of file
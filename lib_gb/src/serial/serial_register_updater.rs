@@ -0,0 +1,36 @@
+use crate::utils::bit_masks::*;
+use super::{gb_serial::GbSerial, serial_device::SerialDevice};
+
+pub fn get_sb<SD:SerialDevice>(serial:&GbSerial<SD>)->u8{
+    serial.sb_register
+}
+
+pub fn set_sb<SD:SerialDevice>(serial:&mut GbSerial<SD>, value:u8){
+    serial.sb_register = value;
+}
+
+// Unused bits are read as 1, the fast clock bit exists only on CGB
+pub fn get_sc<SD:SerialDevice>(serial:&GbSerial<SD>)->u8{
+    let mut value = 0b0111_1110;
+    if serial.transfer_enabled{
+        value |= BIT_7_MASK;
+    }
+    if serial.internal_clock{
+        value |= BIT_0_MASK;
+    }
+    if serial.cgb_mode && !serial.fast_clock{
+        value &= !BIT_1_MASK;
+    }
+
+    return value;
+}
+
+pub fn set_sc<SD:SerialDevice>(serial:&mut GbSerial<SD>, value:u8){
+    serial.internal_clock = value & BIT_0_MASK != 0;
+    serial.fast_clock = value & BIT_1_MASK != 0;
+    serial.transfer_enabled = value & BIT_7_MASK != 0;
+
+    if serial.transfer_enabled{
+        serial.start_transfer();
+    }
+}
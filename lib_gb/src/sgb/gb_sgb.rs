@@ -0,0 +1,476 @@
+use crate::{ppu::{color::Color, gb_ppu::{SCREEN_HEIGHT, SCREEN_WIDTH}}, save_state::*, utils::bit_masks::*};
+use super::{packet_receiver::*, sgb_border::SgbBorder};
+
+pub const SGB_SCREEN_WIDTH:usize = 256;
+pub const SGB_SCREEN_HEIGHT:usize = 224;
+pub const VRAM_TRANSFER_SIZE:usize = 0x1000;
+const SCREEN_X_OFFSET:usize = (SGB_SCREEN_WIDTH - SCREEN_WIDTH) / 2;
+const SCREEN_Y_OFFSET:usize = (SGB_SCREEN_HEIGHT - SCREEN_HEIGHT) / 2;
+const ATTRIBUTE_MAP_WIDTH:usize = SCREEN_WIDTH / 8;
+const ATTRIBUTE_MAP_HEIGHT:usize = SCREEN_HEIGHT / 8;
+const ATTRIBUTE_MAP_SIZE:usize = ATTRIBUTE_MAP_WIDTH * ATTRIBUTE_MAP_HEIGHT;
+const ATTRIBUTE_FILE_SIZE:usize = ATTRIBUTE_MAP_SIZE / 4;
+const ATTRIBUTE_FILES_COUNT:usize = 45;
+const SYSTEM_PALETTES_COUNT:usize = 512;
+const RGB555_MASK:u16 = 0x7FFF;
+const BLACK:u32 = 0;
+
+// The palettes the SGB starts with (1-A)
+const DEFAULT_PALETTE:[u16;4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+const PAL01:u8 = 0x00;
+const PAL23:u8 = 0x01;
+const PAL03:u8 = 0x02;
+const PAL12:u8 = 0x03;
+const ATTR_BLK:u8 = 0x04;
+const ATTR_LIN:u8 = 0x05;
+const ATTR_DIV:u8 = 0x06;
+const ATTR_CHR:u8 = 0x07;
+const PAL_SET:u8 = 0x0A;
+const PAL_TRN:u8 = 0x0B;
+const MLT_REQ:u8 = 0x11;
+const CHR_TRN:u8 = 0x13;
+const PCT_TRN:u8 = 0x14;
+const ATTR_TRN:u8 = 0x15;
+const ATTR_SET:u8 = 0x16;
+const MASK_EN:u8 = 0x17;
+
+#[derive(Clone, Copy)]
+enum VramTransfer{
+    SystemPalettes,
+    BorderTiles{upper_half:bool},
+    BorderMap,
+    AttributeFiles
+}
+
+#[derive(Clone, Copy)]
+enum ScreenMask{
+    Cancel,
+    // Keeps showing the last frame
+    Freeze,
+    Black,
+    Color0
+}
+
+// The Super Game Boy, colors the screen with 4 palettes chosen per tile and draws it inside a 256x224 border.
+// The game sends it commands through JOYP and larger data (VRAM transfers) by displaying it on the screen.
+pub struct GbSgb{
+    receiver:PacketReceiver,
+    last_select:u8,
+    palettes:[[u16;4];4],
+    system_palettes:[u8;VRAM_TRANSFER_SIZE],
+    attribute_map:[u8;ATTRIBUTE_MAP_SIZE],
+    attribute_files:[u8;ATTRIBUTE_FILES_COUNT * ATTRIBUTE_FILE_SIZE],
+    border:SgbBorder,
+    mask:ScreenMask,
+    frozen_shades:[u8;SCREEN_HEIGHT * SCREEN_WIDTH],
+    screen_frozen:bool,
+    players_count:u8,
+    current_player:u8,
+    // The transfer reads the frame after the one the command arrived in
+    pending_transfer:Option<VramTransfer>,
+    transfer_frame_delay:u8,
+    frame_buffer:Vec<u32>
+}
+
+impl Default for GbSgb{
+    fn default() -> Self {
+        GbSgb{
+            receiver:PacketReceiver::default(),
+            last_select:IDLE,
+            palettes:[DEFAULT_PALETTE;4],
+            system_palettes:[0;VRAM_TRANSFER_SIZE],
+            attribute_map:[0;ATTRIBUTE_MAP_SIZE],
+            attribute_files:[0;ATTRIBUTE_FILES_COUNT * ATTRIBUTE_FILE_SIZE],
+            border:SgbBorder::default(),
+            mask:ScreenMask::Cancel,
+            frozen_shades:[0;SCREEN_HEIGHT * SCREEN_WIDTH],
+            screen_frozen:false,
+            players_count:1,
+            current_player:0,
+            pending_transfer:None,
+            transfer_frame_delay:0,
+            frame_buffer:vec![BLACK;SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT]
+        }
+    }
+}
+
+impl GbSgb{
+    // Called with the P14 and P15 bits of JOYP, the next controller is selected when P15 goes high outside of a packet
+    pub fn update_joypad_select(&mut self, select:u8){
+        if select == self.last_select{
+            return;
+        }
+
+        let p15_rising = self.last_select & BIT_5_MASK == 0 && select & BIT_5_MASK != 0;
+        let receiving = self.receiver.is_receiving();
+        if let Some(command) = self.receiver.receive(self.last_select, select){
+            self.handle_command(&command);
+        }
+        else if p15_rising && !receiving && select != RESET_PULSE{
+            self.current_player = (self.current_player + 1) % self.players_count;
+        }
+        self.last_select = select;
+    }
+
+    pub fn get_current_player(&self)->u8{
+        self.current_player
+    }
+
+    // Called at the end of every frame with the DMG shades of the screen
+    pub fn cycle_frame(&mut self, shades:&[u8;SCREEN_HEIGHT * SCREEN_WIDTH]){
+        if let Some(transfer) = self.pending_transfer{
+            if self.transfer_frame_delay == 0{
+                self.handle_vram_transfer(transfer, shades);
+                self.pending_transfer = None;
+            }
+            else{
+                self.transfer_frame_delay -= 1;
+            }
+        }
+
+        match self.mask{
+            ScreenMask::Freeze=> if !self.screen_frozen{
+                self.frozen_shades.copy_from_slice(shades);
+                self.screen_frozen = true;
+            },
+            _=>self.screen_frozen = false
+        }
+
+        self.render(shades);
+    }
+
+    pub fn get_frame_buffer(&self)->&[u32]{
+        &self.frame_buffer
+    }
+
+    fn render(&mut self, shades:&[u8;SCREEN_HEIGHT * SCREEN_WIDTH]){
+        let backdrop = Self::to_rgb888(self.palettes[0][0]);
+        for pixel in self.frame_buffer.iter_mut(){
+            *pixel = backdrop;
+        }
+
+        let shades = if self.screen_frozen {&self.frozen_shades} else {shades};
+        for y in 0..SCREEN_HEIGHT{
+            for x in 0..SCREEN_WIDTH{
+                let color = match self.mask{
+                    ScreenMask::Black=>BLACK,
+                    ScreenMask::Color0=>backdrop,
+                    _=>{
+                        let palette = self.attribute_map[(y / 8) * ATTRIBUTE_MAP_WIDTH + x / 8] as usize;
+                        Self::to_rgb888(self.palettes[palette][shades[y * SCREEN_WIDTH + x] as usize])
+                    }
+                };
+                self.frame_buffer[(y + SCREEN_Y_OFFSET) * SGB_SCREEN_WIDTH + x + SCREEN_X_OFFSET] = color;
+            }
+        }
+
+        self.border.draw(&mut self.frame_buffer);
+    }
+
+    fn handle_command(&mut self, data:&[u8]){
+        let command = data[0] >> 3;
+        log::debug!("sgb command: {:#X}", command);
+        match command{
+            PAL01=>self.set_palettes(data, 0, 1),
+            PAL23=>self.set_palettes(data, 2, 3),
+            PAL03=>self.set_palettes(data, 0, 3),
+            PAL12=>self.set_palettes(data, 1, 2),
+            ATTR_BLK=>self.set_attribute_blocks(data),
+            ATTR_LIN=>self.set_attribute_lines(data),
+            ATTR_DIV=>self.set_attribute_division(data),
+            ATTR_CHR=>self.set_attribute_characters(data),
+            PAL_SET=>self.set_system_palettes(data),
+            ATTR_SET=>self.set_attribute_file(data[1]),
+            MLT_REQ=>{
+                self.players_count = match data[1] & 0b11{
+                    1=>2,
+                    3=>4,
+                    _=>1
+                };
+                self.current_player = 0;
+            }
+            MASK_EN=>self.mask = match data[1] & 0b11{
+                0=>ScreenMask::Cancel,
+                1=>ScreenMask::Freeze,
+                2=>ScreenMask::Black,
+                _=>ScreenMask::Color0
+            },
+            PAL_TRN=>self.start_vram_transfer(VramTransfer::SystemPalettes),
+            CHR_TRN=>self.start_vram_transfer(VramTransfer::BorderTiles{upper_half:data[1] & BIT_0_MASK != 0}),
+            PCT_TRN=>self.start_vram_transfer(VramTransfer::BorderMap),
+            ATTR_TRN=>self.start_vram_transfer(VramTransfer::AttributeFiles),
+            _=>log::warn!("unsupported sgb command: {:#X}", command)
+        }
+    }
+
+    // Color 0 is shared by all the palettes
+    fn set_palettes(&mut self, data:&[u8], first:usize, second:usize){
+        let color = |index:usize| u16::from_le_bytes([data[1 + index * 2], data[2 + index * 2]]) & RGB555_MASK;
+        for palette in self.palettes.iter_mut(){
+            palette[0] = color(0);
+        }
+        for i in 1..4{
+            self.palettes[first][i] = color(i);
+            self.palettes[second][i] = color(i + 3);
+        }
+    }
+
+    // Every block colors the tiles inside, on and outside of a rectangle
+    fn set_attribute_blocks(&mut self, data:&[u8]){
+        for block in data[2..].chunks_exact(6).take(data[1] as usize){
+            let control = block[0] & 0b111;
+            let inside_palette = block[1] & 0b11;
+            let outside_palette = (block[1] >> 4) & 0b11;
+            // coloring only the inside or only the outside colors the line with the same palette
+            let line_palette = match control{
+                0b001=>Some(inside_palette),
+                0b100=>Some(outside_palette),
+                _=>if control & BIT_1_MASK != 0 {Some((block[1] >> 2) & 0b11)} else {None}
+            };
+            let (x1, y1, x2, y2) = (block[2] & 0x1F, block[3] & 0x1F, block[4] & 0x1F, block[5] & 0x1F);
+
+            for y in 0..ATTRIBUTE_MAP_HEIGHT as u8{
+                for x in 0..ATTRIBUTE_MAP_WIDTH as u8{
+                    let inside = x > x1 && x < x2 && y > y1 && y < y2;
+                    let outside = x < x1 || x > x2 || y < y1 || y > y2;
+                    let palette = if inside{
+                        if control & BIT_0_MASK != 0 {Some(inside_palette)} else {None}
+                    }
+                    else if outside{
+                        if control & BIT_2_MASK != 0 {Some(outside_palette)} else {None}
+                    }
+                    else{
+                        line_palette
+                    };
+                    if let Some(palette) = palette{
+                        self.attribute_map[y as usize * ATTRIBUTE_MAP_WIDTH + x as usize] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    // Every byte colors a line of tiles (bit 7 set for a horizontal line)
+    fn set_attribute_lines(&mut self, data:&[u8]){
+        for line in data[2..].iter().take(data[1] as usize){
+            let number = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0b11;
+            if line & BIT_7_MASK != 0{
+                if number < ATTRIBUTE_MAP_HEIGHT{
+                    for x in 0..ATTRIBUTE_MAP_WIDTH{
+                        self.attribute_map[number * ATTRIBUTE_MAP_WIDTH + x] = palette;
+                    }
+                }
+            }
+            else if number < ATTRIBUTE_MAP_WIDTH{
+                for y in 0..ATTRIBUTE_MAP_HEIGHT{
+                    self.attribute_map[y * ATTRIBUTE_MAP_WIDTH + number] = palette;
+                }
+            }
+        }
+    }
+
+    // Divides the screen by a line (bit 6 set for a horizontal line)
+    fn set_attribute_division(&mut self, data:&[u8]){
+        let after_palette = data[1] & 0b11;
+        let before_palette = (data[1] >> 2) & 0b11;
+        let line_palette = (data[1] >> 4) & 0b11;
+        let horizontal = data[1] & BIT_6_MASK != 0;
+        let line = data[2] as usize;
+
+        for y in 0..ATTRIBUTE_MAP_HEIGHT{
+            for x in 0..ATTRIBUTE_MAP_WIDTH{
+                let position = if horizontal {y} else {x};
+                self.attribute_map[y * ATTRIBUTE_MAP_WIDTH + x] = match position.cmp(&line){
+                    std::cmp::Ordering::Less=>before_palette,
+                    std::cmp::Ordering::Equal=>line_palette,
+                    std::cmp::Ordering::Greater=>after_palette
+                };
+            }
+        }
+    }
+
+    // Colors tiles one by one (4 per byte) from a starting tile, left to right or top to bottom
+    fn set_attribute_characters(&mut self, data:&[u8]){
+        let mut x = data[1] as usize;
+        let mut y = data[2] as usize;
+        let count = std::cmp::min(u16::from_le_bytes([data[3], data[4]]) as usize, ATTRIBUTE_MAP_SIZE);
+        let vertical = data[5] & BIT_0_MASK != 0;
+
+        for i in 0..count{
+            let byte = match data.get(6 + i / 4){
+                Some(byte)=>*byte,
+                None=>break
+            };
+            if x < ATTRIBUTE_MAP_WIDTH && y < ATTRIBUTE_MAP_HEIGHT{
+                self.attribute_map[y * ATTRIBUTE_MAP_WIDTH + x] = (byte >> (6 - (i % 4) * 2)) & 0b11;
+            }
+
+            if vertical{
+                y += 1;
+                if y >= ATTRIBUTE_MAP_HEIGHT{
+                    y = 0;
+                    x += 1;
+                }
+            }
+            else{
+                x += 1;
+                if x >= ATTRIBUTE_MAP_WIDTH{
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    // Selects 4 of the palettes from PAL_TRN and optionally an attribute file
+    fn set_system_palettes(&mut self, data:&[u8]){
+        for i in 0..4{
+            let number = (u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) as usize) % SYSTEM_PALETTES_COUNT;
+            for color in 0..4{
+                let address = (number * 4 + color) * 2;
+                self.palettes[i][color] = u16::from_le_bytes([self.system_palettes[address], self.system_palettes[address + 1]]) & RGB555_MASK;
+            }
+        }
+        let color0 = self.palettes[0][0];
+        for palette in self.palettes.iter_mut(){
+            palette[0] = color0;
+        }
+
+        if data[9] & BIT_7_MASK != 0{
+            self.set_attribute_file(data[9]);
+        }
+        else if data[9] & BIT_6_MASK != 0{
+            self.mask = ScreenMask::Cancel;
+        }
+    }
+
+    // Bits 0-5 are the file number and bit 6 cancels the mask
+    fn set_attribute_file(&mut self, value:u8){
+        let file_number = (value & 0x3F) as usize;
+        if file_number < ATTRIBUTE_FILES_COUNT{
+            let file = &self.attribute_files[file_number * ATTRIBUTE_FILE_SIZE..][..ATTRIBUTE_FILE_SIZE];
+            for i in 0..ATTRIBUTE_MAP_SIZE{
+                self.attribute_map[i] = (file[i / 4] >> (6 - (i % 4) * 2)) & 0b11;
+            }
+        }
+        if value & BIT_6_MASK != 0{
+            self.mask = ScreenMask::Cancel;
+        }
+    }
+
+    fn start_vram_transfer(&mut self, transfer:VramTransfer){
+        self.pending_transfer = Some(transfer);
+        self.transfer_frame_delay = 1;
+    }
+
+    fn handle_vram_transfer(&mut self, transfer:VramTransfer, shades:&[u8;SCREEN_HEIGHT * SCREEN_WIDTH]){
+        let data = Self::read_vram_transfer(shades);
+        match transfer{
+            VramTransfer::SystemPalettes=>self.system_palettes = data,
+            VramTransfer::BorderTiles{upper_half}=>self.border.set_tiles(upper_half, &data),
+            VramTransfer::BorderMap=>self.border.set_map(&data),
+            VramTransfer::AttributeFiles=>{
+                let size = self.attribute_files.len();
+                self.attribute_files.copy_from_slice(&data[..size]);
+            }
+        }
+    }
+
+    // The SGB reads the screen back as 2bpp tiles, the first 256 tiles from the top left and left to right
+    fn read_vram_transfer(shades:&[u8;SCREEN_HEIGHT * SCREEN_WIDTH])->[u8;VRAM_TRANSFER_SIZE]{
+        let mut data = [0;VRAM_TRANSFER_SIZE];
+        for tile in 0..VRAM_TRANSFER_SIZE / 16{
+            let tile_x = tile % ATTRIBUTE_MAP_WIDTH;
+            let tile_y = tile / ATTRIBUTE_MAP_WIDTH;
+            for row in 0..8{
+                let line_start = (tile_y * 8 + row) * SCREEN_WIDTH + tile_x * 8;
+                for x in 0..8{
+                    let shade = shades[line_start + x];
+                    data[tile * 16 + row * 2] |= (shade & 1) << (7 - x);
+                    data[tile * 16 + row * 2 + 1] |= (shade >> 1) << (7 - x);
+                }
+            }
+        }
+
+        return data;
+    }
+
+    fn to_rgb888(color:u16)->u32{
+        Color::from_rgb555(color, false).to_rgb888()
+    }
+}
+
+impl SaveState for GbSgb{
+    fn save_state(&self, writer:&mut StateWriter){
+        self.receiver.save_state(writer);
+        writer.write_u8(self.last_select);
+        for palette in self.palettes.iter(){
+            for color in palette.iter(){
+                writer.write_u16(*color);
+            }
+        }
+        writer.write_bytes(&self.system_palettes);
+        writer.write_bytes(&self.attribute_map);
+        writer.write_bytes(&self.attribute_files);
+        self.border.save_state(writer);
+        writer.write_u8(self.mask as u8);
+        writer.write_bytes(&self.frozen_shades);
+        writer.write_bool(self.screen_frozen);
+        writer.write_u8(self.players_count);
+        writer.write_u8(self.current_player);
+        writer.write_u8(match self.pending_transfer{
+            None=>0,
+            Some(VramTransfer::SystemPalettes)=>1,
+            Some(VramTransfer::BorderTiles{upper_half:false})=>2,
+            Some(VramTransfer::BorderTiles{upper_half:true})=>3,
+            Some(VramTransfer::BorderMap)=>4,
+            Some(VramTransfer::AttributeFiles)=>5
+        });
+        writer.write_u8(self.transfer_frame_delay);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.receiver.load_state(reader)?;
+        self.last_select = reader.read_u8()? & IDLE;
+        for palette in self.palettes.iter_mut(){
+            for color in palette.iter_mut(){
+                *color = reader.read_u16()? & RGB555_MASK;
+            }
+        }
+        reader.read_bytes_into(&mut self.system_palettes, "sgb system palettes")?;
+        reader.read_bytes_into(&mut self.attribute_map, "sgb attribute map")?;
+        reader.read_bytes_into(&mut self.attribute_files, "sgb attribute files")?;
+        self.border.load_state(reader)?;
+        self.mask = match reader.read_u8()?{
+            0=>ScreenMask::Cancel,
+            1=>ScreenMask::Freeze,
+            2=>ScreenMask::Black,
+            3=>ScreenMask::Color0,
+            _=>return Err(SaveStateError::InvalidValue("sgb mask"))
+        };
+        reader.read_bytes_into(&mut self.frozen_shades, "sgb frozen screen")?;
+        self.screen_frozen = reader.read_bool()?;
+        self.players_count = reader.read_u8()?;
+        self.current_player = reader.read_u8()?;
+        self.pending_transfer = match reader.read_u8()?{
+            0=>None,
+            1=>Some(VramTransfer::SystemPalettes),
+            2=>Some(VramTransfer::BorderTiles{upper_half:false}),
+            3=>Some(VramTransfer::BorderTiles{upper_half:true}),
+            4=>Some(VramTransfer::BorderMap),
+            5=>Some(VramTransfer::AttributeFiles),
+            _=>return Err(SaveStateError::InvalidValue("sgb vram transfer"))
+        };
+        self.transfer_frame_delay = reader.read_u8()?;
+
+        if self.attribute_map.iter().chain(self.frozen_shades.iter()).any(|value| *value > 0b11){
+            return Err(SaveStateError::InvalidValue("sgb screen attributes"));
+        }
+        if ![1, 2, 4].contains(&self.players_count) || self.current_player >= self.players_count{
+            return Err(SaveStateError::InvalidValue("sgb players"));
+        }
+        Ok(())
+    }
+}
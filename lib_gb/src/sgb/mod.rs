@@ -0,0 +1,3 @@
+pub mod gb_sgb;
+pub mod sgb_border;
+mod packet_receiver;
\ No newline at end of file
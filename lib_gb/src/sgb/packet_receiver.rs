@@ -0,0 +1,94 @@
+use crate::save_state::*;
+
+pub const PACKET_SIZE:usize = 16;
+const MAX_PACKETS:usize = 7;
+const PACKET_BITS:u8 = (PACKET_SIZE * 8) as u8;
+const PACKETS_COUNT_MASK:u8 = 0b111;
+
+// The values of the P14 and P15 bits of JOYP
+pub const RESET_PULSE:u8 = 0x00;
+const ONE_BIT_PULSE:u8 = 0x10;
+const ZERO_BIT_PULSE:u8 = 0x20;
+pub const IDLE:u8 = 0x30;
+
+// Every packet starts with a reset pulse (P14 and P15 low), then 128 bits are sent with a pulse on P14 (0) or P15 (1)
+// each followed by both lines high and a stop bit (0) ends the packet.
+// The lower 3 bits of the first byte are the number of packets of the command.
+pub struct PacketReceiver{
+    data:[u8;PACKET_SIZE * MAX_PACKETS],
+    packets_received:u8,
+    bits_received:u8,
+    receiving:bool
+}
+
+impl Default for PacketReceiver{
+    fn default() -> Self {
+        PacketReceiver{data:[0;PACKET_SIZE * MAX_PACKETS], packets_received:0, bits_received:0, receiving:false}
+    }
+}
+
+impl PacketReceiver{
+    // Returns the command once all of its packets arrived
+    pub fn receive(&mut self, last_select:u8, select:u8)->Option<Vec<u8>>{
+        match select{
+            RESET_PULSE=>{
+                self.receiving = true;
+                self.bits_received = 0;
+                let packet_start = self.packets_received as usize * PACKET_SIZE;
+                for byte in self.data[packet_start..packet_start + PACKET_SIZE].iter_mut(){
+                    *byte = 0;
+                }
+            }
+            ONE_BIT_PULSE | ZERO_BIT_PULSE if self.receiving && last_select == IDLE=>{
+                if self.bits_received == PACKET_BITS{
+                    return self.finish_packet();
+                }
+
+                let bit_index = self.packets_received as usize * PACKET_SIZE * 8 + self.bits_received as usize;
+                if select == ONE_BIT_PULSE{
+                    self.data[bit_index / 8] |= 1 << (bit_index % 8);
+                }
+                self.bits_received += 1;
+            }
+            _=>{}
+        }
+
+        return None;
+    }
+
+    pub fn is_receiving(&self)->bool{
+        self.receiving
+    }
+
+    fn finish_packet(&mut self)->Option<Vec<u8>>{
+        self.receiving = false;
+        self.packets_received += 1;
+        let packets_count = std::cmp::max(self.data[0] & PACKETS_COUNT_MASK, 1);
+        if self.packets_received < packets_count{
+            return None;
+        }
+
+        self.packets_received = 0;
+        return Some(self.data[..packets_count as usize * PACKET_SIZE].to_vec());
+    }
+}
+
+impl SaveState for PacketReceiver{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.data);
+        writer.write_u8(self.packets_received);
+        writer.write_u8(self.bits_received);
+        writer.write_bool(self.receiving);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.data, "sgb packets")?;
+        self.packets_received = reader.read_u8()?;
+        self.bits_received = reader.read_u8()?;
+        self.receiving = reader.read_bool()?;
+        if self.packets_received as usize >= MAX_PACKETS || self.bits_received > PACKET_BITS{
+            return Err(SaveStateError::InvalidValue("sgb packet position"));
+        }
+        Ok(())
+    }
+}
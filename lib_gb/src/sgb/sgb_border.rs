@@ -0,0 +1,81 @@
+use crate::{ppu::color::Color, save_state::*};
+use super::gb_sgb::{SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT, VRAM_TRANSFER_SIZE};
+
+const TILE_SIZE:usize = 32;
+const TILES_SIZE:usize = 256 * TILE_SIZE;
+const MAP_WIDTH:usize = 32;
+const MAP_SIZE:usize = MAP_WIDTH * MAP_WIDTH * 2;
+const PALETTES_SIZE:usize = 4 * 16 * 2;
+const FIRST_PALETTE_NUMBER:u16 = 4;
+
+// The border is a SNES background of 4bpp tiles (from CHR_TRN) and a 32x32 map with palettes 4-7 (from PCT_TRN),
+// color 0 of every palette is transparent
+pub struct SgbBorder{
+    tiles:[u8;TILES_SIZE],
+    map:[u8;MAP_SIZE],
+    palettes:[u8;PALETTES_SIZE]
+}
+
+impl Default for SgbBorder{
+    fn default() -> Self {
+        SgbBorder{tiles:[0;TILES_SIZE], map:[0;MAP_SIZE], palettes:[0;PALETTES_SIZE]}
+    }
+}
+
+impl SgbBorder{
+    // Every transfer holds half of the tiles
+    pub fn set_tiles(&mut self, upper_half:bool, data:&[u8;VRAM_TRANSFER_SIZE]){
+        let start = if upper_half {VRAM_TRANSFER_SIZE} else {0};
+        self.tiles[start..start + VRAM_TRANSFER_SIZE].copy_from_slice(data);
+    }
+
+    // The map is followed by the palettes
+    pub fn set_map(&mut self, data:&[u8;VRAM_TRANSFER_SIZE]){
+        self.map.copy_from_slice(&data[..MAP_SIZE]);
+        self.palettes.copy_from_slice(&data[MAP_SIZE..MAP_SIZE + PALETTES_SIZE]);
+    }
+
+    // Draws the non transparent pixels over the frame
+    pub fn draw(&self, frame_buffer:&mut [u32]){
+        for tile_y in 0..SGB_SCREEN_HEIGHT / 8{
+            for tile_x in 0..SGB_SCREEN_WIDTH / 8{
+                let map_index = (tile_y * MAP_WIDTH + tile_x) * 2;
+                let entry = u16::from_le_bytes([self.map[map_index], self.map[map_index + 1]]);
+                let tile = &self.tiles[(entry & 0xFF) as usize * TILE_SIZE..][..TILE_SIZE];
+                let palette = (((entry >> 10) & 0b111).wrapping_sub(FIRST_PALETTE_NUMBER) & 0b11) as usize;
+                let flip_x = entry & (1 << 14) != 0;
+                let flip_y = entry & (1 << 15) != 0;
+
+                for y in 0..8{
+                    let row = if flip_y {7 - y} else {y};
+                    for x in 0..8{
+                        let bit = if flip_x {x} else {7 - x};
+                        let color_index = ((tile[row * 2] >> bit) & 1) | (((tile[row * 2 + 1] >> bit) & 1) << 1) |
+                            (((tile[16 + row * 2] >> bit) & 1) << 2) | (((tile[16 + row * 2 + 1] >> bit) & 1) << 3);
+                        if color_index == 0{
+                            continue;
+                        }
+
+                        let color_address = (palette * 16 + color_index as usize) * 2;
+                        let color = u16::from_le_bytes([self.palettes[color_address], self.palettes[color_address + 1]]);
+                        frame_buffer[(tile_y * 8 + y) * SGB_SCREEN_WIDTH + tile_x * 8 + x] = Color::from_rgb555(color, false).to_rgb888();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl SaveState for SgbBorder{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_bytes(&self.tiles);
+        writer.write_bytes(&self.map);
+        writer.write_bytes(&self.palettes);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        reader.read_bytes_into(&mut self.tiles, "sgb border tiles")?;
+        reader.read_bytes_into(&mut self.map, "sgb border map")?;
+        reader.read_bytes_into(&mut self.palettes, "sgb border palettes")
+    }
+}
@@ -1,4 +1,4 @@
-use crate::utils::bit_masks::*;
+use crate::{utils::bit_masks::*, save_state::*};
 
 pub struct GbTimer{
     pub system_counter:u16,
@@ -71,4 +71,27 @@ impl GbTimer{
 
         return (self.tac_tegister & 0b11, timer_enable);
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for GbTimer{
+    fn save_state(&self, writer:&mut StateWriter){
+        writer.write_u16(self.system_counter);
+        writer.write_bool(self.tima_overflow);
+        writer.write_u8(self.tima_register);
+        writer.write_u8(self.tma_register);
+        writer.write_u8(self.tac_tegister);
+        writer.write_bool(self.last_and_result);
+        writer.write_u8(self.reload_cooldown_counter);
+    }
+
+    fn load_state(&mut self, reader:&mut StateReader)->Result<(), SaveStateError>{
+        self.system_counter = reader.read_u16()?;
+        self.tima_overflow = reader.read_bool()?;
+        self.tima_register = reader.read_u8()?;
+        self.tma_register = reader.read_u8()?;
+        self.tac_tegister = reader.read_u8()?;
+        self.last_and_result = reader.read_bool()?;
+        self.reload_cooldown_counter = reader.read_u8()?;
+        Ok(())
+    }
+}
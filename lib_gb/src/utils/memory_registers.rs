@@ -1,4 +1,6 @@
 pub const JOYP_REGISTER_ADDRESS:u16 = 0xFF00;
+pub const SB_REGISTER_ADDRESS:u16   = 0xFF01;
+pub const SC_REGISTER_ADDRESS:u16   = 0xFF02;
 pub const DIV_REGISTER_ADDRESS:u16  = 0xFF04;
 pub const TIMA_REGISTER_ADDRESS:u16 = 0xFF05;
 pub const TMA_REGISTER_ADDRESS:u16  = 0xFF06;
@@ -37,5 +39,18 @@ pub const OBP0_REGISTER_ADDRESS:u16 = 0xFF48;
 pub const OBP1_REGISTER_ADDRESS:u16 = 0xFF49;
 pub const WY_REGISTER_ADDRESS:u16   = 0xFF4A;
 pub const WX_REGISTER_ADDRESS:u16   = 0xFF4B;
+pub const KEY0_REGISTER_ADDRESS:u16 = 0xFF4C;
+pub const KEY1_REGISTER_ADDRESS:u16 = 0xFF4D;
+pub const VBK_REGISTER_ADDRESS:u16  = 0xFF4F;
 pub const BOOT_REGISTER_ADDRESS:u16 = 0xFF50;
+pub const HDMA1_REGISTER_ADDRESS:u16 = 0xFF51;
+pub const HDMA2_REGISTER_ADDRESS:u16 = 0xFF52;
+pub const HDMA3_REGISTER_ADDRESS:u16 = 0xFF53;
+pub const HDMA4_REGISTER_ADDRESS:u16 = 0xFF54;
+pub const HDMA5_REGISTER_ADDRESS:u16 = 0xFF55;
+pub const BCPS_REGISTER_ADDRESS:u16 = 0xFF68;
+pub const BCPD_REGISTER_ADDRESS:u16 = 0xFF69;
+pub const OCPS_REGISTER_ADDRESS:u16 = 0xFF6A;
+pub const OCPD_REGISTER_ADDRESS:u16 = 0xFF6B;
+pub const SVBK_REGISTER_ADDRESS:u16 = 0xFF70;
 pub const IE_REGISTER_ADDRESS:u16   = 0xFFFF;
\ No newline at end of file
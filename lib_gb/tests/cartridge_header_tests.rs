@@ -0,0 +1,91 @@
+mod machine_stubs;
+
+use lib_gb::{error::CartridgeError, mmu::carts::CartridgeHeader};
+use crate::machine_stubs::*;
+
+const ROM_SIZE:usize = 0x8000;
+
+fn write_checksums(rom:&mut [u8]){
+    rom[0x14D] = CartridgeHeader::calculate_header_checksum(rom);
+    let global_checksum = CartridgeHeader::calculate_global_checksum(rom);
+    rom[0x14E..0x150].copy_from_slice(&global_checksum.to_be_bytes());
+}
+
+fn build_header_rom(title:&[u8])->Vec<u8>{
+    let mut rom = build_rom(&[0x18, 0xFE]);
+    rom[0x134..0x134 + title.len()].copy_from_slice(title);
+    rom[0x144..0x146].copy_from_slice(b"01");
+    rom[0x146] = 0x03;
+    rom[0x147] = 0x03;
+    rom[0x148] = 0x00;
+    rom[0x149] = 0x02;
+    rom[0x14A] = 0x01;
+    rom[0x14B] = 0x33;
+    rom[0x14C] = 0x01;
+    write_checksums(&mut rom);
+
+    return rom;
+}
+
+#[test]
+fn test_parse_dmg_header(){
+    let rom = build_header_rom(b"MAGENBOY TEST");
+    let header = CartridgeHeader::parse(&rom).unwrap();
+
+    assert_eq!(header.title, "MAGENBOY TEST");
+    assert_eq!(header.manufacturer_code, None);
+    assert_eq!(header.new_licensee_code, "01");
+    assert_eq!(header.cartridge_type, 0x03);
+    assert_eq!(header.ram_size_register, 0x02);
+    assert_eq!(header.destination_code, 0x01);
+    assert_eq!(header.mask_rom_version, 0x01);
+    assert_eq!(header.get_rom_size(), Ok(ROM_SIZE));
+    assert!(header.supports_sgb());
+    assert!(!header.supports_cgb());
+    assert!(header.validate(&rom).is_empty());
+}
+
+#[test]
+fn test_parse_cgb_header_with_manufacturer_code(){
+    let mut rom = build_header_rom(b"CGBGAME    ABCD");
+    rom[0x143] = 0xC0;
+    write_checksums(&mut rom);
+    let header = CartridgeHeader::parse(&rom).unwrap();
+
+    assert_eq!(header.title, "CGBGAME    ");
+    assert_eq!(header.manufacturer_code.as_deref(), Some("ABCD"));
+    assert!(header.supports_cgb());
+    assert!(header.is_cgb_only());
+}
+
+#[test]
+fn test_validate_reports_corrupted_rom(){
+    let mut rom = build_header_rom(b"CORRUPTED");
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    rom[0x134] = b'X';
+    rom[0x4000] = 0x1;
+
+    let errors = header.validate(&rom);
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], CartridgeError::HeaderChecksumMismatch{..}));
+    assert!(matches!(errors[1], CartridgeError::GlobalChecksumMismatch{..}));
+}
+
+#[test]
+fn test_validate_reports_rom_size_mismatch(){
+    let mut rom = build_header_rom(b"BIG");
+    rom[0x148] = 0x01;
+    write_checksums(&mut rom);
+    let header = CartridgeHeader::parse(&rom).unwrap();
+
+    assert_eq!(header.validate(&rom), vec![CartridgeError::RomSizeMismatch{expected:ROM_SIZE * 2, found:ROM_SIZE}]);
+}
+
+#[test]
+fn test_invalid_rom_size_register(){
+    let mut rom = build_header_rom(b"BAD SIZE");
+    rom[0x148] = 0x20;
+    let header = CartridgeHeader::parse(&rom).unwrap();
+
+    assert_eq!(header.get_rom_size(), Err(CartridgeError::InvalidRomSize(0x20)));
+}
@@ -0,0 +1,105 @@
+mod machine_stubs;
+
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use crate::machine_stubs::*;
+
+const CGB_FLAG_ADDRESS:usize = 0x143;
+
+// Switches the wram and vram banks and stores the results in hram starting at 0xFF80
+fn build_banking_rom(cgb:bool)->Vec<u8>{
+    let code = [
+        0xAF,               // xor a
+        0xE0, 0x40,         // ldh (LCDC), a - turn off the lcd for free vram access
+        0x3E, 0x11,         // ld a, 0x11
+        0xEA, 0x00, 0xD0,   // ld (0xD000), a
+        0x3E, 0x02,         // ld a, 2
+        0xE0, 0x70,         // ldh (SVBK), a
+        0x3E, 0x22,         // ld a, 0x22
+        0xEA, 0x00, 0xD0,   // ld (0xD000), a
+        0xFA, 0x00, 0xF0,   // ld a, (0xF000)
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0xF0, 0x70,         // ldh a, (SVBK)
+        0xE0, 0x81,         // ldh (0xFF81), a
+        0x3E, 0x55,         // ld a, 0x55
+        0xEA, 0x00, 0xF1,   // ld (0xF100), a
+        0xFA, 0x00, 0xD1,   // ld a, (0xD100)
+        0xE0, 0x88,         // ldh (0xFF88), a
+        0xAF,               // xor a
+        0xE0, 0x70,         // ldh (SVBK), a
+        0xFA, 0x00, 0xD0,   // ld a, (0xD000)
+        0xE0, 0x82,         // ldh (0xFF82), a
+        0xF0, 0x70,         // ldh a, (SVBK)
+        0xE0, 0x83,         // ldh (0xFF83), a
+        0x3E, 0x33,         // ld a, 0x33
+        0xEA, 0x00, 0x80,   // ld (0x8000), a
+        0x3E, 0x01,         // ld a, 1
+        0xE0, 0x4F,         // ldh (VBK), a
+        0x3E, 0x44,         // ld a, 0x44
+        0xEA, 0x00, 0x80,   // ld (0x8000), a
+        0xFA, 0x00, 0x80,   // ld a, (0x8000)
+        0xE0, 0x84,         // ldh (0xFF84), a
+        0xF0, 0x4F,         // ldh a, (VBK)
+        0xE0, 0x85,         // ldh (0xFF85), a
+        0xAF,               // xor a
+        0xE0, 0x4F,         // ldh (VBK), a
+        0xFA, 0x00, 0x80,   // ld a, (0x8000)
+        0xE0, 0x86,         // ldh (0xFF86), a
+        0xF0, 0x4F,         // ldh a, (VBK)
+        0xE0, 0x87,         // ldh (0xFF87), a
+        0x18, 0xFE          // jr -2
+    ];
+
+    let mut rom = build_rom(&code);
+    if cgb{
+        rom[CGB_FLAG_ADDRESS] = 0x80;
+    }
+
+    return rom;
+}
+
+fn run_banking_rom(cgb:bool)->Vec<u8>{
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_banking_rom(cgb), false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+    gameboy.cycle_frame();
+
+    return (0xFF80..=0xFF88).map(|address|gameboy.read_memory(address)).collect();
+}
+
+#[test]
+fn test_cgb_wram_banking(){
+    let results = run_banking_rom(true);
+
+    // the echo ram mirrors the switched bank
+    assert_eq!(results[0], 0x22);
+    assert_eq!(results[1], 0xFA);
+    // bank 0 selects bank 1
+    assert_eq!(results[2], 0x11);
+    assert_eq!(results[3], 0xF8);
+    // writes to the echo ram goes to the switched bank
+    assert_eq!(results[8], 0x55);
+}
+
+#[test]
+fn test_cgb_vram_banking(){
+    let results = run_banking_rom(true);
+
+    assert_eq!(results[4], 0x44);
+    assert_eq!(results[5], 0xFF);
+    assert_eq!(results[6], 0x33);
+    assert_eq!(results[7], 0xFE);
+}
+
+#[test]
+fn test_dmg_ignores_banking_registers(){
+    let results = run_banking_rom(false);
+
+    assert_eq!(results[0], 0x22);
+    assert_eq!(results[1], 0xFF);
+    assert_eq!(results[2], 0x22);
+    assert_eq!(results[3], 0xFF);
+    assert_eq!(results[4], 0x44);
+    assert_eq!(results[5], 0xFF);
+    assert_eq!(results[6], 0x44);
+    assert_eq!(results[7], 0xFF);
+    assert_eq!(results[8], 0x55);
+}
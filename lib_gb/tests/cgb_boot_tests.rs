@@ -0,0 +1,215 @@
+mod machine_stubs;
+
+use lib_gb::{machine::{gameboy::GameBoy, model::Model}, mmu::{carts::{Mbc, Rom}, gb_mmu::{BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE}}, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use lib_gb::ppu::compatibility_palettes::*;
+use crate::machine_stubs::*;
+
+const CGB_FLAG_ADDRESS:usize = 0x143;
+const TITLE_ADDRESS:usize = 0x134;
+const OLD_LICENSEE_CODE_ADDRESS:usize = 0x14B;
+
+// Stores what it sees at 0x200 and 0x104, selects the mode through KEY0 and unmaps itself at the end (like the real bootrom)
+fn build_cgb_boot_rom(key0:u8)->[u8;CGB_BOOT_ROM_SIZE]{
+    let mut boot_rom = [0;CGB_BOOT_ROM_SIZE];
+    let code = [
+        0xFA, 0x00, 0x02,   // ld a, (0x200)
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0xFA, 0x04, 0x01,   // ld a, (0x104)
+        0xE0, 0x81,         // ldh (0xFF81), a
+        0x3E, key0,         // ld a, key0
+        0xE0, 0x4C,         // ldh (KEY0), a
+    ];
+    boot_rom[..code.len()].copy_from_slice(&code);
+    boot_rom[0xFC..0x100].copy_from_slice(&[
+        0x3E, 0x01,         // ld a, 1
+        0xE0, 0x50          // ldh (BOOT), a
+    ]);
+    boot_rom[0x200] = 0xAB;
+
+    return boot_rom;
+}
+
+fn build_cartridge(cgb:bool, code:&[u8])->Vec<u8>{
+    let mut rom = build_rom(code);
+    rom[0x104] = 0xCE;
+    rom[0x200] = 0xCD;
+    if cgb{
+        rom[CGB_FLAG_ADDRESS] = 0x80;
+    }
+
+    return rom;
+}
+
+fn set_title(rom:&mut [u8], title:&str, old_licensee_code:u8){
+    rom[TITLE_ADDRESS..TITLE_ADDRESS + title.len()].copy_from_slice(title.as_bytes());
+    rom[OLD_LICENSEE_CODE_ADDRESS] = old_licensee_code;
+}
+
+const AFTER_BOOT_CODE:[u8;14] = [
+    0xFA, 0x00, 0x02,   // ld a, (0x200)
+    0xE0, 0x82,         // ldh (0xFF82), a
+    0xF0, 0x4D,         // ldh a, (KEY1)
+    0xE0, 0x83,         // ldh (0xFF83), a
+    0xF0, 0x4C,         // ldh a, (KEY0)
+    0xE0, 0x84,         // ldh (0xFF84), a
+    0x18                // jr -2 (the offset is appended)
+];
+
+fn run_cgb_boot_rom(cgb_cartridge:bool, key0:u8)->Vec<u8>{
+    let mut code = AFTER_BOOT_CODE.to_vec();
+    code.push(0xFE);
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_cartridge(cgb_cartridge, &code), false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_cgb_bootrom(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, build_cgb_boot_rom(key0));
+    gameboy.cycle_frame();
+
+    return (0xFF80..=0xFF84).map(|address|gameboy.read_memory(address)).collect();
+}
+
+#[test]
+fn test_cgb_boot_rom_split_mapping(){
+    let results = run_cgb_boot_rom(true, 0x80);
+
+    assert_eq!(results[0], 0xAB);
+    assert_eq!(results[1], 0xCE);
+    // unmapped after the boot
+    assert_eq!(results[2], 0xCD);
+}
+
+#[test]
+fn test_cgb_boot_rom_keeps_cgb_mode(){
+    let results = run_cgb_boot_rom(true, 0x80);
+
+    assert_eq!(results[3], 0x7E);
+    // KEY0 is locked after the boot
+    assert_eq!(results[4], 0xFF);
+}
+
+#[test]
+fn test_cgb_boot_rom_selects_dmg_compatibility_mode(){
+    let results = run_cgb_boot_rom(false, 0x04);
+
+    assert_eq!(results[2], 0xCD);
+    assert_eq!(results[3], 0xFF);
+    assert_eq!(results[4], 0xFF);
+}
+
+#[test]
+fn test_dmg_boot_rom_runs_cgb_cartridges_in_dmg_mode(){
+    let mut boot_rom = [0;BOOT_ROM_SIZE];
+    boot_rom[0xFC..0x100].copy_from_slice(&[
+        0x3E, 0x01,         // ld a, 1
+        0xE0, 0x50          // ldh (BOOT), a
+    ]);
+    let mut code = AFTER_BOOT_CODE.to_vec();
+    code.push(0xFE);
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_cartridge(true, &code), false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_bootrom(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, boot_rom, Model::DMG);
+    gameboy.cycle_frame();
+
+    // KEY1 (double speed) does not exist on the DMG
+    assert_eq!(gameboy.read_memory(0xFF83), 0xFF);
+}
+
+// Colors the background with shade 1 and stores A at the start to 0xFF80
+fn run_dmg_cartridge(title:&str, old_licensee_code:u8, model:Model)->(u8, u32){
+    let code = [
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0x3E, 0x55,         // ld a, 0x55
+        0xE0, 0x47,         // ldh (BGP), a
+        0x3E, 0x91,         // ld a, 0x91
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x18, 0xFE          // jr -2
+    ];
+    let mut rom = build_cartridge(false, &code);
+    set_title(&mut rom, title, old_licensee_code);
+
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_model(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, model);
+    let mut first_pixel = 0;
+    for _ in 0..2{
+        first_pixel = gameboy.cycle_frame()[0];
+    }
+
+    return (gameboy.read_memory(0xFF80), first_pixel);
+}
+
+#[test]
+fn test_dmg_cartridge_on_cgb_uses_the_title_palette(){
+    let (a_register, first_pixel) = run_dmg_cartridge("POKEMON RED", 0x01, Model::CGB);
+
+    assert_eq!(a_register, 0x11);
+    assert_eq!(first_pixel, 0xFF8484);
+}
+
+#[test]
+fn test_dmg_cartridge_on_cgb_uses_the_default_palette(){
+    let (_, first_pixel) = run_dmg_cartridge("POKEMON RED", 0x02, Model::CGB);
+    assert_eq!(first_pixel, 0x7BFF31);
+}
+
+#[test]
+fn test_dmg_cartridge_on_dmg_stays_gray(){
+    let (a_register, first_pixel) = run_dmg_cartridge("POKEMON RED", 0x01, Model::DMG);
+
+    assert_eq!(a_register, 0x01);
+    assert_eq!(first_pixel, 0xA0A0A0);
+}
+
+fn get_title_palettes(title:&str, old_licensee_code:u8, new_licensee_code:[u8;2])->&'static CompatibilityPalettes{
+    let mut title_bytes = [0;TITLE_LENGTH];
+    title_bytes[..title.len()].copy_from_slice(title.as_bytes());
+    get_compatibility_palettes(&title_bytes, old_licensee_code, new_licensee_code)
+}
+
+#[test]
+fn test_compatibility_palettes_fourth_letter(){
+    assert_eq!(get_title_palettes("POKEMON BLUE", 0x01, [0;2]).bg[1], 0x63A5FF);
+    // same checksum with a different 4th letter
+    assert_eq!(get_title_palettes("POKMEON BLUE", 0x01, [0;2]).bg[1], 0x7BFF31);
+}
+
+#[test]
+fn test_compatibility_palettes_shared_checksum(){
+    // KID ICARUS and SOCCER have the same checksum
+    assert_eq!(get_title_palettes("KID ICARUS", 0x01, [0;2]).bg[1], 0x8C8CDE);
+    assert_eq!(get_title_palettes("SOCCER", 0x01, [0;2]).bg[0], 0x6BFF00);
+    assert_eq!(get_title_palettes("TETRIS ATTACK", 0x01, [0;2]).bg[1], 0x52FF00);
+}
+
+#[test]
+fn test_compatibility_palettes_of_titles(){
+    let zelda = get_title_palettes("ZELDA", 0x01, [0;2]);
+    assert_eq!(zelda.bg, [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000]);
+    assert_eq!(zelda.obj0, [0xFFFFFF, 0x00FF00, 0x318400, 0x004A00]);
+    assert_eq!(zelda.obj1, [0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000]);
+
+    assert_eq!(get_title_palettes("POKEMON GREEN", 0x01, [0;2]).bg, [0xFFFFFF, 0x7BFF31, 0x0063C5, 0x000000]);
+    assert_eq!(get_title_palettes("DR.MARIO", 0x01, [0;2]).obj0[1], 0x63A5FF);
+}
+
+#[test]
+fn test_compatibility_palettes_starting_mid_palette(){
+    // the objects palettes of SUPER MARIOLAND start at the last color of a palette
+    let palettes = get_title_palettes("SUPER MARIOLAND", 0x01, [0;2]);
+    assert_eq!(palettes.obj0, [0x000000, 0xFFFFFF, 0xFF8484, 0x943A3A]);
+    assert_eq!(palettes.obj1, palettes.obj0);
+    assert_eq!(palettes.bg[1], 0xFFFF94);
+}
+
+#[test]
+fn test_compatibility_palettes_unknown_title(){
+    assert_eq!(get_title_palettes("MAGEN BOY", 0x01, [0;2]).bg, [0xFFFFFF, 0x7BFF31, 0x0063C5, 0x000000]);
+}
+
+#[test]
+fn test_compatibility_palettes_new_licensee_code(){
+    assert_eq!(get_title_palettes("TETRIS", 0x33, *b"01").bg[1], 0xFFFF00);
+    assert_eq!(get_title_palettes("TETRIS", 0x33, *b"02").bg[1], 0x7BFF31);
+}
+
+#[test]
+fn test_rgb888_to_rgb555(){
+    assert_eq!(rgb888_to_rgb555(0xFFFFFF), 0x7FFF);
+    assert_eq!(rgb888_to_rgb555(0xFF0000), 0x001F);
+    assert_eq!(rgb888_to_rgb555(0x0000FF), 0x7C00);
+}
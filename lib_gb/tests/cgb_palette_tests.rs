@@ -0,0 +1,115 @@
+mod machine_stubs;
+
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use lib_gb::ppu::{color::Color, gb_ppu::GbPpu, ppu_state::PpuState, ppu_register_updater::*};
+use crate::machine_stubs::*;
+
+const CGB_FLAG_ADDRESS:usize = 0x143;
+
+// Fills background palette 0 color 0 with red, reads the registers back to hram and turns on the lcd
+fn build_palette_rom(cgb:bool)->Vec<u8>{
+    let code = [
+        0xAF,               // xor a
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x3E, 0x80,         // ld a, 0x80 - index 0 with auto increment
+        0xE0, 0x68,         // ldh (BCPS), a
+        0x3E, 0x1F,         // ld a, 0x1F
+        0xE0, 0x69,         // ldh (BCPD), a
+        0xAF,               // xor a
+        0xE0, 0x69,         // ldh (BCPD), a
+        0xF0, 0x68,         // ldh a, (BCPS)
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0x3E, 0x00,         // ld a, 0 - index 0 without auto increment
+        0xE0, 0x68,         // ldh (BCPS), a
+        0xF0, 0x69,         // ldh a, (BCPD)
+        0xE0, 0x81,         // ldh (0xFF81), a
+        0xF0, 0x69,         // ldh a, (BCPD)
+        0xE0, 0x82,         // ldh (0xFF82), a
+        0x3E, 0x85,         // ld a, 0x85
+        0xE0, 0x6A,         // ldh (OCPS), a
+        0x3E, 0x42,         // ld a, 0x42
+        0xE0, 0x6B,         // ldh (OCPD), a
+        0xF0, 0x6A,         // ldh a, (OCPS)
+        0xE0, 0x83,         // ldh (0xFF83), a
+        0x3E, 0x91,         // ld a, 0x91
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x18, 0xFE          // jr -2
+    ];
+
+    let mut rom = build_rom(&code);
+    if cgb{
+        rom[CGB_FLAG_ADDRESS] = 0x80;
+    }
+
+    return rom;
+}
+
+fn run_palette_rom(cgb:bool, color_correction:bool)->(Vec<u8>, u32){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_palette_rom(cgb), false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+    gameboy.set_color_correction(color_correction);
+    let mut first_pixel = 0;
+    for _ in 0..3{
+        first_pixel = gameboy.cycle_frame()[0];
+    }
+
+    return ((0xFF80..=0xFF83).map(|address|gameboy.read_memory(address)).collect(), first_pixel);
+}
+
+#[test]
+fn test_cgb_palette_registers(){
+    let (results, _) = run_palette_rom(true, false);
+
+    // auto increment advanced the index by 2
+    assert_eq!(results[0], 0xC2);
+    assert_eq!(results[1], 0x1F);
+    // no auto increment on reads
+    assert_eq!(results[2], 0x1F);
+    assert_eq!(results[3], 0xC6);
+}
+
+#[test]
+fn test_cgb_palette_colors_the_frame(){
+    let (_, first_pixel) = run_palette_rom(true, false);
+    assert_eq!(first_pixel, 0xFF0000);
+}
+
+#[test]
+fn test_cgb_palette_color_correction(){
+    let (_, first_pixel) = run_palette_rom(true, true);
+    assert_eq!(first_pixel, 0xC9002E);
+}
+
+#[test]
+fn test_dmg_palette_registers_are_unmapped(){
+    let (results, first_pixel) = run_palette_rom(false, false);
+
+    assert_eq!(results, vec![0xFF;4]);
+    assert_eq!(first_pixel, 0xFFFFFF);
+}
+
+#[test]
+fn test_rgb555_conversion(){
+    assert!(Color::from_rgb555(0x7FFF, false) == Color{r:255, g:255, b:255});
+    assert!(Color::from_rgb555(0x03E0, false) == Color{r:0, g:255, b:0});
+    assert!(Color::from_rgb555(0x0000, true) == Color{r:0, g:0, b:0});
+    assert!(Color::from_rgb555(0x7FFF, true) == Color{r:240, g:240, b:240});
+}
+
+#[test]
+fn test_palette_data_is_blocked_during_pixel_transfer(){
+    let mut ppu = GbPpu::default();
+    ppu.bg_color_ram.write_index_register(0x80);
+    ppu.state = PpuState::PixelTransfer;
+
+    set_bcpd(&mut ppu, 0x12);
+    assert_eq!(get_bcpd(&ppu), 0xFF);
+    // the index still increments
+    assert_eq!(ppu.bg_color_ram.read_index_register(), 0xC1);
+
+    ppu.state = PpuState::Hblank;
+    ppu.bg_color_ram.write_index_register(0);
+    assert_eq!(get_bcpd(&ppu), 0xFF);
+    set_bcpd(&mut ppu, 0x12);
+    assert_eq!(get_bcpd(&ppu), 0x12);
+}
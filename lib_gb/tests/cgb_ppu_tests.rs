@@ -0,0 +1,175 @@
+use lib_gb::ppu::{color::Color, gb_ppu::GbPpu, ppu_register_updater::*};
+
+const TILE_MAP_ADDRESS:u16 = 0x1800;
+const LCDC_VALUE:u8 = 0b1001_0011;
+
+fn bg_color(palette:u8, color:u8)->u16{
+    ((palette as u16) << 10) | ((color as u16) << 5) | 1
+}
+
+fn obj_color(palette:u8, color:u8)->u16{
+    ((palette as u16) << 10) | ((color as u16) << 5) | 0x1F
+}
+
+fn as_pixel(value:u16)->u32{
+    let color = Color::from_rgb555(value, false);
+    ((color.r as u32) << 16) | ((color.g as u32) << 8) | color.b as u32
+}
+
+fn init_cgb_ppu()->GbPpu{
+    let mut ppu = GbPpu::default();
+    ppu.gbc_mode = true;
+    handle_lcdcontrol_register(LCDC_VALUE, &mut ppu);
+
+    ppu.bg_color_ram.write_index_register(0x80);
+    ppu.obj_color_ram.write_index_register(0x80);
+    for palette in 0..8{
+        for color in 0..4{
+            for byte in bg_color(palette, color).to_le_bytes().iter(){
+                ppu.bg_color_ram.write_data_register(*byte, false);
+            }
+            for byte in obj_color(palette, color).to_le_bytes().iter(){
+                ppu.obj_color_ram.write_data_register(*byte, false);
+            }
+        }
+    }
+
+    return ppu;
+}
+
+// Every row of the tile gets the 2 bytes
+fn write_tile(ppu:&mut GbPpu, bank:u8, tile:u16, rows:[(u8, u8);8]){
+    ppu.vram.set_bank(bank);
+    for (i, (low, high)) in rows.iter().enumerate(){
+        ppu.vram.write_current_bank(tile * 16 + i as u16 * 2, *low);
+        ppu.vram.write_current_bank(tile * 16 + i as u16 * 2 + 1, *high);
+    }
+}
+
+fn write_bg_tile(ppu:&mut GbPpu, tile:u8, attributes:u8){
+    ppu.vram.set_bank(0);
+    ppu.vram.write_current_bank(TILE_MAP_ADDRESS, tile);
+    ppu.vram.set_bank(1);
+    ppu.vram.write_current_bank(TILE_MAP_ADDRESS, attributes);
+}
+
+fn write_obj(ppu:&mut GbPpu, index:usize, x:u8, tile:u8, attributes:u8){
+    ppu.sprite_attribute_table[index * 4] = 16;
+    ppu.sprite_attribute_table[index * 4 + 1] = x;
+    ppu.sprite_attribute_table[index * 4 + 2] = tile;
+    ppu.sprite_attribute_table[index * 4 + 3] = attributes;
+}
+
+fn render_first_line(ppu:&mut GbPpu)->Vec<u32>{
+    let mut if_register = 0;
+    // oam search takes 20 m cycles
+    ppu.update_gb_screen(&mut if_register, 21);
+    return ppu.get_frame_buffer()[0..8].to_vec();
+}
+
+#[test]
+fn test_bg_attribute_palette(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 1, [(0xFF, 0);8]);
+    write_bg_tile(&mut ppu, 1, 0x03);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(bg_color(3, 1));8]);
+}
+
+#[test]
+fn test_bg_attribute_vram_bank(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 1, [(0xFF, 0);8]);
+    write_tile(&mut ppu, 1, 1, [(0, 0xFF);8]);
+    write_bg_tile(&mut ppu, 1, 0x08);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(bg_color(0, 2));8]);
+}
+
+#[test]
+fn test_bg_attribute_flip_x(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 1, [(0x80, 0);8]);
+    write_bg_tile(&mut ppu, 1, 0x20);
+
+    let line = render_first_line(&mut ppu);
+    assert_eq!(line[0], as_pixel(bg_color(0, 0)));
+    assert_eq!(line[7], as_pixel(bg_color(0, 1)));
+}
+
+#[test]
+fn test_bg_attribute_flip_y(){
+    let mut ppu = init_cgb_ppu();
+    let mut rows = [(0, 0);8];
+    rows[7] = (0xFF, 0xFF);
+    write_tile(&mut ppu, 0, 1, rows);
+    write_bg_tile(&mut ppu, 1, 0x40);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(bg_color(0, 3));8]);
+}
+
+#[test]
+fn test_obj_cgb_palette_and_vram_bank(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 2, [(0xFF, 0);8]);
+    write_tile(&mut ppu, 1, 2, [(0xFF, 0xFF);8]);
+    write_obj(&mut ppu, 0, 8, 2, 0x08 | 0x05);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(obj_color(5, 3));8]);
+}
+
+#[test]
+fn test_bg_attribute_priority_over_objects(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 1, [(0xFF, 0);8]);
+    write_tile(&mut ppu, 0, 2, [(0, 0xFF);8]);
+    write_bg_tile(&mut ppu, 1, 0x80);
+    write_obj(&mut ppu, 0, 8, 2, 0);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(bg_color(0, 1));8]);
+}
+
+#[test]
+fn test_bg_color_0_is_always_behind_objects(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 2, [(0, 0xFF);8]);
+    write_bg_tile(&mut ppu, 1, 0x80);
+    write_obj(&mut ppu, 0, 8, 2, 0x80);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(obj_color(0, 2));8]);
+}
+
+#[test]
+fn test_lcdc_bit_0_is_master_priority(){
+    let mut ppu = init_cgb_ppu();
+    handle_lcdcontrol_register(LCDC_VALUE & !1, &mut ppu);
+    write_tile(&mut ppu, 0, 1, [(0xFF, 0);8]);
+    write_tile(&mut ppu, 0, 2, [(0, 0xFF);8]);
+    write_bg_tile(&mut ppu, 1, 0x80);
+    write_obj(&mut ppu, 0, 8, 2, 0x80);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(obj_color(0, 2));8]);
+}
+
+#[test]
+fn test_bg_is_drawn_without_master_priority(){
+    let mut ppu = init_cgb_ppu();
+    handle_lcdcontrol_register(LCDC_VALUE & !1, &mut ppu);
+    write_tile(&mut ppu, 0, 1, [(0xFF, 0);8]);
+    write_bg_tile(&mut ppu, 1, 0);
+
+    assert_eq!(render_first_line(&mut ppu), vec![as_pixel(bg_color(0, 1));8]);
+}
+
+#[test]
+fn test_obj_priority_is_by_oam_order(){
+    let mut ppu = init_cgb_ppu();
+    write_tile(&mut ppu, 0, 2, [(0xFF, 0);8]);
+    write_obj(&mut ppu, 0, 12, 2, 1);
+    write_obj(&mut ppu, 1, 8, 2, 2);
+
+    let line = render_first_line(&mut ppu);
+    // on DMG the object with the smaller x would have been on top
+    assert_eq!(line[4], as_pixel(obj_color(1, 1)));
+    assert_eq!(line[3], as_pixel(obj_color(2, 1)));
+}
@@ -0,0 +1,128 @@
+use lib_gb::ppu::{color::Color, dmg_palette::*, gb_ppu::GbPpu, ppu_register_updater::*};
+
+const LCDC_VALUE:u8 = 0b1001_0011;
+const DRAW_LINE_CYCLES:u32 = 114;
+
+fn init_ppu(dmg_palette:DmgPalette)->GbPpu{
+    let mut ppu = GbPpu::default();
+    ppu.set_dmg_palette(dmg_palette);
+    handle_lcdcontrol_register(LCDC_VALUE, &mut ppu);
+    set_bgp(&mut ppu, 0xE4);
+    set_obp0(&mut ppu, 0xE4);
+    set_obp1(&mut ppu, 0xE4);
+
+    return ppu;
+}
+
+// The second tile of the map is tile 1 which is color 3 and the object at the top left uses tile 2 which is color 2
+fn write_screen(ppu:&mut GbPpu, obj_attributes:u8){
+    for i in 0..8{
+        ppu.vram.write_current_bank(16 + i * 2, 0xFF);
+        ppu.vram.write_current_bank(16 + i * 2 + 1, 0xFF);
+        ppu.vram.write_current_bank(32 + i * 2 + 1, 0xFF);
+    }
+    ppu.vram.write_current_bank(0x1800 + 1, 1);
+    ppu.sprite_attribute_table[0] = 16;
+    ppu.sprite_attribute_table[1] = 8;
+    ppu.sprite_attribute_table[2] = 2;
+    ppu.sprite_attribute_table[3] = obj_attributes;
+}
+
+fn draw_line(ppu:&mut GbPpu)->[u32;3]{
+    let mut if_register = 0;
+    for _ in 0..DRAW_LINE_CYCLES{
+        ppu.update_gb_screen(&mut if_register, 1);
+    }
+    let buffer = ppu.get_frame_buffer();
+    // the object, the first tile and the background color
+    return [buffer[0], buffer[8], buffer[16]];
+}
+
+fn rgb888(color:&Color)->u32{
+    color.to_rgb888()
+}
+
+#[test]
+fn test_presets_are_parsed_by_name(){
+    for preset in DmgPalettePreset::ALL.iter(){
+        assert_eq!(preset.to_string().parse::<DmgPalettePreset>(), Ok(*preset));
+        assert!(preset.to_string().parse::<DmgPalette>() == Ok(preset.get_palette()));
+    }
+    assert_eq!("High-Contrast".parse::<DmgPalettePreset>(), Ok(DmgPalettePreset::HighContrast));
+    assert!("sepia".parse::<DmgPalettePreset>().is_err());
+    assert!(DmgPalette::default() == DmgPalettePreset::Gray.get_palette());
+}
+
+#[test]
+fn test_palette_colors_are_parsed(){
+    let palette:DmgPalette = "#E0F8D0, 88C070, 346856, 0x081820".parse().unwrap();
+    let expected = [0xE0F8D0, 0x88C070, 0x346856, 0x081820];
+    for set in [palette.bg, palette.obj0, palette.obj1].iter(){
+        assert_eq!(set.iter().map(rgb888).collect::<Vec<u32>>(), expected);
+    }
+
+    let palette:DmgPalette = "FFFFFF AAAAAA 555555 000000\nFFFFFF FF0000 800000 000000\nFFFFFF 0000FF 000080 000000".parse().unwrap();
+    assert_eq!(rgb888(&palette.bg[1]), 0xAAAAAA);
+    assert_eq!(rgb888(&palette.obj0[1]), 0xFF0000);
+    assert_eq!(rgb888(&palette.obj1[2]), 0x000080);
+}
+
+#[test]
+fn test_invalid_palette_colors_are_errors(){
+    assert!("FFFFFF AAAAAA 555555".parse::<DmgPalette>().is_err());
+    assert!("FFFFFF AAAAAA 555555 00000G".parse::<DmgPalette>().is_err());
+    assert!("FFFFFF AAAAAA 555555 0000".parse::<DmgPalette>().is_err());
+    assert!("".parse::<DmgPalette>().is_err());
+}
+
+#[test]
+fn test_screen_is_drawn_with_the_palette(){
+    let palette = DmgPalettePreset::Green.get_palette();
+    let mut ppu = init_ppu(palette);
+    write_screen(&mut ppu, 0);
+
+    assert_eq!(draw_line(&mut ppu), [rgb888(&palette.obj0[2]), rgb888(&palette.bg[3]), rgb888(&palette.bg[0])]);
+}
+
+#[test]
+fn test_objects_use_their_own_colors(){
+    let palette = DmgPalettePreset::ColorBlind.get_palette();
+    let mut ppu = init_ppu(palette);
+    // OBP1
+    write_screen(&mut ppu, 1 << 4);
+
+    assert_eq!(draw_line(&mut ppu)[0], rgb888(&palette.obj1[2]));
+}
+
+#[test]
+fn test_palette_registers_map_shades_to_the_palette(){
+    let palette = DmgPalettePreset::Pocket.get_palette();
+    let mut ppu = init_ppu(palette);
+    write_screen(&mut ppu, 0);
+    // color 0 -> shade 3 and color 3 -> shade 0, objects color 2 -> shade 1
+    set_bgp(&mut ppu, 0x1B);
+    set_obp0(&mut ppu, 0x1B);
+
+    assert_eq!(draw_line(&mut ppu), [rgb888(&palette.obj0[1]), rgb888(&palette.bg[0]), rgb888(&palette.bg[3])]);
+}
+
+#[test]
+fn test_changing_the_palette_remaps_the_registers(){
+    let mut ppu = init_ppu(DmgPalette::default());
+    write_screen(&mut ppu, 0);
+    let palette = DmgPalettePreset::Green.get_palette();
+    ppu.set_dmg_palette(palette);
+
+    assert_eq!(draw_line(&mut ppu), [rgb888(&palette.obj0[2]), rgb888(&palette.bg[3]), rgb888(&palette.bg[0])]);
+}
+
+#[test]
+fn test_lcd_off_is_blank_with_the_lightest_color(){
+    let palette = DmgPalettePreset::Green.get_palette();
+    let mut ppu = init_ppu(palette);
+    draw_line(&mut ppu);
+    handle_lcdcontrol_register(0, &mut ppu);
+    draw_line(&mut ppu);
+
+    assert!(ppu.get_frame_buffer().iter().all(|pixel|*pixel == rgb888(&palette.bg[0])));
+}
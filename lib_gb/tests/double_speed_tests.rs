@@ -0,0 +1,82 @@
+mod machine_stubs;
+
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use crate::machine_stubs::*;
+
+const CGB_FLAG_ADDRESS:usize = 0x143;
+const KEY1_RESULT_ADDRESS:u16 = 0xFF80;
+const COUNTER_HIGH_ADDRESS:u16 = 0xFF81;
+
+// Optionally switches the speed, stores KEY1 at 0xFF80 and then counts loop iterations in BC storing B at 0xFF81
+fn build_speed_rom(cgb:bool, switch_speed:bool)->Vec<u8>{
+    let mut code = Vec::new();
+    if switch_speed{
+        code.extend_from_slice(&[
+            0x3E, 0x01,         // ld a, 1
+            0xE0, 0x4D,         // ldh (KEY1), a
+            0x10, 0x00,         // stop
+        ]);
+    }
+    code.extend_from_slice(&[
+        0xF0, 0x4D,         // ldh a, (KEY1)
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0x01, 0x00, 0x00,   // ld bc, 0
+        0x03,               // loop: inc bc
+        0x78,               // ld a, b
+        0xE0, 0x81,         // ldh (0xFF81), a
+        0x18, 0xFA          // jr loop
+    ]);
+
+    let mut rom = build_rom(&code);
+    if cgb{
+        rom[CGB_FLAG_ADDRESS] = 0x80;
+    }
+
+    return rom;
+}
+
+// Returns KEY1 and the high byte of the loop counter after 4 frames
+fn run_speed_rom(rom:Vec<u8>)->(u8, u8){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+    for _ in 0..4{
+        gameboy.cycle_frame();
+    }
+
+    return (gameboy.read_memory(KEY1_RESULT_ADDRESS), gameboy.read_memory(COUNTER_HIGH_ADDRESS));
+}
+
+#[test]
+fn test_stop_switches_to_double_speed(){
+    let (key1, _) = run_speed_rom(build_speed_rom(true, true));
+    assert_eq!(key1, 0xFE);
+}
+
+#[test]
+fn test_cgb_normal_speed_key1(){
+    let (key1, _) = run_speed_rom(build_speed_rom(true, false));
+    assert_eq!(key1, 0x7E);
+}
+
+#[test]
+fn test_key1_is_unmapped_on_dmg_cartridges(){
+    let (key1, _) = run_speed_rom(build_speed_rom(false, true));
+    assert_eq!(key1, 0xFF);
+}
+
+#[test]
+fn test_double_speed_runs_twice_the_cpu_cycles_per_frame(){
+    let (_, normal_speed_count) = run_speed_rom(build_speed_rom(true, false));
+    let (_, double_speed_count) = run_speed_rom(build_speed_rom(true, true));
+
+    let expected = normal_speed_count as i32 * 2;
+    assert!((double_speed_count as i32 - expected).abs() <= 1, "normal: {}, double: {}", normal_speed_count, double_speed_count);
+}
+
+#[test]
+fn test_dmg_cartridge_ignores_speed_switch(){
+    let (_, normal_speed_count) = run_speed_rom(build_speed_rom(false, false));
+    let (_, switched_count) = run_speed_rom(build_speed_rom(false, true));
+
+    assert!((normal_speed_count as i32 - switched_count as i32).abs() <= 1);
+}
@@ -0,0 +1,113 @@
+mod machine_stubs;
+
+use lib_gb::{
+    error::{CartridgeError, StopReason},
+    machine::{gameboy::GameBoy, mbc_initializer::initialize_mbc},
+    mmu::carts::{Mbc, CartridgeHeader, RtcClockSource, mbc::MBC_RAM_SIZE_LOCATION},
+    serial::disconnected_serial_device::DisconnectedSerialDevice
+};
+use crate::machine_stubs::*;
+
+const CARTRIDGE_TYPE_ADDRESS:usize = 0x147;
+
+fn initialize_mbc_from_rom(rom:Vec<u8>, save_data:Option<Vec<u8>>)->Result<Box<dyn Mbc>, CartridgeError>{
+    let header = CartridgeHeader::parse(&rom)?;
+    return initialize_mbc(&header, rom, save_data, RtcClockSource::Emulated);
+}
+
+fn run_until_stopped(code:&[u8])->Option<StopReason>{
+    let mut mbc = initialize_mbc_from_rom(build_rom(code), None).unwrap();
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+    gameboy.cycle_frame();
+
+    return gameboy.get_stop_reason();
+}
+
+#[test]
+fn test_unsupported_cartridge_type_is_an_error(){
+    let mut rom = build_rom(&[]);
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0xFC;
+    let result = initialize_mbc_from_rom(rom, None);
+    assert_eq!(result.err(), Some(CartridgeError::UnsupportedCartridgeType(0xFC)));
+}
+
+#[test]
+fn test_too_small_rom_is_an_error(){
+    let result = initialize_mbc_from_rom(vec![0;0x100], None);
+    assert_eq!(result.err(), Some(CartridgeError::RomTooSmall(0x100)));
+}
+
+#[test]
+fn test_invalid_ram_size_is_an_error(){
+    let mut rom = build_rom(&[]);
+    rom[MBC_RAM_SIZE_LOCATION] = 0x7;
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0x3;
+    let result = initialize_mbc_from_rom(rom, None);
+    assert_eq!(result.err(), Some(CartridgeError::InvalidRamSize(0x7)));
+}
+
+#[test]
+fn test_save_data_size_mismatch_is_an_error(){
+    let mut rom = build_rom(&[]);
+    rom[MBC_RAM_SIZE_LOCATION] = 0x2;
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0x3;
+    let result = initialize_mbc_from_rom(rom, Some(vec![0;0x800]));
+    assert_eq!(result.err(), Some(CartridgeError::SaveDataSizeMismatch{expected:0x2000, found:0x800}));
+}
+
+#[test]
+fn test_matching_save_data_is_loaded(){
+    let mut rom = build_rom(&[]);
+    rom[MBC_RAM_SIZE_LOCATION] = 0x2;
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0x3;
+    let mbc = initialize_mbc_from_rom(rom, Some(vec![0x42;0x2000])).unwrap();
+    assert_eq!(mbc.get_ram(), &[0x42;0x2000][..]);
+}
+
+#[test]
+fn test_legacy_16kb_save_of_8kb_ram_is_loaded(){
+    let mut rom = build_rom(&[]);
+    rom[MBC_RAM_SIZE_LOCATION] = 0x2;
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0x3;
+    let mut save_data = vec![0x42;0x2000];
+    save_data.resize(0x4000, 0);
+    let mbc = initialize_mbc_from_rom(rom, Some(save_data)).unwrap();
+    assert_eq!(mbc.get_ram(), &[0x42;0x2000][..]);
+    assert_eq!(mbc.get_save_data().len(), 0x2000);
+}
+
+#[test]
+fn test_rom_shorter_than_32kb_is_mirrored(){
+    let mut rom = build_rom(&[]);
+    rom.truncate(0x4000);
+    let mbc = initialize_mbc_from_rom(rom.clone(), None).unwrap();
+    assert_eq!(mbc.read_current_bank(0x101), rom[0x101]);
+}
+
+#[test]
+fn test_mbc3_bank_past_the_rom_end_wraps(){
+    let mut rom = build_rom(&[]);
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0x11;
+    rom[0x4000] = 0x99;
+    let mut mbc = initialize_mbc_from_rom(rom, None).unwrap();
+    mbc.write_rom(0x2000, 5);
+    assert_eq!(mbc.read_current_bank(0), 0x99);
+}
+
+#[test]
+fn test_illegal_opcode_locks_the_cpu(){
+    let stop_reason = run_until_stopped(&[0x00, 0xD3]);
+    assert_eq!(stop_reason, Some(StopReason::IllegalOpcode{opcode:0xD3, address:0x151}));
+}
+
+#[test]
+fn test_invalid_stop_locks_the_cpu(){
+    let stop_reason = run_until_stopped(&[0x10, 0x42]);
+    assert_eq!(stop_reason, Some(StopReason::InvalidStop{second_byte:0x42, address:0x150}));
+}
+
+#[test]
+fn test_valid_code_does_not_stop(){
+    let stop_reason = run_until_stopped(&[0x18, 0xFE]);
+    assert_eq!(stop_reason, None);
+}
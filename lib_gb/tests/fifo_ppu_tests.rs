@@ -0,0 +1,201 @@
+use lib_gb::ppu::{gb_ppu::GbPpu, ppu_renderer::PpuRenderer, ppu_state::PpuState, ppu_register_updater::*};
+
+const LCDC_VALUE:u8 = 0b1001_0011;
+const LCDC_WINDOW_VALUE:u8 = 0b1111_0011;
+const WHITE:u32 = 0xFFFFFF;
+const BLACK:u32 = 0x000000;
+const OAM_CYCLES:u32 = 20;
+const LINE_CYCLES:u32 = 114;
+
+fn init_fifo_ppu()->GbPpu{
+    let mut ppu = GbPpu::default();
+    ppu.set_renderer(PpuRenderer::PixelFifo);
+    handle_lcdcontrol_register(LCDC_VALUE, &mut ppu);
+    set_bgp(&mut ppu, 0xE4);
+    set_obp0(&mut ppu, 0xE4);
+
+    return ppu;
+}
+
+// Every pixel of the tile is color 3
+fn write_black_tile(ppu:&mut GbPpu, tile:u16){
+    for i in 0..16{
+        ppu.vram.write_current_bank(tile * 16 + i, 0xFF);
+    }
+}
+
+fn write_obj(ppu:&mut GbPpu, index:usize, x:u8, tile:u8){
+    ppu.sprite_attribute_table[index * 4] = 16;
+    ppu.sprite_attribute_table[index * 4 + 1] = x;
+    ppu.sprite_attribute_table[index * 4 + 2] = tile;
+    ppu.sprite_attribute_table[index * 4 + 3] = 0;
+}
+
+fn is_pixel_transfer(ppu:&GbPpu)->bool{
+    ppu.state as u8 == PpuState::PixelTransfer as u8
+}
+
+// Counts the m cycles the first line spends in pixel transfer
+fn measure_pixel_transfer(ppu:&mut GbPpu)->u32{
+    let mut if_register = 0;
+    let mut cycles = 0;
+    for _ in 0..LINE_CYCLES - 1{
+        ppu.update_gb_screen(&mut if_register, 1);
+        if is_pixel_transfer(ppu){
+            cycles += 1;
+        }
+    }
+
+    return cycles;
+}
+
+fn run_cycles(ppu:&mut GbPpu, cycles:u32){
+    let mut if_register = 0;
+    for _ in 0..cycles{
+        ppu.update_gb_screen(&mut if_register, 1);
+    }
+}
+
+fn get_line(ppu:&GbPpu, line:usize)->Vec<u32>{
+    ppu.get_frame_buffer()[line * 160..(line + 1) * 160].to_vec()
+}
+
+#[test]
+fn test_pixel_transfer_takes_172_dots(){
+    let mut ppu = init_fifo_ppu();
+
+    assert_eq!(measure_pixel_transfer(&mut ppu), 43);
+}
+
+#[test]
+fn test_scx_fine_scroll_extends_pixel_transfer(){
+    let mut ppu = init_fifo_ppu();
+    set_scx(&mut ppu, 5);
+
+    // 172 + 5 dots
+    assert_eq!(measure_pixel_transfer(&mut ppu), 45);
+}
+
+#[test]
+fn test_objects_extend_pixel_transfer(){
+    let mut ppu = init_fifo_ppu();
+    // an object aligned to the background tiles waits for the whole background fetch, 172 + 11 dots
+    write_obj(&mut ppu, 0, 8, 0);
+    assert_eq!(measure_pixel_transfer(&mut ppu), 46);
+
+    let mut ppu = init_fifo_ppu();
+    // 10 objects at the same position, only the first one waits for the background fetch, 172 + 11 + 9 * 6 dots
+    for i in 0..10{
+        write_obj(&mut ppu, i, 8, 0);
+    }
+    assert_eq!(measure_pixel_transfer(&mut ppu), 60);
+}
+
+#[test]
+fn test_only_10_objects_are_fetched_per_line(){
+    let mut with_10 = init_fifo_ppu();
+    let mut with_20 = init_fifo_ppu();
+    for i in 0..10{
+        write_obj(&mut with_10, i, 8 + i as u8 * 8, 0);
+    }
+    for i in 0..20{
+        write_obj(&mut with_20, i, 8 + i as u8 * 8, 0);
+    }
+
+    assert_eq!(measure_pixel_transfer(&mut with_10), measure_pixel_transfer(&mut with_20));
+}
+
+#[test]
+fn test_window_restarts_the_fetcher(){
+    let mut ppu = init_fifo_ppu();
+    handle_lcdcontrol_register(LCDC_WINDOW_VALUE, &mut ppu);
+    handle_wy_register(0, &mut ppu);
+    handle_wx_register(87, &mut ppu);
+
+    // 172 + 6 dots
+    assert_eq!(measure_pixel_transfer(&mut ppu), 45);
+}
+
+#[test]
+fn test_fifo_draws_the_same_frame_as_the_scanline_renderer(){
+    let mut fifo = init_fifo_ppu();
+    let mut scanline = init_fifo_ppu();
+    scanline.set_renderer(PpuRenderer::Scanline);
+    for ppu in [&mut fifo, &mut scanline].iter_mut(){
+        write_black_tile(ppu, 1);
+        for i in 0..32{
+            ppu.vram.write_current_bank(0x1800 + i * 3, 1);
+        }
+        handle_lcdcontrol_register(LCDC_WINDOW_VALUE, ppu);
+        set_scx(ppu, 3);
+        set_scy(ppu, 2);
+        handle_wy_register(8, ppu);
+        handle_wx_register(100, ppu);
+        write_obj(ppu, 0, 20, 1);
+        write_obj(ppu, 1, 4, 1);
+        run_cycles(ppu, LINE_CYCLES * 20);
+    }
+
+    assert_eq!(fifo.get_frame_buffer()[..], scanline.get_frame_buffer()[..]);
+}
+
+#[test]
+fn test_mid_line_bgp_write_changes_the_rest_of_the_line(){
+    let mut ppu = init_fifo_ppu();
+    // halfway through the pixel transfer of the first line
+    run_cycles(&mut ppu, OAM_CYCLES + 3 + 20);
+    set_bgp(&mut ppu, 0xFF);
+    run_cycles(&mut ppu, LINE_CYCLES);
+
+    let line = get_line(&ppu, 0);
+    assert_eq!(line[0], WHITE);
+    assert_eq!(line[159], BLACK);
+    let first_black = line.iter().position(|pixel|*pixel == BLACK).unwrap();
+    assert!(first_black > 60 && first_black < 100);
+    assert!(line[first_black..].iter().all(|pixel|*pixel == BLACK));
+
+    // the next line is all black
+    assert!(get_line(&ppu, 1).iter().all(|pixel|*pixel == BLACK));
+}
+
+#[test]
+fn test_mid_line_scx_write_scrolls_the_rest_of_the_line(){
+    let mut ppu = init_fifo_ppu();
+    // the tiles at odd map positions are black
+    write_black_tile(&mut ppu, 1);
+    for i in (1..32).step_by(2){
+        ppu.vram.write_current_bank(0x1800 + i, 1);
+    }
+    run_cycles(&mut ppu, OAM_CYCLES + 3 + 20);
+    set_scx(&mut ppu, 8);
+    run_cycles(&mut ppu, LINE_CYCLES * 2);
+
+    let line = get_line(&ppu, 0);
+    let second_line = get_line(&ppu, 1);
+    assert_eq!(line[0], WHITE);
+    assert_eq!(line[8], BLACK);
+    assert_eq!(second_line[0], BLACK);
+    assert_eq!(second_line[8], WHITE);
+    // the end of the first line is already scrolled
+    assert_eq!(line[152..], second_line[152..]);
+}
+
+#[test]
+fn test_mid_line_object_size_change_uses_the_low_bits_of_the_row(){
+    let mut ppu = init_fifo_ppu();
+    write_black_tile(&mut ppu, 2);
+    // a y flipped 8x16 object that reaches the first line with its 13th row
+    handle_lcdcontrol_register(LCDC_VALUE | 0b100, &mut ppu);
+    ppu.sprite_attribute_table[0] = 4;
+    ppu.sprite_attribute_table[1] = 80;
+    ppu.sprite_attribute_table[2] = 2;
+    ppu.sprite_attribute_table[3] = 0x40;
+    // the objects are already selected when the size changes back to 8x8
+    run_cycles(&mut ppu, OAM_CYCLES + 2);
+    handle_lcdcontrol_register(LCDC_VALUE, &mut ppu);
+    run_cycles(&mut ppu, LINE_CYCLES);
+
+    let line = get_line(&ppu, 0);
+    assert!(line[72..80].iter().all(|pixel|*pixel == BLACK));
+    assert_eq!(line[80], WHITE);
+}
@@ -0,0 +1,124 @@
+mod machine_stubs;
+
+use std::{cell::RefCell, rc::Rc};
+use lib_gb::{machine::{gameboy::GameBoy, model::Model}, mmu::carts::{Mbc, Rom}, serial::disconnected_serial_device::DisconnectedSerialDevice,
+    ppu::{dmg_palette::*, frame_sink::*}, sgb::gb_sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH}};
+use crate::machine_stubs::*;
+
+// The pixels of every row of tile 0 are colors 0, 0, 2, 2, 1, 1, 3, 3
+const TILE_ROW_COLORS:[u32;8] = [0, 0, 2, 2, 1, 1, 3, 3];
+
+// The last frame (widened to u32) and its size
+type CapturedFrame = Rc<RefCell<(Vec<u32>, usize, usize)>>;
+
+struct CaptureFrameSink{
+    pixel_format:PixelFormat,
+    frame:CapturedFrame
+}
+
+impl FrameSink for CaptureFrameSink{
+    fn get_pixel_format(&self)->PixelFormat{
+        self.pixel_format
+    }
+
+    fn push_frame(&mut self, pixels:FramePixels, width:usize, height:usize){
+        let pixels = match pixels{
+            FramePixels::Rgb888(pixels)=>pixels.to_vec(),
+            FramePixels::Argb8888(pixels)=>pixels.to_vec(),
+            FramePixels::Rgba8888(pixels)=>pixels.to_vec(),
+            FramePixels::Rgb565(pixels)=>pixels.iter().map(|pixel|*pixel as u32).collect(),
+            FramePixels::Shades(pixels)=>pixels.iter().map(|pixel|*pixel as u32).collect()
+        };
+        *self.frame.borrow_mut() = (pixels, width, height);
+    }
+}
+
+// Fills tile 0 (so the whole background) with the 4 colors and turns on the lcd
+fn build_tile_rom()->Vec<u8>{
+    build_rom(&[
+        0xAF,                   // xor a
+        0xE0, 0x40,             // ldh (LCDC), a
+        0x21, 0x00, 0x80,       // ld hl, 0x8000
+        0x06, 0x08,             // ld b, 8
+        0x3E, 0x0F,             // ld a, 0x0F
+        0x22,                   // ld (hl+), a
+        0x3E, 0x33,             // ld a, 0x33
+        0x22,                   // ld (hl+), a
+        0x05,                   // dec b
+        0x20, 0xF7,             // jr nz, -9
+        0x3E, 0xE4,             // ld a, 0xE4
+        0xE0, 0x47,             // ldh (BGP), a
+        0x3E, 0x91,             // ld a, 0x91
+        0xE0, 0x40,             // ldh (LCDC), a
+        0x18, 0xFE              // jr -2
+    ])
+}
+
+// Returns the last frame cycle_frame returned and the last frame of the sink
+fn run_with_sink(model:Model, pixel_format:PixelFormat, dmg_palette:DmgPalette)->(Vec<u32>, (Vec<u32>, usize, usize)){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_tile_rom(), false, None).unwrap());
+    let captured = CapturedFrame::default();
+    let frame_sink = Box::new(CaptureFrameSink{pixel_format, frame:captured.clone()});
+    let mut gameboy = GameBoy::new_with_frame_sink(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, model, frame_sink);
+    gameboy.set_dmg_palette(dmg_palette);
+    let mut frame = Vec::new();
+    for _ in 0..3{
+        frame = gameboy.cycle_frame().to_vec();
+    }
+    drop(gameboy);
+
+    return (frame, captured.borrow().clone());
+}
+
+#[test]
+fn test_rgb888_frames_are_the_ppu_frames(){
+    let (frame, (rgb, width, height)) = run_with_sink(Model::DMG, PixelFormat::Rgb888, DmgPalette::default());
+    assert_eq!((width, height), (160, 144));
+    assert_eq!(rgb, frame);
+}
+
+#[test]
+fn test_argb8888_and_rgba8888_frames(){
+    let (frame, (argb, width, height)) = run_with_sink(Model::DMG, PixelFormat::Argb8888, DmgPalette::default());
+    assert_eq!((width, height), (160, 144));
+    assert_eq!(argb, frame.iter().map(|pixel|0xFF00_0000 | pixel).collect::<Vec<u32>>());
+    assert_eq!(argb[0], 0xFFFF_FFFF);
+    assert_eq!(argb[7], 0xFF00_0000);
+
+    let (frame, (rgba, _, _)) = run_with_sink(Model::DMG, PixelFormat::Rgba8888, DmgPalette::default());
+    assert_eq!(rgba, frame.iter().map(|pixel|(pixel << 8) | 0xFF).collect::<Vec<u32>>());
+}
+
+#[test]
+fn test_rgb565_frames(){
+    assert_eq!(rgb888_to_rgb565(0xFF0000), 0xF800);
+    assert_eq!(rgb888_to_rgb565(0x00FF00), 0x07E0);
+    assert_eq!(rgb888_to_rgb565(0x0000FF), 0x001F);
+
+    let (frame, (rgb565, width, height)) = run_with_sink(Model::DMG, PixelFormat::Rgb565, DmgPalette::default());
+    assert_eq!((width, height), (160, 144));
+    assert_eq!(rgb565, frame.iter().map(|pixel|rgb888_to_rgb565(*pixel) as u32).collect::<Vec<u32>>());
+    assert_eq!(rgb565[0], 0xFFFF);
+}
+
+#[test]
+fn test_shades_do_not_depend_on_the_palette(){
+    let (gray_frame, (gray_shades, width, height)) = run_with_sink(Model::DMG, PixelFormat::Shades, DmgPalette::default());
+    let (green_frame, (green_shades, _, _)) = run_with_sink(Model::DMG, PixelFormat::Shades, DmgPalettePreset::Green.get_palette());
+
+    assert_eq!((width, height), (160, 144));
+    assert_ne!(gray_frame, green_frame);
+    assert_eq!(gray_shades, green_shades);
+    assert_eq!(gray_shades[..8], TILE_ROW_COLORS);
+    assert_eq!(gray_shades[160 * 100 + 8..160 * 100 + 16], TILE_ROW_COLORS);
+}
+
+#[test]
+fn test_sgb_shades_do_not_include_the_border(){
+    let (_, (_, width, height)) = run_with_sink(Model::SGB, PixelFormat::Argb8888, DmgPalette::default());
+    assert_eq!((width, height), (SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT));
+
+    let (_, (shades, width, height)) = run_with_sink(Model::SGB, PixelFormat::Shades, DmgPalette::default());
+    assert_eq!((width, height), (160, 144));
+    assert_eq!(shades.len(), 160 * 144);
+}
@@ -0,0 +1,147 @@
+mod machine_stubs;
+
+use std::{io::{Read, Write}, net::{TcpListener, TcpStream}, thread, time::{Duration, Instant}};
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, serial::{link_cable_serial_device::LinkCableSerialDevice, serial_device::SerialDevice}};
+use crate::machine_stubs::*;
+
+const RESULT_ADDRESS:u16 = 0xFF80;
+const TRANSFER_REQUEST:u8 = 0x01;
+const TRANSFER_REPLY:u8 = 0x02;
+const LINKED_RUN_TIMEOUT:Duration = Duration::from_secs(10);
+
+// Writes the byte to SB, starts a transfer with the SC value, waits for it to finish and stores the received byte at 0xFF80
+fn build_transfer_rom(byte:u8, sc_value:u8)->Vec<u8>{
+    let code = [
+        0x3E, byte,         // ld a, byte
+        0xE0, 0x01,         // ldh (SB), a
+        0x3E, sc_value,     // ld a, sc_value
+        0xE0, 0x02,         // ldh (SC), a
+        0xF0, 0x02,         // wait: ldh a, (SC)
+        0x87,               // add a, a
+        0x38, 0xFB,         // jr c, wait
+        0xF0, 0x01,         // ldh a, (SB)
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0x18, 0xFE          // done: jr done
+    ];
+
+    return build_rom(&code);
+}
+
+// Runs until the transfer stores its result, the other side might start a bit later
+fn run_linked_gameboy(rom:Vec<u8>, stream:TcpStream)->u8{
+    stream.set_nonblocking(true).unwrap();
+    let link_cable = LinkCableSerialDevice::new(stream);
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, link_cable);
+    let start = Instant::now();
+    while gameboy.read_memory(RESULT_ADDRESS) == 0 && start.elapsed() < LINKED_RUN_TIMEOUT{
+        gameboy.cycle_frame();
+    }
+
+    return gameboy.read_memory(RESULT_ADDRESS);
+}
+
+#[test]
+fn test_link_cable_exchanges_bytes_between_gameboys(){
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let slave = thread::spawn(move ||{
+        let (stream, _) = listener.accept().unwrap();
+        run_linked_gameboy(build_transfer_rom(0x99, 0x80), stream)
+    });
+    let master = thread::spawn(move ||{
+        let stream = TcpStream::connect(address).unwrap();
+        run_linked_gameboy(build_transfer_rom(0x42, 0x81), stream)
+    });
+
+    assert_eq!(master.join().unwrap(), 0x99);
+    assert_eq!(slave.join().unwrap(), 0x42);
+}
+
+#[test]
+fn test_link_cable_reads_ff_once_disconnected(){
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    drop(listener.accept().unwrap());
+
+    assert_eq!(run_linked_gameboy(build_transfer_rom(0x42, 0x81), stream), 0xFF);
+}
+
+// A link cable on one side and the raw stream of the other side
+fn connect_raw_peer()->(LinkCableSerialDevice<TcpStream>, TcpStream){
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (peer, _) = listener.accept().unwrap();
+    stream.set_nonblocking(true).unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+
+    return (LinkCableSerialDevice::new(stream), peer);
+}
+
+fn read_message(peer:&mut TcpStream)->[u8;3]{
+    let mut message = [0;3];
+    peer.read_exact(&mut message).unwrap();
+    return message;
+}
+
+fn poll_until_done(link_cable:&mut LinkCableSerialDevice<TcpStream>)->u8{
+    loop{
+        if let Some(byte) = link_cable.poll_transfer(){
+            return byte;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn test_link_cable_drops_replies_of_older_requests(){
+    let (mut link_cable, mut peer) = connect_raw_peer();
+
+    assert_eq!(link_cable.start_transfer(0x42), None);
+    let [message_type, sequence, data] = read_message(&mut peer);
+    assert_eq!((message_type, data), (TRANSFER_REQUEST, 0x42));
+
+    peer.write_all(&[TRANSFER_REPLY, sequence.wrapping_sub(1), 0x11]).unwrap();
+    peer.write_all(&[TRANSFER_REPLY, sequence, 0x99]).unwrap();
+    assert_eq!(poll_until_done(&mut link_cable), 0x99);
+}
+
+#[test]
+fn test_link_cable_waits_for_the_reply_while_connected(){
+    let (mut link_cable, mut peer) = connect_raw_peer();
+
+    assert_eq!(link_cable.start_transfer(0x42), None);
+    let [_, sequence, _] = read_message(&mut peer);
+    // a slow slave never makes the master read a byte the slave did not send
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(600){
+        assert_eq!(link_cable.poll_transfer(), None);
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    peer.write_all(&[TRANSFER_REPLY, sequence, 0x99]).unwrap();
+    assert_eq!(poll_until_done(&mut link_cable), 0x99);
+}
+
+#[test]
+fn test_link_cable_reads_ff_once_the_peer_disconnects_mid_transfer(){
+    let (mut link_cable, mut peer) = connect_raw_peer();
+
+    assert_eq!(link_cable.start_transfer(0x42), None);
+    read_message(&mut peer);
+    drop(peer);
+    assert_eq!(poll_until_done(&mut link_cable), 0xFF);
+    assert!(!link_cable.is_connected());
+}
+
+#[test]
+fn test_link_cable_slave_replies_to_the_latest_request(){
+    let (mut link_cable, mut peer) = connect_raw_peer();
+
+    peer.write_all(&[TRANSFER_REQUEST, 7, 0x42, TRANSFER_REQUEST, 8, 0x43]).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(link_cable.poll_external_clock(0x99), Some(0x43));
+    assert_eq!(read_message(&mut peer), [TRANSFER_REPLY, 8, 0x99]);
+    assert_eq!(link_cable.poll_external_clock(0x99), None);
+}
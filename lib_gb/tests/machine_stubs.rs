@@ -0,0 +1,27 @@
+// every test crate includes these stubs but uses only some of them
+#![allow(dead_code)]
+
+use lib_gb::{apu::audio_device::{AudioDevice, Sample}, keypad::{joypad::Joypad, joypad_provider::JoypadProvider}};
+
+pub const CODE_START_ADDRESS:usize = 0x150;
+
+pub struct StubJoypadProvider;
+
+impl JoypadProvider for StubJoypadProvider{
+    fn provide(&mut self, _joypad:&mut Joypad) {}
+}
+
+pub struct StubAudioDevice;
+
+impl AudioDevice for StubAudioDevice{
+    fn push_buffer(&mut self, _buffer:&[Sample]) {}
+}
+
+// Builds a 32KB rom only cartridge that jumps to the code at 0x150
+pub fn build_rom(code:&[u8])->Vec<u8>{
+    let mut rom = vec![0;0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, (CODE_START_ADDRESS & 0xFF) as u8, (CODE_START_ADDRESS >> 8) as u8]);
+    rom[CODE_START_ADDRESS..CODE_START_ADDRESS + code.len()].copy_from_slice(code);
+
+    return rom;
+}
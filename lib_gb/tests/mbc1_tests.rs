@@ -0,0 +1,126 @@
+use lib_gb::mmu::carts::{Mbc, Mbc1, mbc::MBC_RAM_SIZE_LOCATION, cartridge_header::{NINTENDO_LOGO, NINTENDO_LOGO_ADDRESS}};
+
+const ROM_BANK_SIZE:usize = 0x4000;
+const RAM_SIZE_32KB:u8 = 0x3;
+
+// The first byte of every rom bank holds its number
+fn build_program(banks:usize, ram_size_register:u8)->Vec<u8>{
+    let mut program = vec![0;banks * ROM_BANK_SIZE];
+    for (bank, chunk) in program.chunks_mut(ROM_BANK_SIZE).enumerate(){
+        chunk[0] = bank as u8;
+    }
+    program[MBC_RAM_SIZE_LOCATION] = ram_size_register;
+
+    return program;
+}
+
+fn build_mbc1(banks:usize, ram_size_register:u8)->Mbc1{
+    Mbc1::new(build_program(banks, ram_size_register), false, None).unwrap()
+}
+
+fn build_multicart()->Mbc1{
+    let mut program = build_program(64, 0);
+    for game_bank in [0, 0x10, 0x20, 0x30].iter(){
+        let logo_start = game_bank * ROM_BANK_SIZE + NINTENDO_LOGO_ADDRESS;
+        program[logo_start..logo_start + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+    }
+
+    return Mbc1::new(program, false, None).unwrap();
+}
+
+#[test]
+fn test_bank1_zero_is_translated_to_one(){
+    let mut mbc = build_mbc1(64, 0);
+    mbc.write_rom(0x2000, 0);
+    assert_eq!(mbc.read_current_bank(0), 1);
+
+    mbc.write_rom(0x2000, 0x20);
+    assert_eq!(mbc.read_current_bank(0), 1);
+
+    mbc.write_rom(0x2000, 0x1F);
+    assert_eq!(mbc.read_current_bank(0), 0x1F);
+}
+
+#[test]
+fn test_large_rom_upper_bits(){
+    let mut mbc = build_mbc1(128, 0);
+    mbc.write_rom(0x2000, 0);
+    mbc.write_rom(0x4000, 0x2);
+    assert_eq!(mbc.read_current_bank(0), 0x41);
+    assert_eq!(mbc.read_bank0(0), 0);
+
+    // mode 1 maps the upper bits to the bank0 area as well
+    mbc.write_rom(0x6000, 1);
+    assert_eq!(mbc.read_bank0(0), 0x40);
+    assert_eq!(mbc.read_current_bank(0), 0x41);
+}
+
+#[test]
+fn test_banks_wrap_by_rom_size(){
+    let mut mbc = build_mbc1(32, 0);
+    mbc.write_rom(0x2000, 0x3);
+    mbc.write_rom(0x4000, 0x1);
+    assert_eq!(mbc.read_current_bank(0), 0x3);
+
+    mbc.write_rom(0x6000, 1);
+    assert_eq!(mbc.read_bank0(0), 0);
+
+    let mut mbc = build_mbc1(4, 0);
+    mbc.write_rom(0x2000, 0x7);
+    assert_eq!(mbc.read_current_bank(0), 0x3);
+}
+
+#[test]
+fn test_ram_enable(){
+    let mut mbc = build_mbc1(4, RAM_SIZE_32KB);
+    mbc.write_external_ram(0, 0x42);
+    assert_eq!(mbc.read_external_ram(0), 0xFF);
+
+    mbc.write_rom(0, 0x1A);
+    mbc.write_external_ram(0, 0x42);
+    assert_eq!(mbc.read_external_ram(0), 0x42);
+
+    mbc.write_rom(0, 0x0B);
+    assert_eq!(mbc.read_external_ram(0), 0xFF);
+}
+
+#[test]
+fn test_ram_banking_only_in_mode_1(){
+    let mut mbc = build_mbc1(4, RAM_SIZE_32KB);
+    mbc.write_rom(0, 0xA);
+    mbc.write_rom(0x4000, 0x2);
+    mbc.write_external_ram(0x10, 0x11);
+
+    mbc.write_rom(0x6000, 1);
+    assert_eq!(mbc.read_external_ram(0x10), 0);
+    mbc.write_external_ram(0x10, 0x22);
+
+    mbc.write_rom(0x6000, 0);
+    assert_eq!(mbc.read_external_ram(0x10), 0x11);
+    assert_eq!(mbc.get_ram()[2 * 0x2000 + 0x10], 0x22);
+}
+
+#[test]
+fn test_multicart_wiring(){
+    let mut mbc = build_multicart();
+    mbc.write_rom(0x2000, 0x12);
+    assert_eq!(mbc.read_current_bank(0), 0x2);
+
+    mbc.write_rom(0x4000, 0x1);
+    assert_eq!(mbc.read_current_bank(0), 0x12);
+
+    mbc.write_rom(0x6000, 1);
+    assert_eq!(mbc.read_bank0(0), 0x10);
+
+    // bank1 is still checked for 0 with all the 5 bits
+    mbc.write_rom(0x2000, 0x10);
+    assert_eq!(mbc.read_current_bank(0), 0x10);
+}
+
+#[test]
+fn test_regular_1mb_rom_is_not_a_multicart(){
+    let mut mbc = build_mbc1(64, 0);
+    mbc.write_rom(0x2000, 0x12);
+    mbc.write_rom(0x4000, 0x1);
+    assert_eq!(mbc.read_current_bank(0), 0x32);
+}
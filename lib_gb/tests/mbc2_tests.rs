@@ -0,0 +1,84 @@
+use lib_gb::{error::CartridgeError, mmu::carts::{Mbc, Mbc2, mbc2::MBC2_RAM_SIZE}};
+
+const ROM_BANKS:usize = 16;
+
+// Every rom bank is filled with its number
+fn build_mbc2(battery:bool, ram:Option<Vec<u8>>)->Mbc2{
+    let mut program = vec![0;ROM_BANKS * 0x4000];
+    for (bank, chunk) in program.chunks_mut(0x4000).enumerate(){
+        chunk.iter_mut().for_each(|byte| *byte = bank as u8);
+    }
+
+    return Mbc2::new(program, battery, ram).unwrap();
+}
+
+#[test]
+fn test_rom_bank_selection_uses_address_bit_8(){
+    let mut mbc = build_mbc2(false, None);
+    assert_eq!(mbc.read_current_bank(0), 1);
+
+    mbc.write_rom(0x2100, 0x5);
+    assert_eq!(mbc.read_current_bank(0x100), 5);
+
+    // bit 8 is clear so this is the ram enable register
+    mbc.write_rom(0x2000, 0x7);
+    assert_eq!(mbc.read_current_bank(0x100), 5);
+
+    mbc.write_rom(0x0100, 0x0);
+    assert_eq!(mbc.read_current_bank(0x100), 1);
+
+    mbc.write_rom(0x3FFF, 0xFF);
+    assert_eq!(mbc.read_current_bank(0x100), 0xF);
+}
+
+#[test]
+fn test_ram_stores_only_the_lower_nibble(){
+    let mut mbc = build_mbc2(false, None);
+    mbc.write_rom(0, 0xA);
+
+    mbc.write_external_ram(0x10, 0x5C);
+    assert_eq!(mbc.read_external_ram(0x10), 0xFC);
+}
+
+#[test]
+fn test_ram_is_mirrored(){
+    let mut mbc = build_mbc2(false, None);
+    mbc.write_rom(0, 0xA);
+
+    mbc.write_external_ram(0x1FF, 0x3);
+    assert_eq!(mbc.read_external_ram(0x3FF), 0xF3);
+    assert_eq!(mbc.read_external_ram(0x1FFF), 0xF3);
+}
+
+#[test]
+fn test_ram_disabled(){
+    let mut mbc = build_mbc2(false, None);
+    mbc.write_external_ram(0, 0x3);
+    assert_eq!(mbc.read_external_ram(0), 0xFF);
+
+    mbc.write_rom(0, 0xA);
+    assert_eq!(mbc.read_external_ram(0), 0xF0);
+
+    mbc.write_rom(0, 0x0);
+    assert_eq!(mbc.read_external_ram(0), 0xFF);
+}
+
+#[test]
+fn test_battery_save_data(){
+    let mut save = vec![0;MBC2_RAM_SIZE];
+    save[3] = 0x9;
+    let mut mbc = build_mbc2(true, Some(save));
+    assert!(mbc.has_battery());
+    mbc.write_rom(0, 0xA);
+    assert_eq!(mbc.read_external_ram(3), 0xF9);
+
+    mbc.write_external_ram(4, 0x7);
+    assert_eq!(mbc.get_ram().len(), MBC2_RAM_SIZE);
+    assert_eq!(mbc.get_ram()[4], 0x7);
+}
+
+#[test]
+fn test_wrong_save_data_size(){
+    let result = Mbc2::new(vec![0;0x8000], true, Some(vec![0;0x2000]));
+    assert_eq!(result.err(), Some(CartridgeError::SaveDataSizeMismatch{expected:MBC2_RAM_SIZE, found:0x2000}));
+}
@@ -0,0 +1,113 @@
+use std::{cell::RefCell, rc::Rc};
+use lib_gb::mmu::carts::{Mbc, Mbc5, RumbleMotor, mbc::MBC_RAM_SIZE_LOCATION};
+
+const ROM_BANKS:usize = 512;
+const RAM_SIZE_128KB:u8 = 0x4;
+
+struct RecordingRumbleMotor{
+    states:Rc<RefCell<Vec<bool>>>
+}
+
+impl RumbleMotor for RecordingRumbleMotor{
+    fn set_rumble(&mut self, on:bool) {
+        self.states.borrow_mut().push(on);
+    }
+}
+
+// The first two bytes of every rom bank hold its number
+fn build_mbc5(rumble:bool)->Mbc5{
+    let mut program = vec![0;ROM_BANKS * 0x4000];
+    for (bank, chunk) in program.chunks_mut(0x4000).enumerate(){
+        chunk[0] = bank as u8;
+        chunk[1] = (bank >> 8) as u8;
+    }
+    program[MBC_RAM_SIZE_LOCATION] = RAM_SIZE_128KB;
+
+    return Mbc5::new(program, false, rumble, None).unwrap();
+}
+
+fn read_bank_number(mbc:&Mbc5)->u16{
+    mbc.read_current_bank(0) as u16 | ((mbc.read_current_bank(1) as u16) << 8)
+}
+
+#[test]
+fn test_9_bit_rom_bank(){
+    let mut mbc = build_mbc5(false);
+    assert_eq!(read_bank_number(&mbc), 1);
+
+    mbc.write_rom(0x2000, 0x34);
+    mbc.write_rom(0x3000, 0x1);
+    assert_eq!(read_bank_number(&mbc), 0x134);
+
+    mbc.write_rom(0x2000, 0xFF);
+    assert_eq!(read_bank_number(&mbc), 0x1FF);
+
+    mbc.write_rom(0x3000, 0x0);
+    assert_eq!(read_bank_number(&mbc), 0xFF);
+}
+
+#[test]
+fn test_rom_bank_0_in_switchable_area(){
+    let mut mbc = build_mbc5(false);
+    mbc.write_rom(0x2000, 0);
+    assert_eq!(read_bank_number(&mbc), 0);
+}
+
+#[test]
+fn test_16_ram_banks(){
+    let mut mbc = build_mbc5(false);
+    mbc.write_rom(0, 0xA);
+    for bank in 0..16{
+        mbc.write_rom(0x4000, bank);
+        mbc.write_external_ram(0x10, bank + 0x20);
+    }
+    for bank in 0..16{
+        mbc.write_rom(0x4000, bank);
+        assert_eq!(mbc.read_external_ram(0x10), bank + 0x20);
+    }
+}
+
+#[test]
+fn test_ram_enable(){
+    let mut mbc = build_mbc5(false);
+    mbc.write_external_ram(0, 0x42);
+    assert_eq!(mbc.read_external_ram(0), 0xFF);
+
+    mbc.write_rom(0, 0xA);
+    mbc.write_external_ram(0, 0x42);
+    assert_eq!(mbc.read_external_ram(0), 0x42);
+
+    mbc.write_rom(0, 0);
+    assert_eq!(mbc.read_external_ram(0), 0xFF);
+}
+
+#[test]
+fn test_rumble_motor(){
+    let states = Rc::new(RefCell::new(Vec::new()));
+    let mut mbc = build_mbc5(true);
+    assert!(mbc.has_rumble());
+    mbc.attach_rumble_motor(Box::new(RecordingRumbleMotor{states:states.clone()}));
+
+    mbc.write_rom(0x4000, 0x8);
+    mbc.write_rom(0x4000, 0x9);
+    mbc.write_rom(0x4000, 0x1);
+    assert_eq!(*states.borrow(), vec![true, false]);
+
+    // The motor bit does not select a ram bank
+    mbc.write_rom(0, 0xA);
+    mbc.write_rom(0x4000, 0x0);
+    mbc.write_external_ram(0, 0x11);
+    mbc.write_rom(0x4000, 0x8);
+    assert_eq!(mbc.read_external_ram(0), 0x11);
+}
+
+#[test]
+fn test_no_rumble_on_regular_cartridges(){
+    let states = Rc::new(RefCell::new(Vec::new()));
+    let mut mbc = build_mbc5(false);
+    assert!(!mbc.has_rumble());
+    mbc.attach_rumble_motor(Box::new(RecordingRumbleMotor{states:states.clone()}));
+
+    mbc.write_rom(0x4000, 0x8);
+    assert!(states.borrow().is_empty());
+}
@@ -0,0 +1,171 @@
+mod machine_stubs;
+
+use lib_gb::{machine::{gameboy::GameBoy, model::Model}, mmu::carts::{Mbc, Rom}, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use crate::machine_stubs::*;
+
+const TITLE_ADDRESS:usize = 0x134;
+const CGB_FLAG_ADDRESS:usize = 0x143;
+const SGB_FLAG_ADDRESS:usize = 0x146;
+const OLD_LICENSEE_CODE_ADDRESS:usize = 0x14B;
+const HEADER_CHECKSUM_ADDRESS:usize = 0x14D;
+
+// Reads DIV, LY and STAT first (they change quickly) and then stores the cpu registers and the IO registers to hram
+const DUMP_STATE_CODE:[u8;53] = [
+    0xF5,               // push af
+    0xF0, 0x04,         // ldh a, (DIV)
+    0xE0, 0x82,         // ldh (0xFF82), a
+    0xF0, 0x44,         // ldh a, (LY)
+    0xE0, 0x80,         // ldh (0xFF80), a
+    0xF0, 0x41,         // ldh a, (STAT)
+    0xE0, 0x81,         // ldh (0xFF81), a
+    0xF1,               // pop af
+    0xE0, 0x83,         // ldh (0xFF83), a
+    0x78, 0xE0, 0x85,   // ld a, b ; ldh (0xFF85), a
+    0x79, 0xE0, 0x86,   // ld a, c ; ldh (0xFF86), a
+    0x7A, 0xE0, 0x87,   // ld a, d ; ldh (0xFF87), a
+    0x7B, 0xE0, 0x88,   // ld a, e ; ldh (0xFF88), a
+    0x7C, 0xE0, 0x89,   // ld a, h ; ldh (0xFF89), a
+    0x7D, 0xE0, 0x8A,   // ld a, l ; ldh (0xFF8A), a
+    0xF5, 0xC1,         // push af ; pop bc
+    0x79, 0xE0, 0x84,   // ld a, c ; ldh (0xFF84), a
+    0xF0, 0x40,         // ldh a, (LCDC)
+    0xE0, 0x8B,         // ldh (0xFF8B), a
+    0xF0, 0x47,         // ldh a, (BGP)
+    0xE0, 0x8C,         // ldh (0xFF8C), a
+    0xF0, 0x26,         // ldh a, (NR52)
+    0xE0, 0x8D,         // ldh (0xFF8D), a
+    0x18, 0xFE          // jr -2
+];
+
+const LY:usize = 0;
+const STAT:usize = 1;
+const DIV:usize = 2;
+const A:usize = 3;
+const F:usize = 4;
+const B:usize = 5;
+const C:usize = 6;
+const D:usize = 7;
+const E:usize = 8;
+const H:usize = 9;
+const L:usize = 10;
+const LCDC:usize = 11;
+const BGP:usize = 12;
+const NR52:usize = 13;
+
+fn build_cartridge(cgb_flag:u8, sgb_flag:u8, header_checksum:u8)->Vec<u8>{
+    let mut rom = build_rom(&DUMP_STATE_CODE);
+    rom[CGB_FLAG_ADDRESS] = cgb_flag;
+    rom[SGB_FLAG_ADDRESS] = sgb_flag;
+    rom[HEADER_CHECKSUM_ADDRESS] = header_checksum;
+
+    return rom;
+}
+
+fn dump_state(rom:Vec<u8>, model:Option<Model>)->Vec<u8>{
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mut gameboy = match model{
+        Some(model)=>GameBoy::new_with_model(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, model),
+        None=>GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice)
+    };
+    gameboy.cycle_frame();
+
+    return (0xFF80..=0xFF8D).map(|address|gameboy.read_memory(address)).collect();
+}
+
+fn get_registers(state:&[u8])->[u8;8]{
+    [state[A], state[F], state[B], state[C], state[D], state[E], state[H], state[L]]
+}
+
+#[test]
+fn test_dmg_post_boot_registers(){
+    let state = dump_state(build_cartridge(0, 0, 0x66), Some(Model::DMG));
+    assert_eq!(get_registers(&state), [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D]);
+
+    // the half carry and carry flags are set only by a non zero header checksum
+    let state = dump_state(build_cartridge(0, 0, 0), Some(Model::DMG));
+    assert_eq!(state[F], 0x80);
+}
+
+#[test]
+fn test_post_boot_registers_of_every_model(){
+    let expected_registers = [
+        (Model::DMG0, [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03]),
+        (Model::MGB,  [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D]),
+        (Model::SGB,  [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60]),
+        (Model::SGB2, [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60]),
+        (Model::CGB,  [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D]),
+        (Model::AGB,  [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D]),
+    ];
+
+    for (model, registers) in expected_registers.iter(){
+        let state = dump_state(build_cartridge(0x80, 0, 0x66), Some(*model));
+        assert_eq!(get_registers(&state), *registers, "model {}", model);
+    }
+}
+
+#[test]
+fn test_cgb_running_dmg_cartridge_registers(){
+    let mut rom = build_cartridge(0, 0, 0x66);
+    rom[TITLE_ADDRESS..TITLE_ADDRESS + 11].copy_from_slice(b"POKEMON RED");
+    rom[OLD_LICENSEE_CODE_ADDRESS] = 0x01;
+
+    let state = dump_state(rom.clone(), Some(Model::CGB));
+    // B holds the title checksum
+    assert_eq!(get_registers(&state), [0x11, 0x80, 0x14, 0x00, 0x00, 0x08, 0x00, 0x7C]);
+
+    let state = dump_state(rom, Some(Model::AGB));
+    assert_eq!(get_registers(&state), [0x11, 0x00, 0x15, 0x00, 0x00, 0x08, 0x00, 0x7C]);
+}
+
+#[test]
+fn test_post_boot_io_registers(){
+    let state = dump_state(build_cartridge(0, 0, 0), Some(Model::DMG));
+    assert_eq!(state[LCDC], 0x91);
+    assert_eq!(state[BGP], 0xFC);
+    assert_eq!(state[DIV], 0xAB);
+
+    let state = dump_state(build_cartridge(0x80, 0, 0), Some(Model::CGB));
+    assert_eq!(state[LCDC], 0x91);
+    assert_eq!(state[DIV], 0x1E);
+}
+
+#[test]
+fn test_post_boot_nr52(){
+    // channel 1 is still on from the boot sound except on the sgb which has no boot sound
+    for model in Model::ALL.iter(){
+        let expected = if model.is_sgb() {0xF0} else {0xF1};
+        assert_eq!(dump_state(build_cartridge(0x80, 0, 0), Some(*model))[NR52], expected, "model {}", model);
+    }
+}
+
+#[test]
+fn test_post_boot_ppu_position(){
+    // the dmg bootrom ends at the end of the last line so a new frame starts right away
+    let state = dump_state(build_cartridge(0, 0, 0), Some(Model::DMG));
+    assert_eq!(state[LY], 0);
+
+    let state = dump_state(build_cartridge(0x80, 0, 0), Some(Model::CGB));
+    assert_eq!(state[LY], 144);
+    assert_eq!(state[STAT] & 0b11, 1);
+}
+
+#[test]
+fn test_model_detection_from_header(){
+    assert_eq!(dump_state(build_cartridge(0x80, 0, 0), None)[A], 0x11);
+    assert_eq!(dump_state(build_cartridge(0xC0, 0, 0), None)[A], 0x11);
+    // sgb, only with the old licensee code that points to the new one
+    let mut rom = build_cartridge(0, 0x03, 0);
+    assert_eq!(dump_state(rom.clone(), None)[C], 0x13);
+    rom[OLD_LICENSEE_CODE_ADDRESS] = 0x33;
+    assert_eq!(dump_state(rom, None)[C], 0x14);
+    assert_eq!(dump_state(build_cartridge(0, 0, 0), None)[C], 0x13);
+}
+
+#[test]
+fn test_model_names(){
+    for model in Model::ALL.iter(){
+        assert_eq!(model.to_string().parse::<Model>(), Ok(*model));
+    }
+    assert_eq!("SGB2".parse::<Model>(), Ok(Model::SGB2));
+    assert!("gba".parse::<Model>().is_err());
+}
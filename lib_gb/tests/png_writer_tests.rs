@@ -0,0 +1,99 @@
+mod machine_stubs;
+
+use lib_gb::{machine::{gameboy::GameBoy, model::Model}, mmu::carts::{Mbc, Rom}, ppu::png_writer::write_png,
+    serial::disconnected_serial_device::DisconnectedSerialDevice};
+use crate::machine_stubs::*;
+
+const PNG_SIGNATURE:[u8;8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+// The crc of an IEND chunk is always the same since it has no data
+const IEND_CHUNK:[u8;12] = [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82];
+
+// Returns the (type, data) of every chunk
+fn read_chunks(png:&[u8])->Vec<([u8;4], Vec<u8>)>{
+    let mut chunks = Vec::new();
+    let mut index = PNG_SIGNATURE.len();
+    while index < png.len(){
+        let length = u32::from_be_bytes([png[index], png[index + 1], png[index + 2], png[index + 3]]) as usize;
+        let mut chunk_type = [0;4];
+        chunk_type.copy_from_slice(&png[index + 4..index + 8]);
+        chunks.push((chunk_type, png[index + 8..index + 8 + length].to_vec()));
+        index += length + 12;
+    }
+    return chunks;
+}
+
+// Joins the stored deflate blocks of the zlib stream
+fn read_stored_zlib(stream:&[u8])->Vec<u8>{
+    let mut data = Vec::new();
+    let mut index = 2;
+    loop{
+        let is_final = stream[index] & 1 != 0;
+        assert_eq!(stream[index] & 0b110, 0);
+        let length = u16::from_le_bytes([stream[index + 1], stream[index + 2]]) as usize;
+        assert_eq!(!u16::from_le_bytes([stream[index + 3], stream[index + 4]]) as usize, length);
+        data.extend_from_slice(&stream[index + 5..index + 5 + length]);
+        index += 5 + length;
+        if is_final{
+            break;
+        }
+    }
+    assert_eq!(index + 4, stream.len());
+    return data;
+}
+
+fn encode(pixels:&[u32], width:usize, height:usize, scale:usize)->Vec<u8>{
+    let mut png = Vec::new();
+    write_png(&mut png, pixels, width, height, scale).unwrap();
+    return png;
+}
+
+#[test]
+fn test_png_header_and_chunks(){
+    let png = encode(&[0;160 * 144], 160, 144, 3);
+    assert_eq!(png[..8], PNG_SIGNATURE);
+    assert_eq!(png[png.len() - 12..], IEND_CHUNK);
+
+    let chunks = read_chunks(&png);
+    let types:Vec<&[u8;4]> = chunks.iter().map(|(chunk_type, _)|chunk_type).collect();
+    assert_eq!(types, vec![b"IHDR", b"IDAT", b"IEND"]);
+    // 480x432, 8 bits RGB
+    assert_eq!(chunks[0].1, vec![0, 0, 0x01, 0xE0, 0, 0, 0x01, 0xB0, 8, 2, 0, 0, 0]);
+}
+
+#[test]
+fn test_png_pixels_are_scaled(){
+    let pixels = [0xFF0000, 0x00FF00, 0x0000FF, 0x123456];
+    let png = encode(&pixels, 2, 2, 2);
+    let image = read_stored_zlib(&read_chunks(&png)[1].1);
+
+    let red_green = [0, 0xFF, 0, 0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0xFF, 0];
+    let blue_other = [0, 0, 0, 0xFF, 0, 0, 0xFF, 0x12, 0x34, 0x56, 0x12, 0x34, 0x56];
+    assert_eq!(image, [red_green, red_green, blue_other, blue_other].concat());
+}
+
+#[test]
+fn test_big_images_are_split_to_blocks(){
+    let pixels:Vec<u32> = (0..160 * 144).map(|i|i as u32).collect();
+    let png = encode(&pixels, 160, 144, 4);
+    let image = read_stored_zlib(&read_chunks(&png)[1].1);
+
+    assert_eq!(image.len(), (640 * 3 + 1) * 576);
+    // the first pixel of the last line
+    let last_line = (640 * 3 + 1) * 575;
+    assert_eq!(image[last_line..last_line + 4], [0, 0, 0x59, 0x60]);
+}
+
+#[test]
+fn test_screenshot_is_the_size_of_the_frame(){
+    for (model, width, height) in [(Model::DMG, 160, 144), (Model::SGB, 256, 224)].iter(){
+        let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_rom(&[0x18, 0xFE]), false, None).unwrap());
+        let mut gameboy = GameBoy::new_with_model(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, *model);
+        gameboy.cycle_frame();
+        let mut png = Vec::new();
+        gameboy.write_screenshot(&mut png, 2).unwrap();
+
+        let header = &read_chunks(&png)[0].1;
+        assert_eq!(u32::from_be_bytes([header[0], header[1], header[2], header[3]]), width * 2);
+        assert_eq!(u32::from_be_bytes([header[4], header[5], header[6], header[7]]), height * 2);
+    }
+}
@@ -0,0 +1,144 @@
+use lib_gb::serial::{printer_serial_device::*, serial_device::SerialDevice};
+
+const WHITE:u32 = 0xFFFFFF;
+const LIGHT_GRAY:u32 = 0xAAAAAA;
+const DARK_GRAY:u32 = 0x555555;
+const BLACK:u32 = 0x000000;
+const TILE_ROW_SIZE:usize = 20 * 16;
+const BUSY_STATUS:u8 = 1 << 1;
+const IMAGE_FULL_STATUS:u8 = 1 << 2;
+const UNPROCESSED_DATA_STATUS:u8 = 1 << 3;
+
+#[derive(Default)]
+struct CapturePagePrinter{
+    pages:Vec<PrintedPage>
+}
+
+impl PagePrinter for CapturePagePrinter{
+    fn print_page(&mut self, page:&PrintedPage){
+        self.pages.push(PrintedPage{width:page.width, height:page.height, pixels:page.pixels.clone()});
+    }
+}
+
+fn build_packet(command:u8, compressed:bool, data:&[u8])->Vec<u8>{
+    let mut packet = vec![0x88, 0x33, command, compressed as u8, data.len() as u8, (data.len() >> 8) as u8];
+    packet.extend_from_slice(data);
+    let checksum = packet[2..].iter().fold(0u16, |sum, byte|sum.wrapping_add(*byte as u16));
+    packet.extend_from_slice(&checksum.to_le_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    return packet;
+}
+
+// Returns the alive and status replies
+fn send_packet<PP:PagePrinter>(printer:&mut PrinterSerialDevice<PP>, packet:&[u8])->(u8, u8){
+    let replies:Vec<u8> = packet.iter().map(|byte|printer.start_transfer(*byte).unwrap()).collect();
+    assert!(replies[..replies.len() - 2].iter().all(|reply|*reply == 0));
+    return (replies[replies.len() - 2], replies[replies.len() - 1]);
+}
+
+// A tile row where every tile has color 0, 1, 2 and 3 in its first 4 rows and color 3 in the rest
+fn build_tile_row()->Vec<u8>{
+    let tile = [0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+    return tile.iter().cycle().take(TILE_ROW_SIZE).copied().collect();
+}
+
+fn print_tile_row(printer:&mut PrinterSerialDevice<CapturePagePrinter>, margins:u8, palette:u8){
+    send_packet(printer, &build_packet(0x01, false, &[]));
+    send_packet(printer, &build_packet(0x04, false, &build_tile_row()));
+    send_packet(printer, &build_packet(0x04, false, &[]));
+    send_packet(printer, &build_packet(0x02, false, &[1, margins, palette, 0x40]));
+}
+
+#[test]
+fn test_printer_replies_alive_and_status(){
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+
+    assert_eq!(send_packet(&mut printer, &build_packet(0x01, false, &[])), (0x81, 0));
+    assert_eq!(send_packet(&mut printer, &build_packet(0x0F, false, &[])), (0x81, 0));
+}
+
+#[test]
+fn test_printer_reports_checksum_errors(){
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+    let mut packet = build_packet(0x04, false, &build_tile_row());
+    packet[10] ^= 0xFF;
+
+    assert_eq!(send_packet(&mut printer, &packet), (0x81, 1));
+    assert_eq!(send_packet(&mut printer, &build_packet(0x0F, false, &[])), (0x81, 0));
+}
+
+#[test]
+fn test_printer_ignores_bytes_before_the_magic(){
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+    let mut packet = vec![0x00, 0x88, 0x12];
+    packet.extend_from_slice(&build_packet(0x0F, false, &[]));
+
+    assert_eq!(send_packet(&mut printer, &packet), (0x81, 0));
+}
+
+#[test]
+fn test_printer_status_through_a_print(){
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+    send_packet(&mut printer, &build_packet(0x01, false, &[]));
+
+    assert_eq!(send_packet(&mut printer, &build_packet(0x04, false, &build_tile_row())).1, UNPROCESSED_DATA_STATUS);
+    assert_eq!(send_packet(&mut printer, &build_packet(0x04, false, &[])).1, UNPROCESSED_DATA_STATUS | IMAGE_FULL_STATUS);
+    assert_eq!(send_packet(&mut printer, &build_packet(0x02, false, &[1, 0x00, 0xE4, 0x40])).1, BUSY_STATUS);
+
+    let mut status = BUSY_STATUS;
+    let mut polls = 0;
+    while status & BUSY_STATUS != 0{
+        status = send_packet(&mut printer, &build_packet(0x0F, false, &[])).1;
+        polls += 1;
+        assert!(polls < 100);
+    }
+    assert_eq!(status, 0);
+}
+
+#[test]
+fn test_printer_prints_with_the_palette(){
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+    // reversed palette
+    print_tile_row(&mut printer, 0x01, 0x1B);
+
+    let pages = &printer.get_page_printer().pages;
+    assert_eq!(pages.len(), 1);
+    assert_eq!((pages[0].width, pages[0].height), (160, 16));
+    let rows:Vec<u32> = (0..4).map(|y|pages[0].pixels[y * 160 + 3]).collect();
+    assert_eq!(rows, vec![BLACK, DARK_GRAY, LIGHT_GRAY, WHITE]);
+    assert!(pages[0].pixels[160 * 8..].iter().all(|pixel|*pixel == WHITE));
+}
+
+#[test]
+fn test_printer_joins_prints_without_a_margin_after(){
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+    print_tile_row(&mut printer, 0x10, 0xE4);
+    print_tile_row(&mut printer, 0x00, 0xE4);
+    assert!(printer.get_page_printer().pages.is_empty());
+    print_tile_row(&mut printer, 0x02, 0xE4);
+
+    let pages = &printer.get_page_printer().pages;
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].height, 8 + 8 * 3 + 8 * 2);
+    assert!(pages[0].pixels[..160 * 8].iter().all(|pixel|*pixel == WHITE));
+    assert_eq!(pages[0].pixels[160 * 8 + 160 * 3], BLACK);
+}
+
+#[test]
+fn test_printer_decompresses_rle_data(){
+    let tile_row = build_tile_row();
+    let mut compressed = Vec::new();
+    for tile in tile_row.chunks(16){
+        // the first 5 bytes are copied and the rest is a run
+        compressed.extend_from_slice(&[0x03, tile[0], tile[1], tile[2], tile[3], 0x00, tile[4], 0x89, 0xFF]);
+    }
+
+    let mut printer = PrinterSerialDevice::new(CapturePagePrinter::default());
+    send_packet(&mut printer, &build_packet(0x04, true, &compressed));
+    send_packet(&mut printer, &build_packet(0x02, false, &[1, 0x01, 0xE4, 0x40]));
+
+    let mut uncompressed = PrinterSerialDevice::new(CapturePagePrinter::default());
+    print_tile_row(&mut uncompressed, 0x01, 0xE4);
+
+    assert_eq!(printer.get_page_printer().pages[0].pixels, uncompressed.get_page_printer().pages[0].pixels);
+}
@@ -0,0 +1,151 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use lib_gb::mmu::carts::{Mbc, Mbc3, RtcClockSource, mbc::MBC_RAM_SIZE_LOCATION, rtc::RTC_FOOTER_SIZE};
+
+const RAM_SIZE_8KB:u8 = 0x2;
+const RAM_SIZE:usize = 0x2000;
+const M_CYCLES_PER_SECOND:u32 = 0x100000;
+
+const SECONDS:u8 = 0x8;
+const MINUTES:u8 = 0x9;
+const HOURS:u8 = 0xA;
+const DAYS_LOW:u8 = 0xB;
+const DAYS_HIGH:u8 = 0xC;
+
+fn build_mbc3(clock_source:RtcClockSource, save_data:Option<Vec<u8>>)->Mbc3{
+    let mut program = vec![0;0x8000];
+    program[MBC_RAM_SIZE_LOCATION] = RAM_SIZE_8KB;
+    let mut mbc = Mbc3::new(program, true, Some(clock_source), save_data).unwrap();
+    mbc.write_rom(0, 0xA);
+
+    return mbc;
+}
+
+fn latch(mbc:&mut Mbc3){
+    mbc.write_rom(0x6000, 0);
+    mbc.write_rom(0x6000, 1);
+}
+
+fn read_rtc(mbc:&mut Mbc3, register:u8)->u8{
+    mbc.write_rom(0x4000, register);
+    return mbc.read_external_ram(0);
+}
+
+fn write_rtc(mbc:&mut Mbc3, register:u8, value:u8){
+    mbc.write_rom(0x4000, register);
+    mbc.write_external_ram(0, value);
+}
+
+fn run_seconds(mbc:&mut Mbc3, seconds:u32){
+    for _ in 0..(seconds * M_CYCLES_PER_SECOND / 4){
+        mbc.cycle(4);
+    }
+}
+
+#[test]
+fn test_emulated_clock_advances_with_cycles(){
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, None);
+    run_seconds(&mut mbc, 2);
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 2);
+}
+
+#[test]
+fn test_latched_registers_do_not_change_until_next_latch(){
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, None);
+    run_seconds(&mut mbc, 1);
+    latch(&mut mbc);
+    run_seconds(&mut mbc, 1);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 1);
+
+    // only a 0 to 1 transition latches
+    mbc.write_rom(0x6000, 1);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 1);
+
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 2);
+}
+
+#[test]
+fn test_day_counter_overflow_sets_carry(){
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, None);
+    write_rtc(&mut mbc, SECONDS, 59);
+    write_rtc(&mut mbc, MINUTES, 59);
+    write_rtc(&mut mbc, HOURS, 23);
+    write_rtc(&mut mbc, DAYS_LOW, 0xFF);
+    write_rtc(&mut mbc, DAYS_HIGH, 0x1);
+
+    run_seconds(&mut mbc, 1);
+    latch(&mut mbc);
+
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 0);
+    assert_eq!(read_rtc(&mut mbc, MINUTES), 0);
+    assert_eq!(read_rtc(&mut mbc, HOURS), 0);
+    assert_eq!(read_rtc(&mut mbc, DAYS_LOW), 0);
+    assert_eq!(read_rtc(&mut mbc, DAYS_HIGH), 0x80);
+}
+
+#[test]
+fn test_out_of_range_seconds_overflow_without_carry(){
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, None);
+    write_rtc(&mut mbc, SECONDS, 63);
+    run_seconds(&mut mbc, 1);
+    latch(&mut mbc);
+
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 0);
+    assert_eq!(read_rtc(&mut mbc, MINUTES), 0);
+}
+
+#[test]
+fn test_halt_stops_the_clock(){
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, None);
+    write_rtc(&mut mbc, DAYS_HIGH, 0x40);
+    run_seconds(&mut mbc, 1);
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 0);
+
+    write_rtc(&mut mbc, DAYS_HIGH, 0);
+    run_seconds(&mut mbc, 1);
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 1);
+}
+
+#[test]
+fn test_rtc_footer_is_saved_with_the_ram(){
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, None);
+    mbc.write_rom(0x4000, 0);
+    mbc.write_external_ram(0x10, 0x42);
+    write_rtc(&mut mbc, MINUTES, 12);
+    write_rtc(&mut mbc, HOURS, 5);
+
+    let save_data = mbc.get_save_data();
+    assert_eq!(save_data.len(), RAM_SIZE + RTC_FOOTER_SIZE);
+
+    let mut mbc = build_mbc3(RtcClockSource::Emulated, Some(save_data));
+    assert_eq!(mbc.get_ram().len(), RAM_SIZE);
+    mbc.write_rom(0x4000, 0);
+    assert_eq!(mbc.read_external_ram(0x10), 0x42);
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, MINUTES), 12);
+    assert_eq!(read_rtc(&mut mbc, HOURS), 5);
+}
+
+#[test]
+fn test_wall_clock_adds_the_time_passed_since_saving(){
+    let mut save_data = vec![0;RAM_SIZE + RTC_FOOTER_SIZE];
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - (24 * 3600 + 3600 + 60 + 1);
+    save_data[RAM_SIZE + 40..].copy_from_slice(&timestamp.to_le_bytes());
+
+    let mut mbc = build_mbc3(RtcClockSource::WallClock, Some(save_data));
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, DAYS_LOW), 1);
+    assert_eq!(read_rtc(&mut mbc, HOURS), 1);
+    assert_eq!(read_rtc(&mut mbc, MINUTES), 1);
+    // a second might pass while the test runs
+    assert!((1..=2).contains(&read_rtc(&mut mbc, SECONDS)));
+}
+
+#[test]
+fn test_ram_without_footer_is_loaded(){
+    let mbc = build_mbc3(RtcClockSource::Emulated, Some(vec![0x11;RAM_SIZE]));
+    assert_eq!(mbc.get_ram(), &[0x11;RAM_SIZE][..]);
+}
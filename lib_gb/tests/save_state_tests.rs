@@ -0,0 +1,95 @@
+mod machine_stubs;
+
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, save_state::SaveStateError, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use crate::machine_stubs::*;
+
+const COUNTER_ADDRESS:u16 = 0xC000;
+
+// Draws a striped tile over the screen and keeps scrolling it using a counter in wram
+fn build_scrolling_rom()->Vec<u8>{
+    let code = [
+        0x3E, 0x00,         // ld a, 0
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x21, 0x00, 0x80,   // ld hl, 0x8000
+        0x3E, 0xFF,         // ld a, 0xFF
+        0x22, 0x22,         // ld (hl+), a x2
+        0xAF,               // xor a
+        0x22, 0x22,         // ld (hl+), a x2
+        0x3E, 0xE4,         // ld a, 0xE4
+        0xE0, 0x47,         // ldh (BGP), a
+        0x3E, 0x91,         // ld a, 0x91
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x21, 0x00, 0xC0,   // loop: ld hl, 0xC000
+        0x34,               // inc (hl)
+        0x7E,               // ld a, (hl)
+        0xE0, 0x42,         // ldh (SCY), a
+        0x18, 0xF7          // jr loop
+    ];
+
+    build_rom(&code)
+}
+
+fn run_frames(gameboy:&mut GameBoy<StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice>, frames:u32)->Vec<u32>{
+    let mut frame_buffer = Vec::new();
+    for _ in 0..frames{
+        frame_buffer = gameboy.cycle_frame().to_vec();
+    }
+
+    return frame_buffer;
+}
+
+#[test]
+fn test_load_state_resumes_the_same_execution(){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_scrolling_rom(), false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+
+    run_frames(&mut gameboy, 10);
+    let state = gameboy.save_state();
+
+    let expected_frame = run_frames(&mut gameboy, 5);
+    let expected_counter = gameboy.read_memory(COUNTER_ADDRESS);
+
+    gameboy.load_state(&state).unwrap();
+    let frame = run_frames(&mut gameboy, 5);
+
+    assert!(expected_frame == frame);
+    assert_eq!(expected_counter, gameboy.read_memory(COUNTER_ADDRESS));
+}
+
+#[test]
+fn test_load_state_rejects_other_versions(){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_scrolling_rom(), false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+
+    let mut state = gameboy.save_state();
+    state[4] = state[4].wrapping_sub(1);
+
+    assert!(matches!(gameboy.load_state(&state), Err(SaveStateError::UnsupportedVersion{..})));
+    assert_eq!(gameboy.load_state(&state[..2]), Err(SaveStateError::InvalidHeader));
+}
+
+#[test]
+fn test_load_state_rejects_other_cartridges(){
+    let mut other_rom = build_scrolling_rom();
+    other_rom[0x14D] = 0x12;
+    let mut other_mbc:Box<dyn Mbc> = Box::new(Rom::new(other_rom, false, None).unwrap());
+    let state = GameBoy::new(&mut other_mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice).save_state();
+
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_scrolling_rom(), false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+
+    assert_eq!(gameboy.load_state(&state), Err(SaveStateError::CartridgeMismatch));
+}
+
+#[test]
+fn test_failed_load_state_keeps_the_machine_state(){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_scrolling_rom(), false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+
+    let state = gameboy.save_state();
+    run_frames(&mut gameboy, 3);
+    let counter = gameboy.read_memory(COUNTER_ADDRESS);
+
+    assert_eq!(gameboy.load_state(&state[..state.len() - 1]), Err(SaveStateError::UnexpectedEnd));
+    assert_eq!(counter, gameboy.read_memory(COUNTER_ADDRESS));
+}
@@ -0,0 +1,207 @@
+mod machine_stubs;
+
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, save_state::*, serial::{
+    capture_serial_device::CaptureSerialDevice, 
+    disconnected_serial_device::DisconnectedSerialDevice, 
+    gb_serial::GbSerial,
+    serial_device::SerialDevice,
+    serial_register_updater::*
+}};
+use crate::machine_stubs::*;
+
+const SERIAL_INTERRUPT_MASK:u8 = 1 << 3;
+const M_CYCLES_PER_NORMAL_TRANSFER:u32 = 1024;
+
+struct ReplyingSerialDevice{
+    reply:u8,
+    pending_external_transfer:bool
+}
+
+impl SerialDevice for ReplyingSerialDevice{
+    fn start_transfer(&mut self, _data:u8)->Option<u8> {
+        Some(self.reply)
+    }
+
+    fn poll_external_clock(&mut self, _data:u8)->Option<u8> {
+        if self.pending_external_transfer{
+            self.pending_external_transfer = false;
+            return Some(self.reply);
+        }
+
+        return None;
+    }
+}
+
+// Knows the byte of the other side only after being polled a few times, like a link cable waiting for a reply
+struct SlowSerialDevice{
+    reply:u8,
+    polls_left:u32
+}
+
+impl SerialDevice for SlowSerialDevice{
+    fn start_transfer(&mut self, _data:u8)->Option<u8> {
+        None
+    }
+
+    fn poll_transfer(&mut self)->Option<u8> {
+        if self.polls_left == 0{
+            return Some(self.reply);
+        }
+        self.polls_left -= 1;
+        return None;
+    }
+
+    fn poll_external_clock(&mut self, _data:u8)->Option<u8> {
+        None
+    }
+}
+
+fn cycle_serial<SD:SerialDevice>(serial:&mut GbSerial<SD>, if_register:&mut u8, m_cycles:u32){
+    for _ in 0..m_cycles{
+        serial.cycle(if_register, 1);
+    }
+}
+
+#[test]
+fn test_internal_clock_transfer_takes_8_bits(){
+    let mut serial = GbSerial::new(ReplyingSerialDevice{reply:0xA5, pending_external_transfer:false});
+    let mut if_register = 0;
+    set_sb(&mut serial, 0x3C);
+    set_sc(&mut serial, 0x81);
+
+    cycle_serial(&mut serial, &mut if_register, M_CYCLES_PER_NORMAL_TRANSFER - 1);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, 0);
+    assert_eq!(get_sc(&serial) & 0x80, 0x80);
+
+    cycle_serial(&mut serial, &mut if_register, 1);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, SERIAL_INTERRUPT_MASK);
+    assert_eq!(get_sb(&serial), 0xA5);
+    assert_eq!(get_sc(&serial), 0x7F);
+}
+
+#[test]
+fn test_internal_clock_transfer_waits_for_the_device(){
+    let mut serial = GbSerial::new(SlowSerialDevice{reply:0x5A, polls_left:3});
+    let mut if_register = 0;
+    set_sb(&mut serial, 0x3C);
+    set_sc(&mut serial, 0x81);
+
+    // the device is polled once per bit period (4 polls) and the 8 bits are shifted only after the reply
+    cycle_serial(&mut serial, &mut if_register, M_CYCLES_PER_NORMAL_TRANSFER / 2 + M_CYCLES_PER_NORMAL_TRANSFER - 1);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, 0);
+
+    cycle_serial(&mut serial, &mut if_register, 1);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, SERIAL_INTERRUPT_MASK);
+    assert_eq!(get_sb(&serial), 0x5A);
+}
+
+#[test]
+fn test_idle_internal_clock_port_does_not_overflow(){
+    let mut serial = GbSerial::new(ReplyingSerialDevice{reply:0xA5, pending_external_transfer:false});
+    let mut if_register = 0;
+    set_sc(&mut serial, 0x81);
+    cycle_serial(&mut serial, &mut if_register, M_CYCLES_PER_NORMAL_TRANSFER);
+    assert_eq!(get_sc(&serial), 0x7F);
+
+    // SC keeps the internal clock after the transfer, more than 2^32 t_cycles pass while it is idle
+    for _ in 0..(1u32 << 30) / 0x100 + 1{
+        serial.cycle(&mut if_register, 0xFF);
+        serial.cycle(&mut if_register, 1);
+    }
+
+    if_register = 0;
+    set_sc(&mut serial, 0x81);
+    cycle_serial(&mut serial, &mut if_register, M_CYCLES_PER_NORMAL_TRANSFER - 1);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, 0);
+    cycle_serial(&mut serial, &mut if_register, 1);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, SERIAL_INTERRUPT_MASK);
+}
+
+#[test]
+fn test_cgb_fast_clock_transfer(){
+    let mut serial = GbSerial::new(ReplyingSerialDevice{reply:0x11, pending_external_transfer:false});
+    serial.cgb_mode = true;
+    let mut if_register = 0;
+    set_sc(&mut serial, 0x83);
+
+    cycle_serial(&mut serial, &mut if_register, 32);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, SERIAL_INTERRUPT_MASK);
+    assert_eq!(get_sb(&serial), 0x11);
+}
+
+#[test]
+fn test_fast_clock_is_ignored_on_dmg(){
+    let mut serial = GbSerial::new(DisconnectedSerialDevice);
+    let mut if_register = 0;
+    set_sc(&mut serial, 0x83);
+
+    cycle_serial(&mut serial, &mut if_register, 32);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, 0);
+    assert_eq!(get_sc(&serial), 0xFF);
+}
+
+#[test]
+fn test_external_clock_transfer(){
+    let mut serial = GbSerial::new(ReplyingSerialDevice{reply:0x42, pending_external_transfer:true});
+    let mut if_register = 0;
+    set_sb(&mut serial, 0x10);
+    set_sc(&mut serial, 0x80);
+
+    cycle_serial(&mut serial, &mut if_register, 128);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, SERIAL_INTERRUPT_MASK);
+    assert_eq!(get_sb(&serial), 0x42);
+}
+
+#[test]
+fn test_disconnected_external_clock_never_completes(){
+    let mut serial = GbSerial::new(DisconnectedSerialDevice);
+    let mut if_register = 0;
+    set_sc(&mut serial, 0x80);
+
+    cycle_serial(&mut serial, &mut if_register, M_CYCLES_PER_NORMAL_TRANSFER * 4);
+    assert_eq!(if_register & SERIAL_INTERRUPT_MASK, 0);
+    assert_eq!(get_sc(&serial) & 0x80, 0x80);
+}
+
+#[test]
+fn test_capture_serial_output_from_rom(){
+    // Sends every byte of the message at 0x200 over the serial port and waits for each transfer to finish
+    let code = [
+        0x21, 0x00, 0x02,   // ld hl, 0x200
+        0x2A,               // next: ld a, (hl+)
+        0xB7,               // or a
+        0x28, 0xFE,         // done: jr z, done
+        0xE0, 0x01,         // ldh (SB), a
+        0x3E, 0x81,         // ld a, 0x81
+        0xE0, 0x02,         // ldh (SC), a
+        0xF0, 0x02,         // wait: ldh a, (SC)
+        0x87,               // add a, a
+        0x38, 0xFB,         // jr c, wait
+        0x18, 0xEF          // jr next
+    ];
+    let mut rom = build_rom(&code);
+    let message = b"Passed\n\0";
+    rom[0x200..0x200 + message.len()].copy_from_slice(message);
+
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mut gameboy = GameBoy::new(&mut mbc, StubJoypadProvider, StubAudioDevice, CaptureSerialDevice::default());
+    for _ in 0..10{
+        gameboy.cycle_frame();
+    }
+
+    assert_eq!(gameboy.get_serial_device().get_output(), "Passed\n");
+}
+
+#[test]
+fn test_load_state_rejects_an_internal_clock_transfer_without_bits_left(){
+    let mut serial = GbSerial::new(DisconnectedSerialDevice);
+    let mut writer = StateWriter::default();
+    serial.save_state(&mut writer);
+    let mut state = writer.into_buffer();
+    // sb, transfer enabled, internal clock, fast clock, cgb mode and bits left
+    state[1] = 1;
+    state[2] = 1;
+    state[5] = 0;
+
+    assert_eq!(serial.load_state(&mut StateReader::new(&state)), Err(SaveStateError::InvalidValue("serial bits left")));
+}
@@ -0,0 +1,228 @@
+mod machine_stubs;
+
+use lib_gb::{machine::{gameboy::GameBoy, model::Model}, mmu::{carts::{Mbc, Rom}, gb_mmu::BOOT_ROM_SIZE}, ppu::color::Color, save_state::SaveStateError,
+    serial::disconnected_serial_device::DisconnectedSerialDevice, sgb::gb_sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH}};
+use crate::machine_stubs::*;
+
+const SCREEN_X:usize = 48;
+const SCREEN_Y:usize = 40;
+const RED:u16 = 0x001F;
+const BLUE:u16 = 0x7C00;
+const WHITE:u16 = 0x7FFF;
+const BLACK:u32 = 0;
+
+// Pulses JOYP to send a 16 bytes packet (LSB first) followed by the stop bit
+fn send_packet(code:&mut Vec<u8>, packet:&[u8]){
+    let mut data = [0;16];
+    data[..packet.len()].copy_from_slice(packet);
+
+    code.extend_from_slice(&[0x3E, 0x00, 0xE0, 0x00, 0x3E, 0x30, 0xE0, 0x00]);
+    for byte in data.iter(){
+        for bit in 0..8{
+            let pulse = if byte & (1 << bit) != 0 {0x10} else {0x20};
+            code.extend_from_slice(&[0x3E, pulse, 0xE0, 0x00, 0x3E, 0x30, 0xE0, 0x00]);
+        }
+    }
+    code.extend_from_slice(&[0x3E, 0x20, 0xE0, 0x00, 0x3E, 0x30, 0xE0, 0x00]);
+}
+
+// Waits for LY to reach 144 and leave it
+fn wait_frame(code:&mut Vec<u8>){
+    code.extend_from_slice(&[0xF0, 0x44, 0xFE, 0x90, 0x20, 0xFA, 0xF0, 0x44, 0xFE, 0x90, 0x28, 0xFA]);
+}
+
+fn set_bgp(code:&mut Vec<u8>, value:u8){
+    code.extend_from_slice(&[0x3E, value, 0xE0, 0x47]);
+}
+
+fn palettes_packet(command:u8, first:[u16;4], second:[u16;3])->Vec<u8>{
+    let mut packet = vec![(command << 3) | 1];
+    for color in first.iter().chain(second.iter()){
+        packet.extend_from_slice(&color.to_le_bytes());
+    }
+    return packet;
+}
+
+fn rgb888(color:u16)->u32{
+    Color::from_rgb555(color, false).to_rgb888()
+}
+
+// Runs the code (ending with an endless loop) and returns the last frame and hram
+fn run_sgb(mut code:Vec<u8>, frames:u32)->(Vec<u32>, Vec<u8>){
+    code.extend_from_slice(&[0x18, 0xFE]);
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_rom(&code), false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_model(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, Model::SGB);
+    let mut frame = Vec::new();
+    for _ in 0..frames{
+        frame = gameboy.cycle_frame().to_vec();
+    }
+
+    return (frame, (0xFF80..0xFF90).map(|address|gameboy.read_memory(address)).collect());
+}
+
+fn get_pixel(frame:&[u32], x:usize, y:usize)->u32{
+    frame[y * SGB_SCREEN_WIDTH + x]
+}
+
+#[test]
+fn test_sgb_frame_includes_the_border(){
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_rom(&[0x18, 0xFE]), false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_model(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, Model::SGB);
+    assert_eq!(gameboy.get_frame_size(), (SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT));
+    assert_eq!(gameboy.cycle_frame().len(), SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT);
+
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_rom(&[0x18, 0xFE]), false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_model(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, Model::DMG);
+    assert_eq!(gameboy.get_frame_size(), (160, 144));
+    assert_eq!(gameboy.cycle_frame().len(), 160 * 144);
+}
+
+#[test]
+fn test_sgb_runs_the_commands_after_the_bootrom(){
+    // unmaps itself right away and leaves the lcd off
+    let mut boot_rom = [0;BOOT_ROM_SIZE];
+    boot_rom[0xFC..0x100].copy_from_slice(&[
+        0x3E, 0x01,         // ld a, 1
+        0xE0, 0x50          // ldh (BOOT), a
+    ]);
+    let mut code = vec![0x3E, 0x91, 0xE0, 0x40];
+    set_bgp(&mut code, 0xFF);
+    send_packet(&mut code, &palettes_packet(0x00, [WHITE, 0, 0, RED], [0, 0, BLUE]));
+    code.extend_from_slice(&[0x18, 0xFE]);
+
+    let mut mbc:Box<dyn Mbc> = Box::new(Rom::new(build_rom(&code), false, None).unwrap());
+    let mut gameboy = GameBoy::new_with_bootrom(&mut mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, boot_rom, Model::SGB);
+    assert_eq!(gameboy.get_frame_size(), (SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT));
+    let mut frame = Vec::new();
+    for _ in 0..3{
+        frame = gameboy.cycle_frame().to_vec();
+    }
+
+    assert_eq!(get_pixel(&frame, SCREEN_X, SCREEN_Y), rgb888(RED));
+}
+
+#[test]
+fn test_pal01_colors_the_screen(){
+    let mut code = Vec::new();
+    set_bgp(&mut code, 0xFF);
+    send_packet(&mut code, &palettes_packet(0x00, [WHITE, 0, 0, RED], [0, 0, BLUE]));
+
+    let (frame, _) = run_sgb(code, 3);
+
+    assert_eq!(get_pixel(&frame, 0, 0), rgb888(WHITE));
+    assert_eq!(get_pixel(&frame, SCREEN_X, SCREEN_Y), rgb888(RED));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 159, SCREEN_Y + 143), rgb888(RED));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 160, SCREEN_Y + 143), rgb888(WHITE));
+}
+
+#[test]
+fn test_attr_blk_selects_the_palette_of_tiles(){
+    let mut code = Vec::new();
+    set_bgp(&mut code, 0xFF);
+    send_packet(&mut code, &palettes_packet(0x00, [WHITE, 0, 0, RED], [0, 0, BLUE]));
+    // a block from tile (0, 0) to (5, 5) colored with palette 1 inside and on its border
+    send_packet(&mut code, &[(0x04 << 3) | 1, 1, 0b011, 0b0101, 0, 0, 5, 5]);
+
+    let (frame, _) = run_sgb(code, 3);
+
+    assert_eq!(get_pixel(&frame, SCREEN_X, SCREEN_Y), rgb888(BLUE));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 3 * 8, SCREEN_Y + 3 * 8), rgb888(BLUE));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 5 * 8 + 7, SCREEN_Y + 5 * 8 + 7), rgb888(BLUE));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 6 * 8, SCREEN_Y), rgb888(RED));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 10 * 8, SCREEN_Y + 10 * 8), rgb888(RED));
+}
+
+#[test]
+fn test_attr_div_splits_the_screen(){
+    let mut code = Vec::new();
+    set_bgp(&mut code, 0xFF);
+    send_packet(&mut code, &palettes_packet(0x00, [WHITE, 0, 0, RED], [0, 0, BLUE]));
+    // a horizontal line at tile 9, palette 0 before, palette 1 on and after it
+    send_packet(&mut code, &[(0x06 << 3) | 1, 0b0101_0001, 9]);
+
+    let (frame, _) = run_sgb(code, 3);
+
+    assert_eq!(get_pixel(&frame, SCREEN_X, SCREEN_Y + 8 * 8 + 7), rgb888(RED));
+    assert_eq!(get_pixel(&frame, SCREEN_X, SCREEN_Y + 9 * 8), rgb888(BLUE));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 159, SCREEN_Y + 143), rgb888(BLUE));
+}
+
+#[test]
+fn test_mask_en_blacks_the_screen(){
+    let mut code = Vec::new();
+    send_packet(&mut code, &palettes_packet(0x00, [WHITE, 0, 0, RED], [0, 0, BLUE]));
+    send_packet(&mut code, &[(0x17 << 3) | 1, 2]);
+
+    let (frame, _) = run_sgb(code, 3);
+
+    assert_eq!(get_pixel(&frame, SCREEN_X + 80, SCREEN_Y + 72), BLACK);
+    assert_eq!(get_pixel(&frame, 0, 0), rgb888(WHITE));
+}
+
+#[test]
+fn test_mlt_req_reads_the_controller_ids(){
+    let mut code = vec![
+        0x3E, 0x30, 0xE0, 0x00, // ld a, 0x30 ; ldh (JOYP), a
+        0xF0, 0x00, 0xE0, 0x80  // ldh a, (JOYP) ; ldh (0xFF80), a
+    ];
+    send_packet(&mut code, &[(0x11 << 3) | 1, 1]);
+    code.extend_from_slice(&[
+        0xF0, 0x00, 0xE0, 0x81, // ldh a, (JOYP) ; ldh (0xFF81), a
+        0x3E, 0x10, 0xE0, 0x00, // ld a, 0x10 ; ldh (JOYP), a
+        0x3E, 0x30, 0xE0, 0x00, // ld a, 0x30 ; ldh (JOYP), a
+        0xF0, 0x00, 0xE0, 0x82, // ldh a, (JOYP) ; ldh (0xFF82), a
+        0x3E, 0x10, 0xE0, 0x00,
+        0x3E, 0x30, 0xE0, 0x00,
+        0xF0, 0x00, 0xE0, 0x83  // ldh a, (JOYP) ; ldh (0xFF83), a
+    ]);
+
+    let (_, hram) = run_sgb(code, 2);
+
+    assert_eq!(hram[0] & 0xF, 0xF);
+    assert_eq!(hram[1] & 0xF, 0xF);
+    assert_eq!(hram[2] & 0xF, 0xE);
+    assert_eq!(hram[3] & 0xF, 0xF);
+}
+
+#[test]
+fn test_border_is_transferred_from_the_screen(){
+    let mut code = vec![
+        0xAF, 0xE0, 0x40,       // xor a ; ldh (LCDC), a
+        0x21, 0x00, 0x80,       // ld hl, 0x8000
+        0x3E, 0xFF              // ld a, 0xFF
+    ];
+    code.extend_from_slice(&[0x22;16]); // ld (hl+), a
+    code.extend_from_slice(&[0x3E, 0x91, 0xE0, 0x40]); // ld a, 0x91 ; ldh (LCDC), a
+    set_bgp(&mut code, 0xE4);
+    wait_frame(&mut code);
+    // every tile is color 15 and every map entry is tile 0xFF with palette 7 which is all white
+    send_packet(&mut code, &[(0x13 << 3) | 1, 1]);
+    for _ in 0..3{
+        wait_frame(&mut code);
+    }
+    send_packet(&mut code, &[(0x14 << 3) | 1]);
+    for _ in 0..3{
+        wait_frame(&mut code);
+    }
+    // the gameboy screen is black but the border is opaque everywhere
+    send_packet(&mut code, &palettes_packet(0x00, [0, 0, 0, 0], [0, 0, 0]));
+
+    let (frame, _) = run_sgb(code, 12);
+
+    assert_eq!(get_pixel(&frame, 0, 0), rgb888(WHITE));
+    assert_eq!(get_pixel(&frame, SCREEN_X + 80, SCREEN_Y + 72), rgb888(WHITE));
+}
+
+#[test]
+fn test_sgb_save_state_needs_a_sgb(){
+    let rom = build_rom(&[0x18, 0xFE]);
+    let mut sgb_mbc:Box<dyn Mbc> = Box::new(Rom::new(rom.clone(), false, None).unwrap());
+    let mut sgb = GameBoy::new_with_model(&mut sgb_mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, Model::SGB);
+    sgb.cycle_frame();
+    let state = sgb.save_state();
+    assert!(sgb.load_state(&state).is_ok());
+
+    let mut dmg_mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mut dmg = GameBoy::new_with_model(&mut dmg_mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice, Model::DMG);
+    assert_eq!(dmg.load_state(&state), Err(SaveStateError::InvalidValue("sgb")));
+}
@@ -0,0 +1,143 @@
+mod machine_stubs;
+
+use lib_gb::{machine::gameboy::GameBoy, mmu::carts::{Mbc, Rom}, serial::disconnected_serial_device::DisconnectedSerialDevice};
+use crate::machine_stubs::*;
+
+const CGB_FLAG_ADDRESS:usize = 0x143;
+const SOURCE_DATA_ADDRESS:usize = 0x4000;
+
+// Every test program starts by pointing the hdma from 0x4000 to 0x8000
+const SET_HDMA_ADDRESSES:[u8;16] = [
+    0x3E, 0x40,         // ld a, 0x40
+    0xE0, 0x51,         // ldh (HDMA1), a
+    0xAF,               // xor a
+    0xE0, 0x52,         // ldh (HDMA2), a
+    0x3E, 0x80,         // ld a, 0x80 - the upper bits are ignored
+    0xE0, 0x53,         // ldh (HDMA3), a
+    0xAF,               // xor a
+    0xE0, 0x54,         // ldh (HDMA4), a
+    0x00, 0x00          // nop nop
+];
+
+// Keeps copying HDMA5 to 0xFF81
+const HDMA5_LOOP:[u8;6] = [
+    0xF0, 0x55,         // ldh a, (HDMA5)
+    0xE0, 0x81,         // ldh (0xFF81), a
+    0x18, 0xFA          // jr -6
+];
+
+fn run_hdma_rom(cgb:bool, code:&[u8], frames:u32)->GameBoy<'static, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice>{
+    let mut program = SET_HDMA_ADDRESSES.to_vec();
+    program.extend_from_slice(code);
+    program.extend_from_slice(&HDMA5_LOOP);
+
+    let mut rom = build_rom(&program);
+    for i in 0..0x800{
+        rom[SOURCE_DATA_ADDRESS + i] = (i as u8).wrapping_add(1);
+    }
+    if cgb{
+        rom[CGB_FLAG_ADDRESS] = 0x80;
+    }
+
+    let mbc:Box<dyn Mbc> = Box::new(Rom::new(rom, false, None).unwrap());
+    let mbc = Box::leak(Box::new(mbc));
+    let mut gameboy = GameBoy::new(mbc, StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice);
+    for _ in 0..frames{
+        gameboy.cycle_frame();
+    }
+
+    return gameboy;
+}
+
+fn read_vram(gameboy:&GameBoy<StubJoypadProvider, StubAudioDevice, DisconnectedSerialDevice>, length:u16)->Vec<u8>{
+    (0x8000..0x8000 + length).map(|address|gameboy.read_memory(address)).collect()
+}
+
+fn expected_data(length:usize)->Vec<u8>{
+    (0..length).map(|i|(i as u8).wrapping_add(1)).collect()
+}
+
+#[test]
+fn test_general_purpose_dma_copies_all_blocks(){
+    let gameboy = run_hdma_rom(true, &[
+        0xAF,               // xor a
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x3E, 0x01,         // ld a, 1 - 2 blocks
+        0xE0, 0x55,         // ldh (HDMA5), a
+        0xF0, 0x55,         // ldh a, (HDMA5)
+        0xE0, 0x80,         // ldh (0xFF80), a
+    ], 1);
+
+    assert_eq!(gameboy.read_memory(0xFF80), 0xFF);
+    assert_eq!(read_vram(&gameboy, 0x20), expected_data(0x20));
+    assert_eq!(gameboy.read_memory(0x8020), 0);
+}
+
+#[test]
+fn test_general_purpose_dma_halts_the_cpu(){
+    let gameboy = run_hdma_rom(true, &[
+        0xAF,               // xor a
+        0xE0, 0x40,         // ldh (LCDC), a
+        0xE0, 0x05,         // ldh (TIMA), a
+        0x3E, 0x05,         // ld a, 5 - timer increments every 4 m cycles
+        0xE0, 0x07,         // ldh (TAC), a
+        0x3E, 0x0F,         // ld a, 0xF - 16 blocks
+        0xE0, 0x55,         // ldh (HDMA5), a
+        0xF0, 0x05,         // ldh a, (TIMA)
+        0xE0, 0x80,         // ldh (0xFF80), a
+    ], 1);
+
+    // 16 blocks of 8 m cycles
+    let tima = gameboy.read_memory(0xFF80);
+    assert!(tima >= 32 && tima <= 34, "tima: {}", tima);
+    assert_eq!(read_vram(&gameboy, 0x100), expected_data(0x100));
+}
+
+#[test]
+fn test_hblank_dma_copies_a_block_per_hblank(){
+    let gameboy = run_hdma_rom(true, &[
+        0x3E, 0x91,         // ld a, 0x91
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x3E, 0x87,         // ld a, 0x87 - 8 blocks on hblank
+        0xE0, 0x55,         // ldh (HDMA5), a
+        0xF0, 0x55,         // ldh a, (HDMA5)
+        0xE0, 0x80,         // ldh (0xFF80), a
+    ], 2);
+
+    assert_eq!(gameboy.read_memory(0xFF80), 0x07);
+    assert_eq!(gameboy.read_memory(0xFF81), 0xFF);
+    assert_eq!(read_vram(&gameboy, 0x80), expected_data(0x80));
+    assert_eq!(gameboy.read_memory(0x8080), 0);
+}
+
+#[test]
+fn test_hblank_dma_cancel(){
+    let gameboy = run_hdma_rom(true, &[
+        0xAF,               // xor a
+        0xE0, 0x40,         // ldh (LCDC), a - no hblanks while the lcd is off
+        0x3E, 0x83,         // ld a, 0x83 - 4 blocks on hblank
+        0xE0, 0x55,         // ldh (HDMA5), a
+        0xAF,               // xor a
+        0xE0, 0x55,         // ldh (HDMA5), a
+        0xF0, 0x55,         // ldh a, (HDMA5)
+        0xE0, 0x80,         // ldh (0xFF80), a
+        0x3E, 0x91,         // ld a, 0x91
+        0xE0, 0x40,         // ldh (LCDC), a
+    ], 2);
+
+    assert_eq!(gameboy.read_memory(0xFF80), 0x83);
+    assert_eq!(read_vram(&gameboy, 0x10), vec![0;0x10]);
+}
+
+#[test]
+fn test_hdma_is_unmapped_on_dmg(){
+    let gameboy = run_hdma_rom(false, &[
+        0xAF,               // xor a
+        0xE0, 0x40,         // ldh (LCDC), a
+        0x3E, 0x01,         // ld a, 1
+        0xE0, 0x55,         // ldh (HDMA5), a
+    ], 1);
+
+    assert_eq!(gameboy.read_memory(0xFF81), 0xFF);
+    assert_eq!(read_vram(&gameboy, 0x20), vec![0;0x20]);
+}